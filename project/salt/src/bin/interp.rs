@@ -0,0 +1,125 @@
+use salt::{diagnostic, eval, parser, types, utils::Lifetime, vm};
+use std::{env, fs, process, thread};
+
+const USAGE: &str = "usage: interp [--check-only] [--checked | --vm] [--leaks] [--trace <out.jsonl>] <program.salt>";
+
+// Enough for calls nested as deep as `eval::MAX_DEPTH` to hit that limit
+// before the tree-walker overflows the host's stack.
+const STACK_SIZE: usize = 256 << 20;
+
+fn main() {
+    let interp = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("cannot spawn the interpreter's thread");
+    if interp.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run() {
+    let mut check_only = false;
+    let mut checked = false;
+    let mut on_vm = false;
+    let mut leaks = false;
+    let mut trace_path = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check-only" => check_only = true,
+            "--checked" => checked = true,
+            "--vm" => on_vm = true,
+            "--leaks" => leaks = true,
+            "--trace" if trace_path.is_none() => match args.next() {
+                Some(out) => trace_path = Some(out),
+                None => {
+                    eprintln!("{}", USAGE);
+                    process::exit(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let (Some(path), false) = (path, on_vm && (checked || leaks || trace_path.is_some())) else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+
+    let src = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("error: cannot read {}: {}", path, e);
+        process::exit(1);
+    });
+
+    let program = parser::parse_program(&src).unwrap_or_else(|e| {
+        eprintln!("{}:{}", path, e);
+        process::exit(1);
+    });
+
+    let mut checker = types::Context::default();
+    let typed = checker.check_all(&program).unwrap_or_else(|errors| {
+        for e in &errors {
+            eprintln!("{}", diagnostic::render(e, &src, &path));
+        }
+        match errors.len() {
+            1 => eprintln!("error: aborting due to previous error"),
+            n => eprintln!("error: aborting due to {} previous errors", n),
+        }
+        process::exit(1);
+    });
+    if check_only {
+        return;
+    }
+
+    let mut ctxt = if checked {
+        eval::Context::checked()
+    } else {
+        eval::Context::default()
+    };
+    if leaks {
+        ctxt.memory = Some(eval::Allocations::default());
+    }
+    if trace_path.is_some() {
+        ctxt.start_tracing();
+    }
+    let result = if on_vm {
+        vm::compile(&typed.expr).run()
+    } else {
+        ctxt.eval_expr(&typed.expr, &Lifetime::global())
+    };
+    // written even when the run fails, to show the steps leading up to it
+    if let (Some(out), Some(trace)) = (&trace_path, &ctxt.trace) {
+        fs::write(out, trace.to_json_lines()).unwrap_or_else(|e| {
+            eprintln!("error: cannot write {}: {}", out, e);
+            process::exit(1);
+        });
+    }
+    let value = match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("runtime error: {}", e);
+            if e.is_unsafe() {
+                eprintln!(
+                    "note: the borrow checker accepted this program, so this is a checker bug"
+                );
+            }
+            process::exit(1);
+        }
+    };
+    println!("{}", value);
+    let errors = ctxt.memory_errors(&value);
+    for e in &errors {
+        eprintln!("{}: memory error: {}", path, e);
+    }
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+}
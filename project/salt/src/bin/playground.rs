@@ -0,0 +1,168 @@
+use salt::parser::{self, Error as ParseError};
+use salt::utils::{Expr, Lifetime, Stmt};
+use salt::{diagnostic, eval, types};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Enter salt statements, e.g. `let mut x = box 5;` or `*x = 3;`.
+A trailing expression is evaluated and printed.
+  :env     show the type environment
+  :store   show the evaluator's store
+  :reset   forget all bindings
+  :help    show this message
+  :quit    exit";
+
+struct Repl {
+    checker: types::Context,
+    ctxt: eval::Context,
+    /// Every complete input so far. Errors can point back into earlier
+    /// inputs, e.g. at where a place was borrowed, so spans count lines
+    /// from the start of the session.
+    session: String,
+}
+
+impl Repl {
+    fn new() -> Self {
+        Repl {
+            checker: types::Context::default(),
+            ctxt: eval::Context::default(),
+            session: String::new(),
+        }
+    }
+
+    /// Parse `input` as the lines that follow the session's so far.
+    fn parse(&self, input: &str) -> Result<(Vec<Stmt>, Expr), ParseError> {
+        let before = "\n".repeat(self.session.lines().count());
+        parser::parse_body(&(before + input))
+    }
+
+    fn dump_env(&self) {
+        let mut vars: Vec<_> = self.checker.env.0.iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        for (x, slot) in vars {
+            println!("{}: {} (lifetime {})", x, slot.tipe, slot.lifetime.0);
+        }
+    }
+
+    fn dump_store(&self) {
+        let store = &self.ctxt.store;
+        let mut vars: Vec<_> = store.frames[0].vars.iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        for (x, slot) in vars {
+            match &slot.value {
+                Some(v) => println!("{} = {} (lifetime {})", x, v, slot.lifetime.0),
+                None => println!("{} = <moved> (lifetime {})", x, slot.lifetime.0),
+            }
+        }
+        for (i, value) in store.heap.iter() {
+            let loc = eval::Location::new(eval::Root::Heap(i));
+            match value {
+                Some(v) => println!("{} = {}", loc, v),
+                None => println!("{} = <moved>", loc),
+            }
+        }
+    }
+
+    /// Check the whole input against a copy of the environment first, and
+    /// keep the new environment only once it has run, so a type or runtime
+    /// error leaves the session untouched.
+    fn run(&mut self, stmts: Vec<Stmt>, final_e: Expr) -> Result<(), String> {
+        let mut checker = self.checker.clone();
+        let render = |e: types::Error| diagnostic::render(&e, &self.session, "<input>");
+        let stmts = checker.elaborate_stmts(&stmts).map_err(render)?;
+        let final_e = checker.elaborate(&final_e).map_err(render)?.expr;
+
+        let store = self.ctxt.store.clone();
+        let fns = self.ctxt.fns.clone();
+        match self.eval(&stmts, &final_e) {
+            Ok(value) => {
+                self.checker = checker;
+                if let Some(value) = value {
+                    println!("{}", value);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.ctxt.store = store;
+                self.ctxt.fns = fns;
+                Err(format!("runtime error: {}", e))
+            }
+        }
+    }
+
+    /// Run checked input, with the value of its trailing expression if it
+    /// has one.
+    fn eval(&mut self, stmts: &[Stmt], final_e: &Expr) -> eval::EvalResult<Option<eval::Value>> {
+        let l = Lifetime::global();
+        self.ctxt.define_fns(stmts);
+        for stmt in stmts {
+            self.ctxt.eval_stmt(stmt, &l)?;
+        }
+        if *final_e == Expr::Unit {
+            return Ok(None);
+        }
+        self.ctxt.eval_expr(final_e, &l).map(Some)
+    }
+}
+
+fn main() {
+    let mut repl = Repl::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut buf = String::new();
+
+    loop {
+        print!("{}", if buf.is_empty() { "salt> " } else { "  ... " });
+        io::stdout().flush().ok();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        if buf.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" | ":q" => break,
+                ":help" => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                ":env" => {
+                    repl.dump_env();
+                    continue;
+                }
+                ":store" => {
+                    repl.dump_store();
+                    continue;
+                }
+                ":reset" => {
+                    repl = Repl::new();
+                    continue;
+                }
+                cmd if cmd.starts_with(':') => {
+                    println!("unknown command `{}`, try :help", cmd);
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        buf.push_str(&line);
+        buf.push('\n');
+        let parsed = repl.parse(&buf);
+        // keep reading until the input is complete
+        if let Err(ParseError::UnexpectedEof(_)) = parsed {
+            continue;
+        }
+        repl.session.push_str(&buf);
+        buf.clear();
+        match parsed {
+            Err(e) => println!("parse error: {}", e),
+            Ok((stmts, final_e)) => {
+                if let Err(msg) = repl.run(stmts, final_e) {
+                    println!("{}", msg.trim_end());
+                }
+            }
+        }
+    }
+}
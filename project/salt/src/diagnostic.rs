@@ -0,0 +1,66 @@
+use crate::types::Error;
+use crate::utils::Span;
+
+/// An underlined range of a source line. The primary mark is drawn with
+/// `^`, secondary ones with `-`.
+struct Mark<'a> {
+    span: Span,
+    label: &'a str,
+    primary: bool,
+}
+
+/// Render `err` in the style of rustc, quoting the lines of `src` it points
+/// at. `file` is only used in the `-->` header.
+pub fn render(err: &Error, src: &str, file: &str) -> String {
+    let mut out = format!("error: {}\n", err);
+    let Some(span) = err.span() else {
+        return out;
+    };
+
+    let mut marks = vec![Mark {
+        span,
+        label: "",
+        primary: true,
+    }];
+    for (span, label) in err.labels() {
+        marks.push(Mark {
+            span: *span,
+            label,
+            primary: false,
+        });
+    }
+    marks.sort_by_key(|m| m.span.start);
+
+    let lines: Vec<&str> = src.lines().collect();
+    let width = marks.last().unwrap().span.start.line.to_string().len();
+    let pad = " ".repeat(width);
+    out += &format!("{}--> {}:{}\n", pad, file, span.start);
+    out += &format!("{} |\n", pad);
+
+    let mut prev_line = 0;
+    for mark in &marks {
+        let (line, col) = (mark.span.start.line, mark.span.start.col as usize);
+        let text = lines.get(line as usize - 1).copied().unwrap_or("");
+        if line != prev_line {
+            out += &format!("{:>w$} | {}\n", line, text, w = width);
+            prev_line = line;
+        }
+        // spans running past the line are underlined to its end
+        let len = if mark.span.end.line == line {
+            (mark.span.end.col as usize).saturating_sub(col)
+        } else {
+            text.chars().count().saturating_sub(col - 1)
+        };
+        let underline = if mark.primary { "^" } else { "-" }.repeat(len.max(1));
+        let marked = format!(
+            "{} | {}{} {}",
+            pad,
+            " ".repeat(col - 1),
+            underline,
+            mark.label
+        );
+        out += marked.trim_end();
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,62 @@
+use crate::diagnostic::render;
+use crate::parser::parse_program;
+use crate::types::Context;
+
+fn diagnose(src: &str) -> String {
+    let e = parse_program(src).unwrap();
+    let err = Context::default().type_expr(&e).unwrap_err();
+    render(&err, src, "test.salt")
+}
+
+#[test]
+fn render_secondary_label() {
+    let src = "let mut x = box 1;\nlet mut r = &x;\nlet mut m = &mut x;\nr;";
+    assert_eq!(
+        diagnose(src),
+        "\
+error: cannot borrow `x` as mutable because it is also borrowed as immutable
+ --> test.salt:3:13
+  |
+2 | let mut r = &x;
+  |             -- immutable borrow occurs here
+3 | let mut m = &mut x;
+  |             ^^^^^^
+"
+    );
+}
+
+#[test]
+fn render_same_line() {
+    assert_eq!(
+        diagnose("let mut x = 1; let mut r = &mut x; let mut y = x; r;"),
+        "\
+error: cannot use `x` because it is mutably borrowed
+ --> test.salt:1:48
+  |
+1 | let mut x = 1; let mut r = &mut x; let mut y = x; r;
+  |                            ------ mutable borrow occurs here
+  |                                                ^
+"
+    );
+}
+
+#[test]
+fn render_lifetime_too_short_at_the_tail() {
+    assert_eq!(
+        diagnose("fn f(x: int) -> &int {\n    let mut y = x;\n    &x\n}"),
+        "\
+error: the borrow `&x` does not live long enough
+ --> test.salt:3:5
+  |
+3 |     &x
+  |     ^^
+"
+    );
+    assert_eq!(
+        Context::default()
+            .type_expr(&parse_program("let mut r = { let mut z = 1; &z };").unwrap())
+            .unwrap_err()
+            .to_string(),
+        "the borrow `&z` does not live long enough"
+    );
+}
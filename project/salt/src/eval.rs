@@ -1,152 +1,1094 @@
-use crate::utils::{Expr, Ident, Lifetime, Lval, Stmt};
-use std::collections::HashMap;
+use crate::oracle::{Access, Borrows, Fault, Tag};
+use crate::trace::{Changes, Trace};
+use crate::utils::{BinOp, Binding, Expr, FnDef, Ident, Lifetime, Lval, Pattern, Span, Stmt};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
 
-type Location = Ident;
 type Owned = bool;
+pub type FrameId = usize;
 
-#[derive(Debug, Clone)]
+/// A box's place in the heap arena. The generation tells it apart from the
+/// boxes that held the same cell before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Index {
+    pub cell: usize,
+    pub generation: u32,
+}
+
+/// What a location is part of: a variable of a call's frame, or a box.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Root {
+    Var(FrameId, Ident),
+    Heap(Index),
+}
+
+/// A root projected through tuple and enum `fields`. In a checked run, a
+/// reference's location also carries the tag of the borrow that made it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub root: Root,
+    pub fields: Vec<usize>,
+    pub tag: Option<Tag>,
+}
+
+impl Location {
+    pub fn new(root: Root) -> Self {
+        Location {
+            root,
+            fields: vec![],
+            tag: None,
+        }
+    }
+
+    /// Field `i` of the location, keeping its tag.
+    pub fn field(&self, i: usize) -> Self {
+        let mut loc = self.clone();
+        loc.fields.push(i);
+        loc
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.root {
+            Root::Var(0, x) => write!(f, "{}", x)?,
+            Root::Var(id, x) => write!(f, "{}@{}", x, id)?,
+            Root::Heap(i) => write!(f, "heap[{}]", i.cell)?,
+        }
+        for field in &self.fields {
+            write!(f, ".{}", field)?;
+        }
+        if let Some(tag) = self.tag {
+            write!(f, "#{}", tag)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Unit,
     Int(i32),
+    Bool(bool),
     Ref(Location, Owned),
+    Tuple(Vec<Pvalue>),
+    Enum(Ident, Ident, Vec<Pvalue>),
+}
+
+fn write_fields(f: &mut fmt::Formatter, vs: &[Pvalue]) -> fmt::Result {
+    for (i, v) in vs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match v {
+            Some(v) => write!(f, "{}", v)?,
+            None => write!(f, "<moved>")?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Ref(loc, true) => write!(f, "box@{}", loc),
+            Value::Ref(loc, false) => write!(f, "&{}", loc),
+            Value::Tuple(vs) => {
+                write!(f, "(")?;
+                write_fields(f, vs)?;
+                if vs.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Enum(name, variant, vs) => {
+                write!(f, "{}::{}", name, variant)?;
+                if !vs.is_empty() {
+                    write!(f, "(")?;
+                    write_fields(f, vs)?;
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 type Pvalue = Option<Value>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Slot {
     pub value: Pvalue,
     pub lifetime: Lifetime,
 }
 
-#[derive(Debug, Default)]
-pub struct Store(pub HashMap<Location, Slot>);
+/// An arena of boxes. A freed cell is reused, lowest first, under a new
+/// generation, so indices to the box it held no longer reach it.
+#[derive(Debug, Clone)]
+pub struct Heap<T> {
+    cells: Vec<Cell<T>>,
+    free: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Cell<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<T> Default for Heap<T> {
+    fn default() -> Self {
+        Heap {
+            cells: vec![],
+            free: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T> Heap<T> {
+    pub fn alloc(&mut self, value: T) -> Index {
+        let Some(cell) = self.free.pop_first() else {
+            self.cells.push(Cell {
+                generation: 0,
+                value: Some(value),
+            });
+            return Index {
+                cell: self.cells.len() - 1,
+                generation: 0,
+            };
+        };
+        self.cells[cell].value = Some(value);
+        Index {
+            cell,
+            generation: self.cells[cell].generation,
+        }
+    }
+
+    pub fn get(&self, i: Index) -> Option<&T> {
+        let cell = self.cells.get(i.cell)?;
+        (cell.generation == i.generation).then_some(cell.value.as_ref())?
+    }
+
+    pub fn get_mut(&mut self, i: Index) -> Option<&mut T> {
+        let cell = self.cells.get_mut(i.cell)?;
+        (cell.generation == i.generation).then_some(cell.value.as_mut())?
+    }
+
+    /// Take the box at `i` out of the heap, if it is still there.
+    pub fn free(&mut self, i: Index) -> Option<T> {
+        let cell = self.cells.get_mut(i.cell)?;
+        if cell.generation != i.generation {
+            return None;
+        }
+        let value = cell.value.take()?;
+        cell.generation += 1;
+        self.free.insert(i.cell);
+        Some(value)
+    }
+
+    /// The live boxes, lowest index first.
+    pub fn iter(&self) -> impl Iterator<Item = (Index, &T)> {
+        self.cells.iter().enumerate().filter_map(|(cell, c)| {
+            let i = Index {
+                cell,
+                generation: c.generation,
+            };
+            c.value.as_ref().map(|v| (i, v))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Heaps are equal when they hold the same boxes, whatever they freed.
+impl<T: PartialEq> PartialEq for Heap<T> {
+    fn eq(&self, other: &Heap<T>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+/// The variables of a call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub id: FrameId,
+    pub vars: HashMap<Ident, Slot>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    /// The call stack, the program's own frame first. Frame ids only ever
+    /// grow, so a reference into a call that returned dangles.
+    pub frames: Vec<Frame>,
+    pub heap: Heap<Pvalue>,
+    next_frame: FrameId,
+    /// The slots changed since the last step, collected only by traced runs.
+    pub changes: Option<Changes>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            frames: vec![Frame {
+                id: 0,
+                vars: HashMap::new(),
+            }],
+            heap: Heap::default(),
+            next_frame: 1,
+            changes: None,
+        }
+    }
+}
+
+/// Stores are equal when they hold the same values, whatever frame ids
+/// they have handed out.
+impl PartialEq for Store {
+    fn eq(&self, other: &Store) -> bool {
+        self.frames == other.frames && self.heap == other.heap
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    UnknownLocation(Lval),
+    UninitializedRead(Lval),
+    DanglingReference(Lval),
+    BadDeref(Lval),
+    UnknownFn(Ident),
+    Overflow(BinOp, i32, i32),
+    DivisionByZero(BinOp, i32),
+    NotAnInt(Value),
+    NotABool(Value),
+    NoArmMatches(Value),
+    /// A call nested deeper than the limit, see `Context::max_depth`.
+    RecursionLimit(usize),
+    /// Found by a checked run, see `Context::checked`.
+    AliasingViolation(Lval),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::UnknownLocation(lv) => write!(f, "unknown location `{}`", lv),
+            RuntimeError::UninitializedRead(lv) => {
+                write!(f, "`{}` is read after being moved out", lv)
+            }
+            RuntimeError::DanglingReference(lv) => {
+                write!(f, "`{}` goes through a dangling reference", lv)
+            }
+            RuntimeError::BadDeref(lv) => {
+                write!(f, "cannot dereference a non-reference in `{}`", lv)
+            }
+            RuntimeError::UnknownFn(name) => write!(f, "unknown function `{}`", name),
+            RuntimeError::Overflow(op, a, b) => {
+                write!(
+                    f,
+                    "attempt to compute `{} {} {}`, which overflows",
+                    a, op, b
+                )
+            }
+            RuntimeError::DivisionByZero(op, a) => {
+                write!(f, "attempt to compute `{} {} 0`, division by zero", a, op)
+            }
+            RuntimeError::NotAnInt(v) => write!(f, "expected an integer, found `{}`", v),
+            RuntimeError::NotABool(v) => write!(f, "expected a boolean, found `{}`", v),
+            RuntimeError::NoArmMatches(v) => write!(f, "no match arm matches `{}`", v),
+            RuntimeError::RecursionLimit(n) => {
+                write!(f, "calls nested more than {} deep", n)
+            }
+            RuntimeError::AliasingViolation(lv) => write!(
+                f,
+                "`{}` uses a reference invalidated by a conflicting access",
+                lv
+            ),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// `x` resolved to a location that is not in the store.
+    pub(crate) fn missing(x: &Lval) -> Self {
+        if x.derefs == 0 {
+            RuntimeError::UnknownLocation(x.clone())
+        } else {
+            RuntimeError::DanglingReference(x.clone())
+        }
+    }
+
+    /// Whether this breaks memory safety, which must never happen to a
+    /// program the borrow checker accepted.
+    pub fn is_unsafe(&self) -> bool {
+        matches!(
+            self,
+            RuntimeError::UninitializedRead(_)
+                | RuntimeError::DanglingReference(_)
+                | RuntimeError::AliasingViolation(_)
+        )
+    }
+}
+
+pub type EvalResult<T> = Result<T, RuntimeError>;
 
 impl Store {
-    pub fn locate(&self, w: &Lval) -> Location {
-        assert_eq!(w.derefs, 0, "Only base identifiers are supported in locate");
-        w.ident.clone()
+    fn top(&self) -> &Frame {
+        self.frames
+            .last()
+            .expect("the program's frame is never popped")
+    }
+
+    /// Set variable `x` of the current frame.
+    pub fn insert(&mut self, x: &str, value: Pvalue, lifetime: Lifetime) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("the program's frame is never popped");
+        let id = frame.id;
+        frame.vars.insert(x.to_string(), Slot { value, lifetime });
+        if let Some(changes) = &mut self.changes {
+            changes.create(Root::Var(id, x.to_string()));
+        }
+    }
+
+    /// The location of variable `x` of the current frame.
+    pub fn var(&self, x: &str) -> Location {
+        Location::new(Root::Var(self.top().id, x.to_string()))
+    }
+
+    /// Put `value` in a new box.
+    pub fn alloc(&mut self, value: Pvalue) -> Location {
+        let root = Root::Heap(self.heap.alloc(value));
+        if let Some(changes) = &mut self.changes {
+            changes.create(root.clone());
+        }
+        Location::new(root)
+    }
+
+    /// Enter a call, whose variables are kept apart from the caller's.
+    pub fn push_frame(&mut self) {
+        self.frames.push(Frame {
+            id: self.next_frame,
+            vars: HashMap::new(),
+        });
+        self.next_frame += 1;
+    }
+
+    /// Leave the current call, forgetting its variables.
+    pub fn pop_frame(&mut self) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    fn frame(&self, id: FrameId) -> Option<&Frame> {
+        let i = self.frames.binary_search_by_key(&id, |f| f.id).ok()?;
+        Some(&self.frames[i])
+    }
+
+    fn frame_mut(&mut self, id: FrameId) -> Option<&mut Frame> {
+        let i = self.frames.binary_search_by_key(&id, |f| f.id).ok()?;
+        Some(&mut self.frames[i])
+    }
+
+    /// Whether the variable or box `root` is still allocated.
+    pub fn contains(&self, root: &Root) -> bool {
+        match root {
+            Root::Var(id, x) => self.frame(*id).is_some_and(|f| f.vars.contains_key(x)),
+            Root::Heap(i) => self.heap.get(*i).is_some(),
+        }
+    }
+
+    /// The value at `loc`, ignoring its tag.
+    fn get(&self, loc: &Location) -> Option<&Pvalue> {
+        let mut v = match &loc.root {
+            Root::Var(id, x) => &self.frame(*id)?.vars.get(x)?.value,
+            Root::Heap(i) => self.heap.get(*i)?,
+        };
+        for f in &loc.fields {
+            match v {
+                Some(Value::Tuple(vs) | Value::Enum(_, _, vs)) => v = vs.get(*f)?,
+                _ => return None,
+            }
+        }
+        Some(v)
+    }
+
+    fn get_mut(&mut self, loc: &Location) -> Option<&mut Pvalue> {
+        let mut v = match &loc.root {
+            Root::Var(id, x) => &mut self.frame_mut(*id)?.vars.get_mut(x)?.value,
+            Root::Heap(i) => self.heap.get_mut(*i)?,
+        };
+        for f in &loc.fields {
+            match v {
+                Some(Value::Tuple(vs) | Value::Enum(_, _, vs)) => v = vs.get_mut(*f)?,
+                _ => return None,
+            }
+        }
+        Some(v)
+    }
+
+    /// Follow `w.derefs` references from `w.ident`'s field `w.fields` to the
+    /// location it names.
+    pub fn locate(&self, w: &Lval) -> EvalResult<Location> {
+        let mut loc = self.var(&w.ident);
+        loc.fields = w.fields.clone();
+        for i in 0..w.derefs {
+            let here = w.prefix(w.fields.len() + i);
+            loc = match self.get(&loc) {
+                Some(Some(Value::Ref(next, _))) => next.clone(),
+                Some(Some(_)) => return Err(RuntimeError::BadDeref(w.clone())),
+                Some(None) => return Err(RuntimeError::UninitializedRead(here)),
+                None => return Err(RuntimeError::missing(&here)),
+            };
+        }
+        Ok(loc)
     }
 
-    pub fn read(&self, x: &Lval) -> &Slot {
-        let loc = self.locate(x);
-        self.0
-            .get(&loc)
-            .expect("Attempted to read from unknown location")
+    pub fn read(&self, x: &Lval) -> EvalResult<&Pvalue> {
+        let loc = self.locate(x)?;
+        self.get(&loc).ok_or_else(|| RuntimeError::missing(x))
     }
 
-    pub fn write(&mut self, x: &Lval, v: Pvalue) -> Pvalue {
-        assert_eq!(x.derefs, 0, "Only base identifiers are supported in write");
-        let slot = self
-            .0
-            .get_mut(&x.ident)
-            .expect("Attempted to write to unknown location");
-        let old_val = slot.value.clone();
-        slot.value = v;
-        old_val
+    pub fn write(&mut self, x: &Lval, v: Pvalue) -> EvalResult<Pvalue> {
+        let loc = self.locate(x)?;
+        let old = self.get_mut(&loc).ok_or_else(|| RuntimeError::missing(x))?;
+        let old = std::mem::replace(old, v);
+        if let Some(changes) = &mut self.changes {
+            changes.write(loc.root);
+        }
+        Ok(old)
+    }
+
+    /// Move the value out of `loc`, if it is there.
+    fn take(&mut self, loc: &Location) -> Pvalue {
+        let old = self.get_mut(loc).and_then(Option::take);
+        if let Some(changes) = &mut self.changes {
+            changes.write(loc.root.clone());
+        }
+        old
+    }
+
+    /// Every variable of the current frame created with lifetime `l`, as
+    /// owning references so that `drop` frees them, in name order.
+    pub fn locs_by_lifetime(&self, l: Lifetime) -> Vec<Pvalue> {
+        let frame = self.top();
+        let mut vars: Vec<_> = frame
+            .vars
+            .iter()
+            .filter(|(_, slot)| slot.lifetime == l)
+            .map(|(x, _)| x)
+            .collect();
+        vars.sort();
+        vars.into_iter()
+            .map(|x| {
+                let loc = Location::new(Root::Var(frame.id, x.clone()));
+                Some(Value::Ref(loc, true))
+            })
+            .collect()
+    }
+
+    /// Free the targets of owning references, in order, following chains
+    /// of owned boxes all the way down. Borrowed references are left alone.
+    /// Returns the boxes that had been freed already.
+    pub fn drop(&mut self, values: Vec<Pvalue>) -> Vec<Index> {
+        let mut freed_before = vec![];
+        let mut pending: Vec<_> = values.into_iter().rev().collect();
+        while let Some(pval) = pending.pop() {
+            match pval {
+                Some(Value::Ref(loc, true)) => {
+                    let freed = match &loc.root {
+                        Root::Var(id, x) => self
+                            .frame_mut(*id)
+                            .and_then(|f| f.vars.remove(x))
+                            .map(|slot| slot.value),
+                        Root::Heap(i) => {
+                            let freed = self.heap.free(*i);
+                            if freed.is_none() {
+                                freed_before.push(*i);
+                            }
+                            freed
+                        }
+                    };
+                    if let (Some(changes), Some(_)) = (&mut self.changes, &freed) {
+                        changes.drop(loc.root);
+                    }
+                    pending.extend(freed);
+                }
+                Some(Value::Tuple(vs) | Value::Enum(_, _, vs)) => pending.extend(vs),
+                _ => {}
+            }
+        }
+        freed_before
     }
 
-    pub fn drop(&mut self, values: Vec<Pvalue>) {
-        for pval in values {
-            if let Some(Value::Ref(loc, _owned)) = pval {
-                self.0.remove(&loc);
+    /// The boxes that some variable or `result` reaches, through references
+    /// of any kind.
+    pub fn reachable(&self, result: &Value) -> HashSet<Index> {
+        let result = Some(result.clone());
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&Pvalue> = self
+            .frames
+            .iter()
+            .flat_map(|f| f.vars.values().map(|slot| &slot.value))
+            .chain([&result])
+            .collect();
+        while let Some(pval) = pending.pop() {
+            match pval {
+                Some(Value::Ref(loc, _)) => {
+                    if let Root::Heap(i) = loc.root {
+                        if seen.insert(i) {
+                            pending.extend(self.heap.get(i));
+                        }
+                    }
+                }
+                Some(Value::Tuple(vs) | Value::Enum(_, _, vs)) => pending.extend(vs),
+                _ => {}
             }
         }
+        seen
+    }
+}
+
+/// Where each box was allocated, tracked only by runs looking for leaks.
+#[derive(Debug, Default)]
+pub struct Allocations {
+    sites: HashMap<Index, Span>,
+    freed_twice: Vec<Index>,
+    /// The span of the innermost expression being evaluated.
+    here: Span,
+}
+
+/// A box a run mishandled, by the span of the `box` that allocated it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryError {
+    /// Still on the heap once the program ends, though nothing reaches it.
+    Leak(Span),
+    /// Dropped again after it was freed.
+    DoubleFree(Span),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (site, what) = match self {
+            MemoryError::Leak(site) => (site, "is never freed"),
+            MemoryError::DoubleFree(site) => (site, "is freed twice"),
+        };
+        if site.is_known() {
+            write!(f, "box allocated at {} {}", site.start, what)
+        } else {
+            write!(f, "box allocated at an unknown site {}", what)
+        }
     }
 }
 
+/// How deep calls nest before a run fails, unless its context says
+/// otherwise.
+pub const MAX_DEPTH: usize = 1000;
+
+#[derive(Debug)]
 pub struct Context {
     pub store: Store,
-    pub counter: usize,
+    pub fns: HashMap<Ident, Rc<FnDef>>,
+    /// Borrow state, tracked only by checked runs.
+    pub borrows: Option<Borrows>,
+    /// Allocation sites, tracked only by runs looking for leaks.
+    pub memory: Option<Allocations>,
+    /// The steps of a traced run, see `start_tracing`.
+    pub trace: Option<Trace>,
+    /// How many calls may be in progress at once; the next one fails with
+    /// `RecursionLimit` rather than overflowing the host's stack.
+    pub max_depth: usize,
+}
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            store: Store::default(),
+            fns: HashMap::new(),
+            borrows: None,
+            memory: None,
+            trace: None,
+            max_depth: MAX_DEPTH,
+        }
+    }
 }
 impl Context {
-    pub fn eval_expr(&mut self, expr: &Expr, l: &Lifetime) -> Value {
+    /// A context that also tracks borrows as it runs, and fails with an
+    /// `AliasingViolation` when a reference is used after a conflicting
+    /// access, or with `DanglingReference` when its variable or box was
+    /// freed even if the name or heap cell has been allocated again.
+    pub fn checked() -> Self {
+        Context {
+            borrows: Some(Borrows::default()),
+            ..Context::default()
+        }
+    }
+
+    /// A context that also records where each box is allocated, so that
+    /// `memory_errors` can report the boxes a run leaked or freed twice.
+    pub fn tracking_memory() -> Self {
+        Context {
+            memory: Some(Allocations::default()),
+            ..Context::default()
+        }
+    }
+
+    /// A context that also records a `Trace` of the store after each
+    /// statement.
+    pub fn tracing() -> Self {
+        let mut ctxt = Context::default();
+        ctxt.start_tracing();
+        ctxt
+    }
+
+    /// Record a `Trace` from here on: after each statement, and once more
+    /// when the program's block has dropped its variables.
+    pub fn start_tracing(&mut self) {
+        self.trace = Some(Trace::default());
+        self.store.changes = Some(Changes::default());
+    }
+
+    /// For a run that tracks memory, every box still on the heap that
+    /// neither a variable nor the program's `result` reaches, then every box
+    /// dropped after it was freed. Meant for when the program has finished.
+    pub fn memory_errors(&self, result: &Value) -> Vec<MemoryError> {
+        let Some(memory) = &self.memory else {
+            return vec![];
+        };
+        let site = |i: &Index| memory.sites.get(i).copied().unwrap_or_default();
+        let reachable = self.store.reachable(result);
+        let mut errors: Vec<MemoryError> = self
+            .store
+            .heap
+            .iter()
+            .filter(|(i, _)| !reachable.contains(i))
+            .map(|(i, _)| MemoryError::Leak(site(&i)))
+            .collect();
+        errors.extend(
+            memory
+                .freed_twice
+                .iter()
+                .map(|i| MemoryError::DoubleFree(site(i))),
+        );
+        errors
+    }
+
+    /// In a checked run, record an `access` to `lval` and the reads of the
+    /// references it goes through, and return the location it names.
+    fn check(&mut self, lval: &Lval, access: Access) -> EvalResult<Location> {
+        let loc = self.store.locate(lval)?;
+        if self.borrows.is_none() {
+            return Ok(loc);
+        }
+        for i in 0..lval.derefs {
+            let here = self.store.locate(&lval.prefix(lval.fields.len() + i))?;
+            self.access(lval, &here, Access::Read)?;
+        }
+        self.access(lval, &loc, access)?;
+        Ok(loc)
+    }
+
+    /// In a checked run, record an `access` to the location `loc`, which
+    /// `lval` reaches.
+    fn access(&mut self, lval: &Lval, loc: &Location, access: Access) -> EvalResult<()> {
+        let Some(borrows) = &mut self.borrows else {
+            return Ok(());
+        };
+        borrows.access(loc, access).map_err(|fault| match fault {
+            Fault::Dangling => RuntimeError::DanglingReference(lval.clone()),
+            Fault::Invalidated => RuntimeError::AliasingViolation(lval.clone()),
+        })
+    }
+
+    /// Bind the fields of the value at `loc`, which `place` names, as
+    /// `bindings` say, in slots with lifetime `l`.
+    fn bind(
+        &mut self,
+        place: &Lval,
+        loc: &Location,
+        bindings: &[Binding],
+        l: &Lifetime,
+    ) -> EvalResult<()> {
+        for (i, binding) in bindings.iter().enumerate() {
+            let field = loc.field(i);
+            let (x, val) = match binding {
+                Binding::Ignore => continue,
+                Binding::Move(x, true) => {
+                    self.access(place, &field, Access::Read)?;
+                    (x, self.store.get(&field).cloned().flatten())
+                }
+                Binding::Move(x, false) => {
+                    self.access(place, &field, Access::Write)?;
+                    (x, self.store.take(&field))
+                }
+                Binding::Ref(x, is_mut) => {
+                    let access = if *is_mut { Access::Write } else { Access::Read };
+                    self.access(place, &field, access)?;
+                    let whole = self.store.get(&field).is_some_and(is_whole);
+                    let loc = match &mut self.borrows {
+                        Some(borrows) => borrows.retag(&field, *is_mut),
+                        None => field,
+                    };
+                    (x, whole.then_some(Value::Ref(loc, false)))
+                }
+            };
+            let val = val.ok_or_else(|| RuntimeError::UninitializedRead(place.clone()))?;
+            self.allocate(x, Some(val), l.clone());
+        }
+        Ok(())
+    }
+
+    /// A new variable `x` invalidates references to the one it replaces.
+    fn allocate(&mut self, x: &str, value: Pvalue, lifetime: Lifetime) {
+        if let Some(borrows) = &mut self.borrows {
+            borrows.free(&self.store.var(x).root);
+        }
+        self.store.insert(x, value, lifetime);
+    }
+
+    /// Drop the current frame's variables that were created with lifetime
+    /// `l`.
+    fn drop_locals(&mut self, l: &Lifetime) {
+        let to_drop = self.store.locs_by_lifetime(l.clone());
+        self.drop_values(to_drop);
+    }
+
+    /// Drop `values` as their owners going away would, noting any box a run
+    /// that tracks memory finds freed already.
+    fn drop_values(&mut self, values: Vec<Pvalue>) {
+        let freed_before = self.store.drop(values);
+        if let Some(memory) = &mut self.memory {
+            memory.freed_twice.extend(freed_before);
+        }
+        self.forget_freed();
+    }
+
+    /// In a checked run, leave references to freed memory dangling.
+    fn forget_freed(&mut self) {
+        if let Some(borrows) = &mut self.borrows {
+            borrows.free_unless(|root| self.store.contains(root));
+        }
+    }
+
+    pub fn define_fns(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::Fn(def) = stmt.peel() {
+                self.fns.insert(def.name.clone(), Rc::new(def.clone()));
+            }
+        }
+    }
+
+    // `l` is the lifetime of the enclosing block; only statements use it.
+    // Arms that need more than a few locals are methods of their own, which
+    // keeps the frame this recursion repeats small.
+    #[allow(clippy::only_used_in_recursion)]
+    pub fn eval_expr(&mut self, expr: &Expr, l: &Lifetime) -> EvalResult<Value> {
         match expr {
-            Expr::Int(n) => Value::Int(*n),
+            Expr::Int(n) => Ok(Value::Int(*n)),
+
+            Expr::Unit => Ok(Value::Unit),
+
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
 
-            Expr::Unit => Value::Unit,
+            Expr::Lval(lval, copyable) => self.eval_lval(lval, *copyable),
 
-            Expr::Lval(lval, _copyable) => {
-                let slot = self.store.read(lval);
-                slot.value
-                    .clone()
-                    .expect("Attempted to read an uninitialized value")
+            Expr::Tuple(elems) => Ok(Value::Tuple(self.eval_fields(elems, l)?)),
+
+            Expr::Box(inner) => self.eval_box(inner, l),
+
+            Expr::Borrow(lval, mutable) => self.eval_borrow(lval, *mutable),
+
+            Expr::Block(stmts, final_expr, block_lifetime) => {
+                self.eval_block(stmts, final_expr, block_lifetime, l)
             }
 
-            Expr::Box(inner) => {
-                let val = self.eval_expr(inner, l);
-                let loc = self.fresh_location();
+            Expr::BinOp(op, lhs, rhs) => self.eval_binop(*op, lhs, rhs, l),
 
-                self.store.0.insert(
-                    loc.clone(),
-                    Slot {
-                        value: Some(val),
-                        lifetime: l.clone(),
-                    },
-                );
+            Expr::If(cond, then_e, else_e) => {
+                if self.eval_bool(cond, l)? {
+                    self.eval_expr(then_e, l)
+                } else {
+                    self.eval_expr(else_e, l)
+                }
+            }
 
-                Value::Ref(loc, true)
+            Expr::While(cond, body) => {
+                while self.eval_bool(cond, l)? {
+                    self.eval_expr(body, l)?;
+                }
+                Ok(Value::Unit)
             }
 
-            Expr::Borrow(lval, _mutability) => {
-                let loc = self.store.locate(lval).clone();
-                Value::Ref(loc, false)
+            Expr::Call(name, args) => self.eval_call(name, args, l),
+
+            Expr::Variant(name, variant, args) => {
+                let vals = self.eval_fields(args, l)?;
+                Ok(Value::Enum(name.clone(), variant.clone(), vals))
             }
 
-            Expr::Block(stmts, final_expr, block_lifetime) => {
-                let pre_keys: Vec<_> = self.store.0.keys().cloned().collect();
+            Expr::Match(scrutinee, arms, match_lifetime) => {
+                self.eval_match(scrutinee, arms, match_lifetime, l)
+            }
 
-                for stmt in stmts {
-                    self.eval_stmt(stmt, &block_lifetime.clone());
-                }
+            Expr::Spanned(inner, span) => match self.memory {
+                Some(_) => self.eval_spanned(inner, *span, l),
+                None => self.eval_expr(inner, l),
+            },
+        }
+    }
+
+    fn eval_spanned(&mut self, inner: &Expr, span: Span, l: &Lifetime) -> EvalResult<Value> {
+        let Some(memory) = &mut self.memory else {
+            return self.eval_expr(inner, l);
+        };
+        let outer = std::mem::replace(&mut memory.here, span);
+        let result = self.eval_expr(inner, l);
+        if let Some(memory) = &mut self.memory {
+            memory.here = outer;
+        }
+        result
+    }
+
+    fn eval_fields(&mut self, exprs: &[Expr], l: &Lifetime) -> EvalResult<Vec<Option<Value>>> {
+        let mut vals = vec![];
+        for e in exprs {
+            vals.push(Some(self.eval_expr(e, l)?));
+        }
+        Ok(vals)
+    }
+
+    fn eval_binop(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, l: &Lifetime) -> EvalResult<Value> {
+        let a = self.eval_int(lhs, l)?;
+        let b = self.eval_int(rhs, l)?;
+        binop(op, a, b)
+    }
+
+    fn eval_lval(&mut self, lval: &Lval, copyable: bool) -> EvalResult<Value> {
+        let access = if copyable {
+            Access::Read
+        } else {
+            Access::Write
+        };
+        self.check(lval, access)?;
+        // a move leaves the place uninitialised, as the checker assumes, so
+        // its value has a single owner
+        let val = if copyable {
+            self.store.read(lval)?.clone()
+        } else {
+            self.store.write(lval, None)?
+        };
+        val.ok_or_else(|| RuntimeError::UninitializedRead(lval.clone()))
+    }
 
-                let result = self.eval_expr(final_expr, &block_lifetime.clone());
+    fn eval_box(&mut self, inner: &Expr, l: &Lifetime) -> EvalResult<Value> {
+        let site = self.memory.as_ref().map(|m| m.here);
+        let val = self.eval_expr(inner, l)?;
 
-                let post_keys: Vec<_> = self.store.0.keys().cloned().collect();
-                let new_keys: Vec<_> = post_keys
-                    .into_iter()
-                    .filter(|k| !pre_keys.contains(k))
-                    .collect();
+        // boxes live on the heap until their owner drops them
+        let loc = self.store.alloc(Some(val));
+        if let (Some(memory), Some(site), Root::Heap(i)) = (&mut self.memory, site, &loc.root) {
+            memory.sites.insert(*i, site);
+        }
+        Ok(Value::Ref(loc, true))
+    }
 
-                let to_drop = new_keys
-                    .into_iter()
-                    .filter_map(|k| self.store.0.get(&k).map(|slot| slot.value.clone()))
-                    .collect();
+    fn eval_borrow(&mut self, lval: &Lval, mutable: bool) -> EvalResult<Value> {
+        let access = if mutable { Access::Write } else { Access::Read };
+        let loc = self.check(lval, access)?;
+        let Some(borrows) = &mut self.borrows else {
+            return Ok(Value::Ref(loc, false));
+        };
+        // the checker never lets a moved-out place be borrowed
+        if !is_whole(self.store.get(&loc).unwrap_or(&None)) {
+            return Err(RuntimeError::UninitializedRead(lval.clone()));
+        }
+        Ok(Value::Ref(borrows.retag(&loc, mutable), false))
+    }
 
-                self.store.drop(to_drop);
+    fn eval_block(
+        &mut self,
+        stmts: &[Stmt],
+        final_expr: &Expr,
+        block_lifetime: &Lifetime,
+        l: &Lifetime,
+    ) -> EvalResult<Value> {
+        self.define_fns(stmts);
+        for stmt in stmts {
+            self.eval_stmt(stmt, block_lifetime)?;
+        }
 
-                result
+        let result = self.eval_expr(final_expr, block_lifetime)?;
+
+        self.drop_locals(block_lifetime);
+
+        // the program's own block is in no statement whose step would show
+        // what it dropped
+        if let (Some(trace), true) = (&mut self.trace, *l == Lifetime::global()) {
+            let span = final_expr.span().unwrap_or_default();
+            trace.record(span, block_lifetime, &mut self.store);
+        }
+
+        Ok(result)
+    }
+
+    fn eval_call(&mut self, name: &Ident, args: &[Expr], l: &Lifetime) -> EvalResult<Value> {
+        let def = self
+            .fns
+            .get(name)
+            .cloned()
+            .ok_or_else(|| RuntimeError::UnknownFn(name.clone()))?;
+        let mut vals = vec![];
+        for arg in args {
+            vals.push(self.eval_expr(arg, l)?);
+        }
+
+        // the program's own frame is not a call
+        if self.store.frames.len() > self.max_depth {
+            return Err(RuntimeError::RecursionLimit(self.max_depth));
+        }
+        self.store.push_frame();
+        for ((x, _), val) in def.params.iter().zip(vals) {
+            self.allocate(x, Some(val), def.lifetime.clone());
+        }
+        let result = self.eval_expr(&def.body, &def.lifetime);
+        self.drop_locals(&def.lifetime);
+        self.store.pop_frame();
+        self.forget_freed();
+
+        result
+    }
+
+    fn eval_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[(Pattern, Expr)],
+        match_lifetime: &Lifetime,
+        l: &Lifetime,
+    ) -> EvalResult<Value> {
+        // a place is matched where it is, as the checker assumes
+        let place = match scrutinee.peel() {
+            Expr::Lval(lval, _) => lval.clone(),
+            _ => {
+                let val = self.eval_expr(scrutinee, l)?;
+                let temp = format!("match'{}", match_lifetime.0);
+                self.allocate(&temp, Some(val), match_lifetime.clone());
+                Lval::new(&temp, 0)
             }
+        };
+        let loc = self.check(&place, Access::Read)?;
+        let val = match self.store.get(&loc) {
+            Some(Some(val)) => val.clone(),
+            _ => return Err(RuntimeError::UninitializedRead(place)),
+        };
+        let arm = arms.iter().find(|(pattern, _)| match (pattern, &val) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Variant(_, v, _), Value::Enum(_, w, _)) => v == w,
+            _ => false,
+        });
+        let Some((pattern, body)) = arm else {
+            return Err(RuntimeError::NoArmMatches(val));
+        };
+        if let Pattern::Variant(_, _, bindings) = pattern {
+            self.bind(&place, &loc, bindings, match_lifetime)?;
+        }
+        let result = self.eval_expr(body, l);
+        self.drop_locals(match_lifetime);
+        result
+    }
+
+    fn eval_int(&mut self, expr: &Expr, l: &Lifetime) -> EvalResult<i32> {
+        match self.eval_expr(expr, l)? {
+            Value::Int(n) => Ok(n),
+            other => Err(RuntimeError::NotAnInt(other)),
         }
     }
 
-    pub fn eval_stmt(&mut self, stmt: &Stmt, l: &Lifetime) {
+    fn eval_bool(&mut self, expr: &Expr, l: &Lifetime) -> EvalResult<bool> {
+        match self.eval_expr(expr, l)? {
+            Value::Bool(b) => Ok(b),
+            other => Err(RuntimeError::NotABool(other)),
+        }
+    }
+
+    pub fn eval_stmt(&mut self, stmt: &Stmt, l: &Lifetime) -> EvalResult<()> {
+        self.exec_stmt(stmt, l)?;
+        if let Some(trace) = &mut self.trace {
+            // definitions leave the store alone
+            if !matches!(stmt.peel(), Stmt::Fn(_) | Stmt::Enum(_)) {
+                trace.record(stmt.span().unwrap_or_default(), l, &mut self.store);
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt, l: &Lifetime) -> EvalResult<()> {
         match stmt {
             Stmt::LetMut(ident, expr) => {
-                let val = self.eval_expr(expr, &l.clone());
-                self.store.0.insert(
-                    ident.clone(),
-                    Slot {
-                        value: Some(val),
-                        lifetime: l.clone(),
-                    },
-                );
+                let val = self.eval_expr(expr, &l.clone())?;
+                self.allocate(ident, Some(val), l.clone());
             }
 
             Stmt::Assign(lval, expr) => {
-                let val = self.eval_expr(expr, &l.clone());
-                self.store.write(lval, Some(val));
+                let val = self.eval_expr(expr, &l.clone())?;
+                self.check(lval, Access::Write)?;
+                // the value overwritten goes, as if its owner were dropped
+                let old = self.store.write(lval, Some(val))?;
+                self.drop_values(vec![old]);
             }
 
             Stmt::Expr(expr) => {
-                let _ = self.eval_expr(expr, &l.clone());
+                self.eval_expr(expr, &l.clone())?;
             }
+
+            // defined when the enclosing block starts, and enums only matter
+            // to the checker
+            Stmt::Fn(_) | Stmt::Enum(_) => {}
+
+            Stmt::Spanned(inner, _) => self.exec_stmt(inner, l)?,
         }
+        Ok(())
     }
+}
 
-    fn fresh_location(&mut self) -> String {
-        let loc = format!("__box{}", self.counter);
-        self.counter += 1;
-        loc
+/// Whether `v` is initialised, fields of tuples and enums included.
+fn is_whole(v: &Pvalue) -> bool {
+    match v {
+        Some(Value::Tuple(vs) | Value::Enum(_, _, vs)) => vs.iter().all(is_whole),
+        Some(_) => true,
+        None => false,
     }
 }
+
+/// Checked integer arithmetic and comparisons.
+pub(crate) fn binop(op: BinOp, a: i32, b: i32) -> EvalResult<Value> {
+    let cmp = match op {
+        BinOp::Lt => a < b,
+        BinOp::Le => a <= b,
+        BinOp::Gt => a > b,
+        BinOp::Ge => a >= b,
+        BinOp::Eq => a == b,
+        BinOp::Ne => a != b,
+        _ => return arith(op, a, b).map(Value::Int),
+    };
+    Ok(Value::Bool(cmp))
+}
+
+fn arith(op: BinOp, a: i32, b: i32) -> EvalResult<i32> {
+    let checked = match op {
+        BinOp::Add => a.checked_add(b),
+        BinOp::Sub => a.checked_sub(b),
+        BinOp::Mul => a.checked_mul(b),
+        BinOp::Div | BinOp::Rem if b == 0 => return Err(RuntimeError::DivisionByZero(op, a)),
+        BinOp::Div => a.checked_div(b),
+        BinOp::Rem => a.checked_rem(b),
+        _ => unreachable!("comparisons are handled by `binop`"),
+    };
+    checked.ok_or(RuntimeError::Overflow(op, a, b))
+}
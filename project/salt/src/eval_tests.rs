@@ -0,0 +1,545 @@
+use crate::eval::{Context, MemoryError, Root, RuntimeError, Store, Value};
+use crate::parser::{parse_body, parse_program};
+use crate::types::{self, Type};
+use crate::utils::{BinOp, Expr, Lifetime, Lval, Pattern, Stmt};
+
+/// `src` annotated by the checker with which reads copy and which move.
+fn elaborate(src: &str) -> Expr {
+    let program = parse_program(src).unwrap();
+    types::Context::default().elaborate(&program).unwrap().expr
+}
+
+fn chain() -> Store {
+    // z -> y -> x -> 7
+    let mut store = Store::default();
+    store.insert("x", Some(Value::Int(7)), Lifetime::global());
+    store.insert(
+        "y",
+        Some(Value::Ref(store.var("x"), false)),
+        Lifetime::global(),
+    );
+    store.insert(
+        "z",
+        Some(Value::Ref(store.var("y"), false)),
+        Lifetime::global(),
+    );
+    store
+}
+
+#[test]
+fn locate_derefs() {
+    let store = chain();
+    assert_eq!(store.locate(&Lval::new("z", 0)), Ok(store.var("z")));
+    assert_eq!(store.locate(&Lval::new("z", 1)), Ok(store.var("y")));
+    assert_eq!(store.locate(&Lval::new("z", 2)), Ok(store.var("x")));
+    assert_eq!(
+        store.locate(&Lval::new("z", 3)),
+        Err(RuntimeError::BadDeref(Lval::new("z", 3)))
+    );
+}
+
+#[test]
+fn read_write_derefs() {
+    let mut store = chain();
+    assert_eq!(store.read(&Lval::new("z", 2)), Ok(&Some(Value::Int(7))));
+    assert_eq!(
+        store.write(&Lval::new("z", 2), Some(Value::Int(5))),
+        Ok(Some(Value::Int(7)))
+    );
+    assert_eq!(store.read(&Lval::new("x", 0)), Ok(&Some(Value::Int(5))));
+}
+
+#[test]
+fn dangling_and_unknown() {
+    let mut store = chain();
+    store.frames[0].vars.remove("x");
+    assert_eq!(
+        store.read(&Lval::new("z", 2)),
+        Err(RuntimeError::DanglingReference(Lval::new("z", 2)))
+    );
+    assert_eq!(
+        store.write(&Lval::new("z", 2), None),
+        Err(RuntimeError::DanglingReference(Lval::new("z", 2)))
+    );
+    assert_eq!(
+        store.read(&Lval::new("w", 1)).map(|_| ()),
+        Err(RuntimeError::UnknownLocation(Lval::new("w", 0)))
+    );
+}
+
+#[test]
+fn eval_deref_assign() {
+    let prog = elaborate(
+        "let mut x = box box 1;
+             **x = 5;
+             let mut y = 0;
+             let mut r = &mut y;
+             *r = **x;
+             y",
+    );
+    let mut ctxt = Context::default();
+    assert_eq!(
+        ctxt.eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(5))
+    );
+}
+
+#[test]
+fn eval_errors() {
+    let mut ctxt = Context::default();
+    ctxt.store.insert("x", None, Lifetime::global());
+    ctxt.store
+        .insert("n", Some(Value::Int(3)), Lifetime::global());
+    ctxt.store.insert(
+        "r",
+        Some(Value::Ref(ctxt.store.var("gone"), false)),
+        Lifetime::global(),
+    );
+    let run = |ctxt: &mut Context, src: &str| {
+        let (stmts, e) = parse_body(src).unwrap();
+        for s in &stmts {
+            ctxt.eval_stmt(s, &Lifetime::global())?;
+        }
+        ctxt.eval_expr(&e, &Lifetime::global())
+    };
+    assert_eq!(
+        run(&mut ctxt, "x"),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        run(&mut ctxt, "let mut y = &*x;"),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        run(&mut ctxt, "*n = 4;"),
+        Err(RuntimeError::BadDeref(Lval::new("n", 1)))
+    );
+    assert_eq!(
+        run(&mut ctxt, "*r"),
+        Err(RuntimeError::DanglingReference(Lval::new("r", 1)))
+    );
+    assert_eq!(
+        run(&mut ctxt, "q = 1;"),
+        Err(RuntimeError::UnknownLocation(Lval::new("q", 0)))
+    );
+}
+
+#[test]
+fn drop_owned_chain() {
+    let mut store = Store::default();
+    let b2 = store.alloc(Some(Value::Ref(store.var("y"), false)));
+    let b1 = store.alloc(Some(Value::Ref(b2, true)));
+    store.insert("x", Some(Value::Ref(b1, true)), Lifetime(1));
+    store.insert("y", Some(Value::Int(1)), Lifetime(2));
+    store.drop(store.locs_by_lifetime(Lifetime(1)));
+    let mut store_2 = Store::default();
+    store_2.insert("y", Some(Value::Int(1)), Lifetime(2));
+    assert_eq!(store, store_2);
+}
+
+#[test]
+fn heap_reuses_cells_under_new_generations() {
+    let mut store = Store::default();
+    let a = store.alloc(Some(Value::Int(1)));
+    store.insert("r", Some(Value::Ref(a.clone(), false)), Lifetime(1));
+    store.drop(vec![Some(Value::Ref(a.clone(), true))]);
+    let b = store.alloc(Some(Value::Int(2)));
+    let (Root::Heap(i), Root::Heap(j)) = (&a.root, &b.root) else {
+        panic!("expected boxes");
+    };
+    assert_eq!((i.cell, j.cell), (0, 0));
+    assert_ne!(i.generation, j.generation);
+    assert_eq!(
+        store.read(&Lval::new("r", 1)),
+        Err(RuntimeError::DanglingReference(Lval::new("r", 1)))
+    );
+    assert_eq!(store.heap.len(), 1);
+}
+
+#[test]
+fn boxes_dont_collide_with_variables() {
+    let prog = elaborate("let mut __box0 = 1; let mut b = box 2; __box0 + *b");
+    assert_eq!(
+        Context::default().eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(3))
+    );
+    // a reference into a call that returned dangles, even when another
+    // call has taken its place on the stack
+    let mut ctxt = Context::default();
+    ctxt.store.push_frame();
+    ctxt.store.insert("x", Some(Value::Int(1)), Lifetime(1));
+    let x = ctxt.store.var("x");
+    ctxt.store.pop_frame();
+    ctxt.store.push_frame();
+    ctxt.store.insert("x", Some(Value::Int(2)), Lifetime(1));
+    ctxt.store
+        .insert("r", Some(Value::Ref(x, false)), Lifetime(1));
+    assert_eq!(
+        ctxt.store.read(&Lval::new("r", 1)),
+        Err(RuntimeError::DanglingReference(Lval::new("r", 1)))
+    );
+}
+
+#[test]
+fn memory_errors_name_allocation_sites() {
+    let track = |prog: &Expr| {
+        let mut ctxt = Context::tracking_memory();
+        let result = ctxt.eval_expr(prog, &Lifetime::global()).unwrap();
+        let errors = ctxt.memory_errors(&result);
+        errors
+            .iter()
+            .map(MemoryError::to_string)
+            .collect::<Vec<_>>()
+    };
+    // nested boxes are freed with their owner, and a box in the result
+    // still has one
+    let clean = elaborate(
+        "let mut x = box box 1;
+             let mut t = (box 2, 3);
+             let mut y = box 4;
+             (**x, y)",
+    );
+    assert_eq!(track(&clean), Vec::<String>::new());
+    // assigning over a box drops it
+    let overwritten = elaborate(
+        "let mut x = box box 1;
+         x = box box 2;
+         **x",
+    );
+    assert_eq!(track(&overwritten), Vec::<String>::new());
+    // a box copied without the checker has two owners
+    let mut copied = parse_program("let mut x = box 1; let mut y = x; 0").unwrap();
+    if let Expr::Block(stmts, _, _) = &mut copied {
+        if let Stmt::LetMut(_, e) = stmts[1].peel_mut() {
+            *e = Expr::Lval(Lval::new("x", 0), true);
+        }
+    }
+    assert_eq!(track(&copied), ["box allocated at 1:13 is freed twice"]);
+    // and so does one assignments overwrite
+    let mut overwritten = parse_program("let mut x = box 1; let mut y = x; y = box 2; 0").unwrap();
+    if let Expr::Block(stmts, _, _) = &mut overwritten {
+        if let Stmt::LetMut(_, e) = stmts[1].peel_mut() {
+            *e = Expr::Lval(Lval::new("x", 0), true);
+        }
+    }
+    assert_eq!(
+        track(&overwritten),
+        ["box allocated at 1:13 is freed twice"]
+    );
+    assert_eq!(Context::default().memory_errors(&Value::Unit), []);
+}
+
+#[test]
+fn eval_block_drops() {
+    let mut ctxt = Context::default();
+    ctxt.store
+        .insert("y", Some(Value::Int(0)), Lifetime::global());
+    let mut checker = types::Context::default();
+    checker.env.insert("y", Type::Int, Lifetime::global());
+    let prog = checker
+        .elaborate(
+            &parse_program(
+                "let mut x = box box 2;
+                     { let mut r = &mut y; *r = **x; }
+                     y",
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .expr;
+    assert_eq!(
+        ctxt.eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(2))
+    );
+    let mut store_2 = Store::default();
+    store_2.insert("y", Some(Value::Int(2)), Lifetime::global());
+    assert_eq!(ctxt.store, store_2);
+}
+
+#[test]
+fn assign_drops_old_value() {
+    // an overwritten box goes with the boxes it owns, but not one that was
+    // moved out of the place first
+    let prog = elaborate(
+        "let mut x = box box 1;
+         x = box box 2;
+         *x = box 5;
+         let mut y = box 3;
+         let mut z = y;
+         y = box 4;
+         *y + **x + *z",
+    );
+    let mut ctxt = Context::default();
+    assert_eq!(
+        ctxt.eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(12))
+    );
+    assert!(ctxt.store.heap.is_empty());
+}
+
+#[test]
+fn eval_calls() {
+    let prog = elaborate(
+        "fn bump(r: &mut int, by: int) { *r = by; }
+             fn pick(a: &int, b: &int) -> &int { a }
+             let mut x = 1;
+             let mut y = 5;
+             bump(&mut x, 7);
+             let mut p = pick(&y, &x);
+             *p",
+    );
+    let mut ctxt = Context::default();
+    assert_eq!(
+        ctxt.eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(5))
+    );
+    assert_eq!(ctxt.store, Store::default());
+    assert_eq!(
+        Context::default().eval_expr(&parse_program("g(1)").unwrap(), &Lifetime::global()),
+        Err(RuntimeError::UnknownFn(String::from("g")))
+    );
+}
+
+#[test]
+fn call_frames_separate() {
+    let prog = elaborate(
+        "fn f(x: int) -> int { let mut y = x; y }
+             let mut x = 1;
+             let mut y = 2;
+             f(9)",
+    );
+    let mut ctxt = Context::default();
+    assert_eq!(
+        ctxt.eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(9))
+    );
+}
+
+#[test]
+fn call_depth_limit() {
+    let depth = |n| {
+        elaborate(&format!(
+            "fn f(n: int) -> int {{ if n == 0 {{ 0 }} else {{ 1 + f(n - 1) }} }} f({})",
+            n
+        ))
+    };
+    let limited = || Context {
+        max_depth: 10,
+        ..Context::default()
+    };
+    // `f(9)` makes ten calls, one of them at a time
+    assert_eq!(
+        limited().eval_expr(&depth(9), &Lifetime::global()),
+        Ok(Value::Int(9))
+    );
+    assert_eq!(
+        limited().eval_expr(&depth(10), &Lifetime::global()),
+        Err(RuntimeError::RecursionLimit(10))
+    );
+    // calls made one after another don't add up
+    let prog = elaborate(
+        "fn f(n: int) -> int { n + 1 }
+             let mut i = 0;
+             while i < 20 { i = f(i); }
+             i",
+    );
+    assert_eq!(
+        limited().eval_expr(&prog, &Lifetime::global()),
+        Ok(Value::Int(20))
+    );
+}
+
+#[test]
+fn eval_arith() {
+    let eval = |src: &str| Context::default().eval_expr(&elaborate(src), &Lifetime::global());
+    // ill-typed programs can't be elaborated, but still fail at runtime
+    let eval_unchecked =
+        |src: &str| Context::default().eval_expr(&parse_program(src).unwrap(), &Lifetime::global());
+    assert_eq!(
+        eval("let mut x = box 7; (*x + 1) * 2 - 10 / 3 % 2"),
+        Ok(Value::Int(15))
+    );
+    assert_eq!(
+        eval_unchecked("(1 < 2) + 1"),
+        Err(RuntimeError::NotAnInt(Value::Bool(true)))
+    );
+    assert_eq!(
+        eval("2147483647 + 1"),
+        Err(RuntimeError::Overflow(BinOp::Add, i32::MAX, 1))
+    );
+    assert_eq!(
+        eval("-2147483648 / -1"),
+        Err(RuntimeError::Overflow(BinOp::Div, i32::MIN, -1))
+    );
+    assert_eq!(
+        eval("let mut z = 0; 5 % z"),
+        Err(RuntimeError::DivisionByZero(BinOp::Rem, 5))
+    );
+}
+
+#[test]
+fn eval_control_flow() {
+    let eval = |src: &str| Context::default().eval_expr(&elaborate(src), &Lifetime::global());
+    let eval_unchecked =
+        |src: &str| Context::default().eval_expr(&parse_program(src).unwrap(), &Lifetime::global());
+    assert_eq!(
+        eval(
+            "let mut n = 5;
+                 let mut acc = box 1;
+                 while n > 1 { *acc = *acc * n; n = n - 1; }
+                 *acc"
+        ),
+        Ok(Value::Int(120))
+    );
+    assert_eq!(
+        eval("if 1 >= 2 { 1 } else if false { 2 } else { 3 }"),
+        Ok(Value::Int(3))
+    );
+    assert_eq!(
+        eval("fn fib(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } } fib(10)"),
+        Ok(Value::Int(55))
+    );
+    assert_eq!(
+        eval_unchecked("if 1 { 2 }"),
+        Err(RuntimeError::NotABool(Value::Int(1)))
+    );
+}
+
+#[test]
+fn eval_tuples() {
+    let eval = |src: &str| Context::default().eval_expr(&elaborate(src), &Lifetime::global());
+    assert_eq!(
+        eval(
+            "let mut t = (1, box 2);
+                 let mut r = &mut t.0;
+                 *r = *t.1 + 1;
+                 *t.1 = 5;
+                 t.0 * 10 + *t.1"
+        ),
+        Ok(Value::Int(35))
+    );
+    assert_eq!(
+        eval("let mut t = (1, (true,)); t.1"),
+        Ok(Value::Tuple(vec![Some(Value::Bool(true))]))
+    );
+}
+
+#[test]
+fn eval_moves() {
+    let mut ctxt = Context::default();
+    let prog = elaborate(
+        "let mut x = box 1;
+             let mut t = (2, box 3);
+             let mut y = x;
+             let mut z = t.1;
+             let mut n = t.0;
+             *y + *z + n",
+    );
+    let (stmts, final_e) = match prog {
+        Expr::Block(stmts, final_e, _) => (stmts, final_e),
+        _ => panic!("expected a block"),
+    };
+    for s in &stmts {
+        ctxt.eval_stmt(s, &Lifetime::global()).unwrap();
+    }
+    assert_eq!(
+        ctxt.eval_expr(&final_e, &Lifetime::global()),
+        Ok(Value::Int(6))
+    );
+    // moved-out places are left empty, copied ones keep their value
+    assert_eq!(ctxt.store.read(&Lval::new("x", 0)), Ok(&None));
+    assert_eq!(
+        ctxt.store.read(&Lval::new("t", 0)),
+        Ok(&Some(Value::Tuple(vec![Some(Value::Int(2)), None])))
+    );
+    // a read not marked as a copy moves, so a second one finds nothing
+    let read_y = Expr::Lval(Lval::new("y", 0), false);
+    assert!(ctxt.eval_expr(&read_y, &Lifetime::global()).is_ok());
+    assert_eq!(
+        ctxt.eval_expr(&read_y, &Lifetime::global()),
+        Err(RuntimeError::UninitializedRead(Lval::new("y", 0)))
+    );
+}
+
+#[test]
+fn eval_match() {
+    let eval = |src: &str| Context::default().eval_expr(&elaborate(src), &Lifetime::global());
+    assert_eq!(
+        eval(
+            "enum List { Nil, Cons(int, box List) }
+                 fn sum(l: &List) -> int {
+                     match *l { List::Nil => 0, List::Cons(n, ref rest) => n + sum(&**rest) }
+                 }
+                 fn push(l: &mut List, n: int) {
+                     match *l {
+                         List::Nil => { *l = List::Cons(n, box List::Nil); }
+                         List::Cons(_, ref mut rest) => push(&mut **rest, n),
+                     }
+                 }
+                 let mut l = List::Nil;
+                 push(&mut l, 1);
+                 push(&mut l, 2);
+                 push(&mut l, 3);
+                 sum(&l)"
+        ),
+        Ok(Value::Int(6))
+    );
+    assert_eq!(
+        eval("enum E { A(int, bool), B } (E::A(1, true), E::B)")
+            .unwrap()
+            .to_string(),
+        "(E::A(1, true), E::B)"
+    );
+    let no_arm = Expr::Match(
+        Box::new(Expr::Int(1)),
+        vec![(
+            Pattern::Variant(String::from("E"), String::from("A"), vec![]),
+            Expr::Int(2),
+        )],
+        Lifetime(1),
+    );
+    assert_eq!(
+        Context::default().eval_expr(&no_arm, &Lifetime::global()),
+        Err(RuntimeError::NoArmMatches(Value::Int(1)))
+    );
+}
+
+#[test]
+fn match_moves_fields() {
+    let mut ctxt = Context::default();
+    let prog = elaborate(
+        "enum Opt { None, Some(box int) }
+             let mut o = Opt::Some(box 5);
+             let mut n = match o { Opt::Some(b) => *b, Opt::None => 0 };
+             let mut m = match Opt::Some(box 6) { Opt::Some(c) => *c, Opt::None => 0 };
+             n + m",
+    );
+    let Expr::Block(stmts, final_e, _) = prog else {
+        panic!("expected a block");
+    };
+    for s in &stmts {
+        ctxt.eval_stmt(s, &Lifetime::global()).unwrap();
+    }
+    assert_eq!(
+        ctxt.eval_expr(&final_e, &Lifetime::global()),
+        Ok(Value::Int(11))
+    );
+    assert_eq!(
+        ctxt.store.read(&Lval::new("o", 0)),
+        Ok(&Some(Value::Enum(
+            String::from("Opt"),
+            String::from("Some"),
+            vec![None]
+        )))
+    );
+    // bindings and the matched temporary are dropped with the arm,
+    // along with the box moved into `b`
+    let mut vars: Vec<&str> = ctxt.store.frames[0]
+        .vars
+        .keys()
+        .map(String::as_str)
+        .collect();
+    vars.sort();
+    assert_eq!(vars, ["m", "n", "o"]);
+    assert!(ctxt.store.heap.is_empty());
+}
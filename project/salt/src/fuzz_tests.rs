@@ -0,0 +1,619 @@
+use crate::eval;
+use crate::parser::parse_program;
+use crate::pretty::program;
+use crate::test_rng::Rng;
+use crate::types;
+use crate::utils::{BinOp, Binding, EnumDef, Expr, FnDef, Lifetime, Lval, Pattern, Stmt, TypeAnn};
+use crate::vm;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Override with `SALT_FUZZ_SEED=<n> cargo test fuzz` to explore further;
+/// every `n`, 0 included, gives a run of its own.
+const SEED: u64 = 0x5a17_f022;
+const PROGRAMS: usize = 400;
+
+/// The types of generated variables, fn parameters and results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Unit,
+    Int,
+    Bool,
+    Boxed,
+    Ref,
+    RefMut,
+    /// `(int, box int)`
+    Pair,
+    /// `Opt`, which every program declares as
+    /// `enum Opt { None, Some(int, box int) }`
+    Opt,
+    /// `&mut Opt`
+    OptMut,
+}
+
+const VAR_KINDS: [Kind; 8] = [
+    Kind::Int,
+    Kind::Bool,
+    Kind::Boxed,
+    Kind::Ref,
+    Kind::RefMut,
+    Kind::Pair,
+    Kind::Opt,
+    Kind::OptMut,
+];
+const PARAM_KINDS: [Kind; 7] = [
+    Kind::Int,
+    Kind::Boxed,
+    Kind::Ref,
+    Kind::RefMut,
+    Kind::Pair,
+    Kind::Opt,
+    Kind::OptMut,
+];
+const ARITH: [BinOp; 5] = [BinOp::Add, BinOp::Sub, BinOp::Mul, BinOp::Div, BinOp::Rem];
+const CMP: [BinOp; 4] = [BinOp::Lt, BinOp::Le, BinOp::Eq, BinOp::Ne];
+
+impl Kind {
+    fn ann(self) -> TypeAnn {
+        let boxed_int = TypeAnn::Box(Box::new(TypeAnn::Int));
+        match self {
+            Kind::Unit => TypeAnn::Unit,
+            Kind::Int => TypeAnn::Int,
+            Kind::Bool => TypeAnn::Bool,
+            Kind::Boxed => boxed_int,
+            Kind::Ref => TypeAnn::Ref(Box::new(TypeAnn::Int), false, None),
+            Kind::RefMut => TypeAnn::Ref(Box::new(TypeAnn::Int), true, None),
+            Kind::Pair => TypeAnn::Tuple(vec![TypeAnn::Int, boxed_int]),
+            Kind::Opt => TypeAnn::Enum(String::from("Opt")),
+            Kind::OptMut => TypeAnn::Ref(Box::new(Kind::Opt.ann()), true, None),
+        }
+    }
+}
+
+fn opt_def() -> Stmt {
+    let boxed_int = TypeAnn::Box(Box::new(TypeAnn::Int));
+    Stmt::Enum(EnumDef {
+        name: String::from("Opt"),
+        variants: vec![
+            (String::from("None"), vec![]),
+            (String::from("Some"), vec![TypeAnn::Int, boxed_int]),
+        ],
+    })
+}
+
+/// Loop counters are named `n..` and left out of `Gen::vars`, so no
+/// generated statement and no shrink step can stop a loop.
+fn is_counter(lv: &Lval) -> bool {
+    lv.ident.starts_with('n')
+}
+
+/// Builds programs that only mention variables in scope, at the type
+/// they were declared with. Whether borrows and moves line up is left to
+/// chance, so the checker rejects some of them.
+struct Gen {
+    rng: Rng,
+    vars: Vec<(String, Kind)>,
+    fns: Vec<(String, Vec<Kind>, Kind)>,
+    next_name: usize,
+}
+
+impl Gen {
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_name += 1;
+        format!("{}{}", prefix, self.next_name)
+    }
+
+    fn var(&mut self, kinds: &[Kind]) -> Option<(String, Kind)> {
+        let found: Vec<_> = self
+            .vars
+            .iter()
+            .filter(|(_, k)| kinds.contains(k))
+            .cloned()
+            .collect();
+        (!found.is_empty()).then(|| found[self.rng.below(found.len())].clone())
+    }
+
+    /// A place holding an int, or `None` if there are no variables to
+    /// reach one through. `mutable` leaves out shared references.
+    fn int_place(&mut self, mutable: bool) -> Option<Lval> {
+        let kinds: &[Kind] = if mutable {
+            &[Kind::Int, Kind::Boxed, Kind::RefMut, Kind::Pair]
+        } else {
+            &[Kind::Int, Kind::Boxed, Kind::Ref, Kind::RefMut, Kind::Pair]
+        };
+        let (x, kind) = self.var(kinds)?;
+        Some(match kind {
+            Kind::Int => Lval::new(&x, 0),
+            Kind::Pair if self.rng.below(2) == 0 => Lval::field(&x, &[0], 0),
+            Kind::Pair => Lval::field(&x, &[1], 1),
+            _ => Lval::new(&x, 1),
+        })
+    }
+
+    fn int(&mut self, depth: usize) -> Expr {
+        let literal = Expr::Int(self.rng.below(13) as i32 - 3);
+        let choice = self.rng.below(if depth == 0 { 3 } else { 9 });
+        let depth = depth.saturating_sub(1);
+        match choice {
+            0 => literal,
+            1 | 2 => match self.int_place(false) {
+                Some(lv) => Expr::Lval(lv, false),
+                None => literal,
+            },
+            3 | 4 => {
+                let ops = if self.rng.below(3) == 0 {
+                    &ARITH[..]
+                } else {
+                    &ARITH[..2]
+                };
+                let op = ops[self.rng.below(ops.len())];
+                Expr::binop(op, self.int(depth), self.int(depth))
+            }
+            5 => Expr::iff(
+                self.bool(depth),
+                self.block(Kind::Int, depth),
+                self.block(Kind::Int, depth),
+            ),
+            6 => self.block(Kind::Int, depth),
+            7 => self.match_opt(depth),
+            _ => self.call(Kind::Int, depth).unwrap_or(literal),
+        }
+    }
+
+    /// A match on an `Opt` with int arms, binding its fields by copy,
+    /// move, `ref` or `ref mut`.
+    fn match_opt(&mut self, depth: usize) -> Expr {
+        let scrutinee = match self.var(&[Kind::Opt, Kind::OptMut]) {
+            Some((x, Kind::Opt)) => Expr::Lval(Lval::new(&x, 0), false),
+            Some((x, _)) => Expr::Lval(Lval::new(&x, 1), false),
+            None => self.expr(Kind::Opt, depth),
+        };
+        let scope = self.vars.len();
+        let mut bindings = vec![];
+        for (i, choice) in [self.rng.below(4), self.rng.below(2)]
+            .into_iter()
+            .enumerate()
+        {
+            let x = self.fresh("b");
+            let (binding, kind) = match (i, choice) {
+                (_, 0) => {
+                    bindings.push(Binding::Ignore);
+                    continue;
+                }
+                (0, 1) => (Binding::Move(x.clone(), false), Kind::Int),
+                (0, 2) => (Binding::Ref(x.clone(), false), Kind::Ref),
+                (0, _) => (Binding::Ref(x.clone(), true), Kind::RefMut),
+                _ => (Binding::Move(x.clone(), false), Kind::Boxed),
+            };
+            bindings.push(binding);
+            self.vars.push((x, kind));
+        }
+        let some = Pattern::Variant(String::from("Opt"), String::from("Some"), bindings);
+        let some = (some, self.block(Kind::Int, depth));
+        self.vars.truncate(scope);
+        let none = match self.rng.below(3) {
+            0 => Pattern::Wildcard,
+            _ => Pattern::Variant(String::from("Opt"), String::from("None"), vec![]),
+        };
+        let none = (none, self.int(depth));
+        let arms = if self.rng.below(2) == 0 {
+            vec![some, none]
+        } else {
+            vec![none, some]
+        };
+        Expr::Match(Box::new(scrutinee), arms, Lifetime(0))
+    }
+
+    fn bool(&mut self, depth: usize) -> Expr {
+        match self.rng.below(3) {
+            0 => Expr::Bool(self.rng.below(2) == 0),
+            1 => match self.var(&[Kind::Bool]) {
+                Some((x, _)) => Expr::Lval(Lval::new(&x, 0), false),
+                None => Expr::Bool(true),
+            },
+            _ => {
+                let op = CMP[self.rng.below(CMP.len())];
+                Expr::binop(op, self.int(depth), self.int(depth))
+            }
+        }
+    }
+
+    fn expr(&mut self, kind: Kind, depth: usize) -> Expr {
+        // reuse a variable of the right type now and then, which moves it
+        if !matches!(kind, Kind::Unit | Kind::Int | Kind::Bool) && self.rng.below(4) == 0 {
+            if let Some((x, _)) = self.var(&[kind]) {
+                return Expr::Lval(Lval::new(&x, 0), false);
+            }
+        }
+        match kind {
+            Kind::Unit => Expr::Unit,
+            Kind::Int => self.int(depth),
+            Kind::Bool => self.bool(depth),
+            Kind::Boxed => match self.var(&[Kind::Pair]) {
+                Some((p, _)) if self.rng.below(4) == 0 => {
+                    Expr::Lval(Lval::field(&p, &[1], 0), false)
+                }
+                _ => Expr::boxx(self.int(depth)),
+            },
+            Kind::Ref | Kind::RefMut => {
+                let mutable = kind == Kind::RefMut;
+                match self.int_place(mutable) {
+                    Some(lv) => Expr::Borrow(lv, mutable),
+                    // something to borrow that outlives the reference
+                    None => Expr::Borrow(Lval::new("g", 0), mutable),
+                }
+            }
+            Kind::Pair => Expr::Tuple(vec![self.int(depth), Expr::boxx(self.int(depth))]),
+            Kind::Opt if self.rng.below(3) == 0 => {
+                Expr::Variant(String::from("Opt"), String::from("None"), vec![])
+            }
+            Kind::Opt => Expr::Variant(
+                String::from("Opt"),
+                String::from("Some"),
+                vec![self.int(depth), Expr::boxx(self.int(depth))],
+            ),
+            Kind::OptMut => match self.var(&[Kind::Opt, Kind::OptMut]) {
+                Some((x, Kind::Opt)) => Expr::Borrow(Lval::new(&x, 0), true),
+                Some((x, _)) => Expr::Borrow(Lval::new(&x, 1), true),
+                None => Expr::Borrow(Lval::new("h", 0), true),
+            },
+        }
+    }
+
+    /// A block of a few statements ending in an expression of `kind`.
+    fn block(&mut self, kind: Kind, depth: usize) -> Expr {
+        let scope = self.vars.len();
+        let mut stmts = vec![];
+        for _ in 0..self.rng.below(3) {
+            stmts.extend(self.stmt(depth));
+        }
+        let final_e = self.expr(kind, depth);
+        self.vars.truncate(scope);
+        Expr::block(stmts, final_e, Lifetime(0))
+    }
+
+    fn assign(&mut self, depth: usize) -> Option<Stmt> {
+        let (x, kind) = self.var(&VAR_KINDS)?;
+        let (lv, rhs) = match kind {
+            Kind::Int => (Lval::new(&x, 0), Kind::Int),
+            Kind::Bool => (Lval::new(&x, 0), Kind::Bool),
+            Kind::RefMut if self.rng.below(2) == 0 => (Lval::new(&x, 1), Kind::Int),
+            Kind::Boxed if self.rng.below(2) == 0 => (Lval::new(&x, 1), Kind::Int),
+            Kind::Pair if self.rng.below(2) == 0 => (Lval::field(&x, &[0], 0), Kind::Int),
+            Kind::Pair if self.rng.below(2) == 0 => (Lval::field(&x, &[1], 1), Kind::Int),
+            Kind::OptMut if self.rng.below(2) == 0 => (Lval::new(&x, 1), Kind::Opt),
+            _ => (Lval::new(&x, 0), kind),
+        };
+        Some(Stmt::Assign(lv, self.expr(rhs, depth)))
+    }
+
+    fn stmt(&mut self, depth: usize) -> Vec<Stmt> {
+        let choice = self.rng.below(if depth == 0 { 4 } else { 8 });
+        let depth = depth.saturating_sub(1);
+        match choice {
+            0 | 1 => {
+                let kind = VAR_KINDS[self.rng.below(VAR_KINDS.len())];
+                let e = self.expr(kind, depth);
+                let x = self.fresh("v");
+                self.vars.push((x.clone(), kind));
+                vec![Stmt::LetMut(x, e)]
+            }
+            2 | 3 => self.assign(depth).into_iter().collect(),
+            4 => vec![Stmt::Expr(Expr::iff(
+                self.bool(depth),
+                self.block(Kind::Unit, depth),
+                self.block(Kind::Unit, depth),
+            ))],
+            5 => {
+                let n = self.fresh("n");
+                let counter = Lval::new(&n, 0);
+                let read = || Expr::Lval(counter.clone(), false);
+                let bound = Expr::Int(self.rng.below(4) as i32);
+                let body = match self.block(Kind::Unit, depth) {
+                    Expr::Block(mut stmts, final_e, l) => {
+                        stmts.push(Stmt::Assign(
+                            counter.clone(),
+                            Expr::binop(BinOp::Add, read(), Expr::Int(1)),
+                        ));
+                        Expr::Block(stmts, final_e, l)
+                    }
+                    _ => unreachable!(),
+                };
+                vec![
+                    Stmt::LetMut(n, Expr::Int(0)),
+                    Stmt::Expr(Expr::whilee(Expr::binop(BinOp::Lt, read(), bound), body)),
+                ]
+            }
+            6 => self
+                .call(Kind::Unit, depth)
+                .map(Stmt::Expr)
+                .into_iter()
+                .collect(),
+            _ => vec![Stmt::Expr(self.block(Kind::Unit, depth))],
+        }
+    }
+
+    fn call(&mut self, ret: Kind, depth: usize) -> Option<Expr> {
+        let found: Vec<_> = self.fns.iter().filter(|f| f.2 == ret).cloned().collect();
+        if found.is_empty() {
+            return None;
+        }
+        let (name, params, _) = found[self.rng.below(found.len())].clone();
+        let args = params.iter().map(|k| self.expr(*k, depth)).collect();
+        Some(Expr::Call(name, args))
+    }
+
+    /// A fn over its parameters alone, which may call the ones before it
+    /// but never itself.
+    fn fn_def(&mut self) -> Stmt {
+        let kinds: Vec<Kind> = (0..self.rng.below(3))
+            .map(|_| PARAM_KINDS[self.rng.below(PARAM_KINDS.len())])
+            .collect();
+        let ret = [Kind::Unit, Kind::Int][self.rng.below(2)];
+        let params: Vec<(String, Kind)> = kinds.iter().map(|k| (self.fresh("a"), *k)).collect();
+        let outer = std::mem::replace(&mut self.vars, params.clone());
+        let body = self.block(ret, 2);
+        self.vars = outer;
+        let name = self.fresh("f");
+        self.fns.push((name.clone(), kinds, ret));
+        Stmt::Fn(FnDef {
+            name,
+            params: params.into_iter().map(|(x, k)| (x, k.ann())).collect(),
+            ret: ret.ann(),
+            lifetimes: vec![],
+            bounds: vec![],
+            body,
+            lifetime: Lifetime(0),
+        })
+    }
+
+    fn program(&mut self) -> Expr {
+        self.vars = vec![
+            (String::from("g"), Kind::Int),
+            (String::from("h"), Kind::Opt),
+        ];
+        self.fns = vec![];
+        let none = Expr::Variant(String::from("Opt"), String::from("None"), vec![]);
+        let mut stmts = vec![
+            opt_def(),
+            Stmt::LetMut(String::from("g"), Expr::Int(1)),
+            Stmt::LetMut(String::from("h"), none),
+        ];
+        for _ in 0..self.rng.below(3) {
+            stmts.push(self.fn_def());
+        }
+        for _ in 0..self.rng.below(6) + 1 {
+            stmts.extend(self.stmt(3));
+        }
+        let final_e = self.int(2);
+        Expr::block(stmts, final_e, Lifetime(0))
+    }
+}
+
+enum Outcome {
+    Rejected,
+    Ran,
+    Failed(String),
+}
+
+/// Check `e` and, if it is accepted, run it under the safety oracle and
+/// on the VM, which must agree with the tree-walker.
+/// Printing and parsing it again gives it real lifetimes.
+fn run(e: &Expr) -> Outcome {
+    let Ok(parsed) = parse_program(&program(e)) else {
+        return Outcome::Rejected;
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let Ok(typed) = types::Context::default().check_all(&parsed) else {
+            return Outcome::Rejected;
+        };
+        match eval::Context::checked().eval_expr(&typed.expr, &Lifetime::global()) {
+            Err(e) if e.is_unsafe() => return Outcome::Failed(e.to_string()),
+            _ => {}
+        }
+        let walked = eval::Context::default().eval_expr(&typed.expr, &Lifetime::global());
+        let ran = vm::compile(&typed.expr).run();
+        if ran != walked {
+            return Outcome::Failed(format!("the VM gave {:?}, not {:?}", ran, walked));
+        }
+        Outcome::Ran
+    }));
+    result.unwrap_or_else(|_| Outcome::Failed(String::from("panicked")))
+}
+
+/// Every program one step simpler than `e`.
+fn shrink(e: &Expr) -> Vec<Expr> {
+    let mut out = vec![];
+    match e {
+        Expr::Block(stmts, final_e, l) => {
+            for (i, s) in stmts.iter().enumerate() {
+                if !matches!(s, Stmt::Assign(lv, _) if is_counter(lv)) {
+                    let mut fewer = stmts.clone();
+                    fewer.remove(i);
+                    out.push(Expr::Block(fewer, final_e.clone(), l.clone()));
+                }
+                for smaller in shrink_stmt(s) {
+                    let mut stmts = stmts.clone();
+                    stmts[i] = smaller;
+                    out.push(Expr::Block(stmts, final_e.clone(), l.clone()));
+                }
+            }
+            for f in shrink(final_e) {
+                out.push(Expr::block(stmts.clone(), f, l.clone()));
+            }
+        }
+        Expr::BinOp(op, lhs, rhs) => {
+            out.push(*lhs.clone());
+            out.push(*rhs.clone());
+            out.extend(
+                shrink(lhs)
+                    .into_iter()
+                    .map(|a| Expr::binop(*op, a, *rhs.clone())),
+            );
+            out.extend(
+                shrink(rhs)
+                    .into_iter()
+                    .map(|b| Expr::binop(*op, *lhs.clone(), b)),
+            );
+        }
+        Expr::If(c, t, f) => {
+            out.push(*t.clone());
+            out.push(*f.clone());
+            out.extend(
+                shrink(c)
+                    .into_iter()
+                    .map(|c| Expr::iff(c, *t.clone(), *f.clone())),
+            );
+            out.extend(
+                shrink(t)
+                    .into_iter()
+                    .map(|t| Expr::iff(*c.clone(), t, *f.clone())),
+            );
+            out.extend(
+                shrink(f)
+                    .into_iter()
+                    .map(|f| Expr::iff(*c.clone(), *t.clone(), f)),
+            );
+        }
+        // the condition is left alone, so the loop still ends
+        Expr::While(c, body) => {
+            out.push(Expr::Unit);
+            out.extend(
+                shrink(body)
+                    .into_iter()
+                    .map(|b| Expr::whilee(*c.clone(), b)),
+            );
+        }
+        Expr::Box(inner) => out.extend(shrink(inner).into_iter().map(Expr::boxx)),
+        Expr::Match(scrutinee, arms, l) => {
+            for (i, (_, body)) in arms.iter().enumerate() {
+                for smaller in shrink(body) {
+                    let mut arms = arms.clone();
+                    arms[i].1 = smaller;
+                    out.push(Expr::Match(scrutinee.clone(), arms, l.clone()));
+                }
+            }
+        }
+        Expr::Tuple(elems) | Expr::Call(_, elems) => {
+            for (i, elem) in elems.iter().enumerate() {
+                for smaller in shrink(elem) {
+                    let mut elems = elems.clone();
+                    elems[i] = smaller;
+                    out.push(match e {
+                        Expr::Tuple(_) => Expr::Tuple(elems),
+                        Expr::Call(f, _) => Expr::Call(f.clone(), elems),
+                        _ => unreachable!(),
+                    });
+                }
+            }
+        }
+        Expr::Int(n) if *n != 0 => out.push(Expr::Int(0)),
+        _ => {}
+    }
+    out
+}
+
+fn shrink_stmt(s: &Stmt) -> Vec<Stmt> {
+    match s {
+        Stmt::LetMut(x, e) => shrink(e)
+            .into_iter()
+            .map(|e| Stmt::LetMut(x.clone(), e))
+            .collect(),
+        Stmt::Assign(lv, _) if is_counter(lv) => vec![],
+        Stmt::Assign(lv, e) => shrink(e)
+            .into_iter()
+            .map(|e| Stmt::Assign(lv.clone(), e))
+            .collect(),
+        Stmt::Expr(e) => shrink(e).into_iter().map(Stmt::Expr).collect(),
+        Stmt::Fn(def) => shrink(&def.body)
+            .into_iter()
+            .map(|body| {
+                Stmt::Fn(FnDef {
+                    body,
+                    ..def.clone()
+                })
+            })
+            .collect(),
+        Stmt::Enum(_) | Stmt::Spanned(..) => vec![],
+    }
+}
+
+/// The smallest program reachable from `e` by shrink steps that still
+/// fails, with its failure.
+fn minimize(mut e: Expr, mut failure: String) -> (Expr, String) {
+    'outer: loop {
+        for smaller in shrink(&e) {
+            if let Outcome::Failed(f) = run(&smaller) {
+                (e, failure) = (smaller, f);
+                continue 'outer;
+            }
+        }
+        return (e, failure);
+    }
+}
+
+#[test]
+fn shrinking_keeps_loops_finite() {
+    // stands in for a checker bug: fail whenever there is a `*`
+    let fails = |e: &Expr| program(e).contains('*');
+    let (n, v) = (Lval::new("n1", 0), Lval::new("v", 0));
+    let read = |lv: &Lval| Expr::Lval(lv.clone(), false);
+    let body = vec![
+        Stmt::Assign(v.clone(), Expr::binop(BinOp::Mul, read(&v), Expr::Int(2))),
+        Stmt::Assign(n.clone(), Expr::binop(BinOp::Add, read(&n), Expr::Int(1))),
+    ];
+    let mut e = Expr::block(
+        vec![
+            Stmt::LetMut(String::from("n1"), Expr::Int(0)),
+            Stmt::Expr(Expr::whilee(
+                Expr::binop(BinOp::Lt, read(&n), Expr::Int(3)),
+                Expr::block(body, Expr::Unit, Lifetime(0)),
+            )),
+        ],
+        Expr::Int(5),
+        Lifetime(0),
+    );
+    'outer: loop {
+        for smaller in shrink(&e) {
+            if fails(&smaller) {
+                e = smaller;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    assert_eq!(
+        program(&e),
+        "while n1 < 3 {\n    v = v * 0;\n    n1 = n1 + 1;\n}\n0"
+    );
+}
+
+/// Every generated program the checker accepts runs without a panic
+/// or any violation the safety oracle can see.
+#[test]
+fn fuzz_accepted_programs_are_safe() {
+    let seed = env::var("SALT_FUZZ_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(SEED);
+    let mut gen = Gen {
+        rng: Rng::new(seed),
+        vars: vec![],
+        fns: vec![],
+        next_name: 0,
+    };
+    let mut accepted = 0;
+    for i in 0..PROGRAMS {
+        let e = gen.program();
+        match run(&e) {
+            Outcome::Rejected => {}
+            Outcome::Ran => accepted += 1,
+            Outcome::Failed(failure) => {
+                let (e, failure) = minimize(e, failure);
+                panic!("seed {}, program {}: {}\n{}", seed, i, failure, program(&e));
+            }
+        }
+    }
+    // make sure the checker isn't rejecting nearly everything
+    assert!(accepted * 5 >= PROGRAMS, "only {} accepted", accepted);
+}
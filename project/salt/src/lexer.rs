@@ -1,3 +1,7 @@
+pub use crate::utils::{Position, Span};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Lparen,
     Rparen,
@@ -7,11 +11,281 @@ pub enum Token {
     Ampersand,
     Star,
     Comma,
+    Dot,
     Semicolon,
+    Colon,
+    Arrow,
+    FatArrow,
+    ColonColon,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    EqEq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
     Fn,
     Let,
     Mut,
     Box,
+    If,
+    Else,
+    While,
+    Enum,
+    Match,
+    Ref,
+    True,
+    False,
     Int(i32),
     Var(String),
+    /// A lifetime name such as `'a`, quote included.
+    Lifetime(String),
+}
+
+/// The source text of the token.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Token::Lparen => "(",
+            Token::Rparen => ")",
+            Token::Lbracket => "{",
+            Token::Rbracket => "}",
+            Token::Eq => "=",
+            Token::Ampersand => "&",
+            Token::Star => "*",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Semicolon => ";",
+            Token::Colon => ":",
+            Token::Arrow => "->",
+            Token::FatArrow => "=>",
+            Token::ColonColon => "::",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::EqEq => "==",
+            Token::Ne => "!=",
+            Token::Lt => "<",
+            Token::Le => "<=",
+            Token::Gt => ">",
+            Token::Ge => ">=",
+            Token::Fn => "fn",
+            Token::Let => "let",
+            Token::Mut => "mut",
+            Token::Box => "box",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::Enum => "enum",
+            Token::Match => "match",
+            Token::Ref => "ref",
+            Token::True => "true",
+            Token::False => "false",
+            Token::Int(n) => return write!(f, "{}", n),
+            Token::Var(x) | Token::Lifetime(x) => x,
+        };
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned {
+    pub token: Token,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnexpectedChar(char, Position),
+    IntOverflow(String, Position),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedChar(c, pos) => write!(f, "{}: unexpected character `{}`", pos, c),
+            Error::IntOverflow(lit, pos) => {
+                write!(f, "{}: integer literal `{}` does not fit in i32", pos, lit)
+            }
+        }
+    }
+}
+
+pub type LexResult<T> = Result<T, Error>;
+
+pub struct Lexer {
+    chars: Vec<char>,
+    idx: usize,
+    pos: Position,
+    /// Whether the last token ended an operand, in which case a `-` is
+    /// subtraction rather than the sign of a literal.
+    after_operand: bool,
+}
+
+impl Lexer {
+    pub fn new(src: &str) -> Self {
+        Lexer {
+            chars: src.chars().collect(),
+            idx: 0,
+            pos: Position::start(),
+            after_operand: false,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.idx += 1;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                // `//` line comments; a lone `/` is left for the caller to reject
+                Some('/') if self.chars.get(self.idx + 1) == Some(&'/') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn take_while(&mut self, mut text: String, pred: impl Fn(char) -> bool) -> String {
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        text
+    }
+
+    fn next_token(&mut self) -> Option<LexResult<Spanned>> {
+        self.skip_trivia();
+        let start = self.pos;
+        let c = self.bump()?;
+        let token = match c {
+            '(' => Token::Lparen,
+            ')' => Token::Rparen,
+            '{' => Token::Lbracket,
+            '}' => Token::Rbracket,
+            '=' if self.eat('=') => Token::EqEq,
+            '=' if self.eat('>') => Token::FatArrow,
+            '=' => Token::Eq,
+            '!' if self.eat('=') => Token::Ne,
+            '<' if self.eat('=') => Token::Le,
+            '<' => Token::Lt,
+            '>' if self.eat('=') => Token::Ge,
+            '>' => Token::Gt,
+            '+' => Token::Plus,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '&' => Token::Ampersand,
+            '*' => Token::Star,
+            ',' => Token::Comma,
+            '.' => Token::Dot,
+            ';' => Token::Semicolon,
+            ':' if self.eat(':') => Token::ColonColon,
+            ':' => Token::Colon,
+            '-' if self.eat('>') => Token::Arrow,
+            '-' if !self.after_operand && self.peek().is_some_and(|d| d.is_ascii_digit()) => {
+                match self.int(String::from("-"), start) {
+                    Ok(t) => t,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            '-' => Token::Minus,
+            c if c.is_ascii_digit() => match self.int(c.to_string(), start) {
+                Ok(t) => t,
+                Err(e) => return Some(Err(e)),
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                let word = self.take_while(c.to_string(), |c| c.is_alphanumeric() || c == '_');
+                match word.as_str() {
+                    "fn" => Token::Fn,
+                    "let" => Token::Let,
+                    "mut" => Token::Mut,
+                    "box" => Token::Box,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "while" => Token::While,
+                    "enum" => Token::Enum,
+                    "match" => Token::Match,
+                    "ref" => Token::Ref,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    _ => Token::Var(word),
+                }
+            }
+            '\'' if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') => {
+                Token::Lifetime(self.take_while(c.to_string(), |c| c.is_alphanumeric() || c == '_'))
+            }
+            other => return Some(Err(Error::UnexpectedChar(other, start))),
+        };
+        self.after_operand = matches!(
+            token,
+            Token::Int(_)
+                | Token::Var(_)
+                | Token::True
+                | Token::False
+                | Token::Rparen
+                | Token::Rbracket
+        );
+        Some(Ok(Spanned {
+            token,
+            span: Span {
+                start,
+                end: self.pos,
+            },
+        }))
+    }
+
+    fn int(&mut self, prefix: String, start: Position) -> LexResult<Token> {
+        let lit = self.take_while(prefix, |c| c.is_ascii_digit());
+        lit.parse::<i32>()
+            .map(Token::Int)
+            .map_err(|_| Error::IntOverflow(lit, start))
+    }
+}
+
+impl Iterator for Lexer {
+    type Item = LexResult<Spanned>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+/// Lex the whole input, stopping at the first error.
+pub fn tokenize(src: &str) -> LexResult<Vec<Spanned>> {
+    Lexer::new(src).collect()
 }
@@ -0,0 +1,210 @@
+use crate::lexer::{tokenize, Error, Lexer, Position, Token};
+
+fn tokens(src: &str) -> Vec<Token> {
+    tokenize(src)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.token)
+        .collect()
+}
+
+#[test]
+fn lex_punctuation() {
+    assert_eq!(
+        tokens("( ) { } = & * , ;"),
+        vec![
+            Token::Lparen,
+            Token::Rparen,
+            Token::Lbracket,
+            Token::Rbracket,
+            Token::Eq,
+            Token::Ampersand,
+            Token::Star,
+            Token::Comma,
+            Token::Semicolon,
+        ]
+    );
+}
+
+#[test]
+fn display_tokens_as_written() {
+    let src = "fn f<'a>(x: &'a mut int) -> int { match *x { E::A(n) => -1 != n % 2, _ => true } }";
+    let shown: Vec<_> = tokens(src).iter().map(|t| t.to_string()).collect();
+    assert_eq!(
+        shown.join(" "),
+        "fn f < 'a > ( x : & 'a mut int ) -> int { match * x { E :: A ( n ) => -1 != n % 2 , _ => true } }"
+    );
+}
+
+#[test]
+fn lex_keywords_and_vars() {
+    assert_eq!(
+        tokens("fn let mut box boxed _x mutable"),
+        vec![
+            Token::Fn,
+            Token::Let,
+            Token::Mut,
+            Token::Box,
+            Token::Var(String::from("boxed")),
+            Token::Var(String::from("_x")),
+            Token::Var(String::from("mutable")),
+        ]
+    );
+}
+
+#[test]
+fn lex_let_mut_box() {
+    assert_eq!(
+        tokens("let mut x = box -5;"),
+        vec![
+            Token::Let,
+            Token::Mut,
+            Token::Var(String::from("x")),
+            Token::Eq,
+            Token::Box,
+            Token::Int(-5),
+            Token::Semicolon,
+        ]
+    );
+}
+
+#[test]
+fn lex_int_bounds() {
+    assert_eq!(
+        tokens("2147483647, -2147483648"),
+        vec![Token::Int(i32::MAX), Token::Comma, Token::Int(i32::MIN)]
+    );
+}
+
+#[test]
+fn lex_int_overflow() {
+    assert_eq!(
+        tokenize("let mut x = 2147483648;"),
+        Err(Error::IntOverflow(
+            String::from("2147483648"),
+            Position { line: 1, col: 13 }
+        ))
+    );
+}
+
+#[test]
+fn lex_stray_char() {
+    assert_eq!(
+        tokenize("x = 1;\n  y # 2"),
+        Err(Error::UnexpectedChar('#', Position { line: 2, col: 5 }))
+    );
+    assert_eq!(
+        tokenize("x ! y"),
+        Err(Error::UnexpectedChar('!', Position { line: 1, col: 3 }))
+    );
+}
+
+#[test]
+fn lex_spans() {
+    let spans: Vec<_> = Lexer::new("let mut\n  xy = -12;")
+        .map(|t| {
+            let s = t.unwrap().span;
+            ((s.start.line, s.start.col), (s.end.line, s.end.col))
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        vec![
+            ((1, 1), (1, 4)),
+            ((1, 5), (1, 8)),
+            ((2, 3), (2, 5)),
+            ((2, 6), (2, 7)),
+            ((2, 8), (2, 11)),
+            ((2, 11), (2, 12)),
+        ]
+    );
+}
+
+#[test]
+fn lex_comments() {
+    assert_eq!(
+        tokens("// header\n*x = 3; // trailing\n"),
+        vec![
+            Token::Star,
+            Token::Var(String::from("x")),
+            Token::Eq,
+            Token::Int(3),
+            Token::Semicolon,
+        ]
+    );
+}
+
+#[test]
+fn lex_operators() {
+    assert_eq!(
+        tokens("+ - / % == != < <= > >= ->"),
+        vec![
+            Token::Plus,
+            Token::Minus,
+            Token::Slash,
+            Token::Percent,
+            Token::EqEq,
+            Token::Ne,
+            Token::Lt,
+            Token::Le,
+            Token::Gt,
+            Token::Ge,
+            Token::Arrow,
+        ]
+    );
+}
+
+#[test]
+fn lex_minus_vs_negative() {
+    assert_eq!(
+        tokens("x -1 - -2 * (3)-4"),
+        vec![
+            Token::Var(String::from("x")),
+            Token::Minus,
+            Token::Int(1),
+            Token::Minus,
+            Token::Int(-2),
+            Token::Star,
+            Token::Lparen,
+            Token::Int(3),
+            Token::Rparen,
+            Token::Minus,
+            Token::Int(4),
+        ]
+    );
+}
+
+#[test]
+fn lex_lifetimes() {
+    assert_eq!(
+        tokens("&'a mut 'b_2"),
+        vec![
+            Token::Ampersand,
+            Token::Lifetime(String::from("'a")),
+            Token::Mut,
+            Token::Lifetime(String::from("'b_2")),
+        ]
+    );
+    assert_eq!(
+        tokenize("' a"),
+        Err(Error::UnexpectedChar('\'', Position { line: 1, col: 1 }))
+    );
+}
+
+#[test]
+fn lex_enums_and_match() {
+    assert_eq!(
+        tokens("enum match ref _ => E::A =="),
+        vec![
+            Token::Enum,
+            Token::Match,
+            Token::Ref,
+            Token::Var(String::from("_")),
+            Token::FatArrow,
+            Token::Var(String::from("E")),
+            Token::ColonColon,
+            Token::Var(String::from("A")),
+            Token::EqEq,
+        ]
+    );
+}
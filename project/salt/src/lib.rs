@@ -1,10 +1,39 @@
+pub mod diagnostic;
 pub mod eval;
 pub mod lexer;
+pub mod oracle;
 pub mod parser;
+pub mod pretty;
+pub mod trace;
 pub mod types;
 pub mod utils;
+pub mod vm;
 
 #[cfg(test)]
 mod part_1_tests;
+#[cfg(test)]
 mod part_2_1_tests;
+#[cfg(test)]
 mod part_2_2_tests;
+#[cfg(test)]
+mod lexer_tests;
+#[cfg(test)]
+mod parser_tests;
+#[cfg(test)]
+mod types_tests;
+#[cfg(test)]
+mod eval_tests;
+#[cfg(test)]
+mod diagnostic_tests;
+#[cfg(test)]
+mod pretty_tests;
+#[cfg(test)]
+mod oracle_tests;
+#[cfg(test)]
+mod fuzz_tests;
+#[cfg(test)]
+mod vm_tests;
+#[cfg(test)]
+mod trace_tests;
+#[cfg(test)]
+mod test_rng;
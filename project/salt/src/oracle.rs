@@ -0,0 +1,121 @@
+// Borrow state tracked at runtime, to cross-check the borrow checker.
+//
+// Each slot keeps a stack of the references derived from it, in the style
+// of Stacked Borrows. A borrow pushes an item tagged with a fresh number,
+// and the reference carries the tag in its location, shown as `x.0#3`.
+// Accessing a place pops the items it conflicts with, so a reference the
+// checker considered dead may linger in the store as long as it is never
+// used again. Using a tag whose item was popped is an aliasing violation.
+
+use crate::eval::{Location, Root};
+use std::collections::{HashMap, HashSet};
+
+pub type Tag = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Why an access was refused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The tag's slot has been freed, or freed and allocated again.
+    Dangling,
+    /// The tag's item was popped by a conflicting access, or it is shared and
+    /// was used to write.
+    Invalidated,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    tag: Tag,
+    path: Vec<usize>,
+    unique: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Borrows {
+    next_tag: Tag,
+    /// The live items of each slot, oldest first. Slots nobody borrowed have
+    /// no entry.
+    stacks: HashMap<Root, Vec<Item>>,
+    freed: HashSet<Tag>,
+}
+
+fn overlaps(a: &[usize], b: &[usize]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+impl Borrows {
+    /// Check an access to `loc`, through its tag if it has one and by the
+    /// slot's owner otherwise, and pop the items it invalidates: every
+    /// overlapping item above for a write, the unique ones for a read.
+    pub fn access(&mut self, loc: &Location, access: Access) -> Result<(), Fault> {
+        let (path, tag) = (&loc.fields, loc.tag);
+        if tag.is_some_and(|t| self.freed.contains(&t)) {
+            return Err(Fault::Dangling);
+        }
+        let Some(stack) = self.stacks.get_mut(&loc.root) else {
+            return match tag {
+                Some(_) => Err(Fault::Dangling),
+                None => Ok(()),
+            };
+        };
+        let base = match tag {
+            None => 0,
+            Some(t) => {
+                let i = stack
+                    .iter()
+                    .position(|item| item.tag == t && path.starts_with(&item.path))
+                    .ok_or(Fault::Invalidated)?;
+                if access == Access::Write && !stack[i].unique {
+                    return Err(Fault::Invalidated);
+                }
+                i + 1
+            }
+        };
+        let above = stack.split_off(base);
+        stack.extend(above.into_iter().filter(|item| {
+            !overlaps(&item.path, path) || (access == Access::Read && !item.unique)
+        }));
+        Ok(())
+    }
+
+    /// Push a fresh item for a borrow of `loc`, which must already have been
+    /// accessed, and return the location the new reference points to.
+    pub fn retag(&mut self, loc: &Location, unique: bool) -> Location {
+        let tag = self.next_tag;
+        self.next_tag += 1;
+        self.stacks.entry(loc.root.clone()).or_default().push(Item {
+            tag,
+            path: loc.fields.clone(),
+            unique,
+        });
+        Location {
+            tag: Some(tag),
+            ..loc.clone()
+        }
+    }
+
+    /// Forget the slot `root`, leaving every reference into it dangling.
+    pub fn free(&mut self, root: &Root) {
+        if let Some(stack) = self.stacks.remove(root) {
+            self.freed.extend(stack.into_iter().map(|item| item.tag));
+        }
+    }
+
+    /// Free every tracked slot for which `live` is false.
+    pub fn free_unless(&mut self, live: impl Fn(&Root) -> bool) {
+        let dead: Vec<Root> = self
+            .stacks
+            .keys()
+            .filter(|root| !live(root))
+            .cloned()
+            .collect();
+        for root in dead {
+            self.free(&root);
+        }
+    }
+}
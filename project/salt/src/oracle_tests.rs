@@ -0,0 +1,199 @@
+use crate::eval::{Context, EvalResult, Location, Root, RuntimeError, Value};
+use crate::oracle::{Access, Borrows, Fault};
+use crate::parser::parse_program;
+use crate::types;
+use crate::utils::{Expr, Lifetime, Lval, Stmt};
+
+fn run(ctxt: &mut Context, e: &Expr) -> EvalResult<Value> {
+    ctxt.eval_expr(e, &Lifetime::global())
+}
+
+/// Run an accepted program both ways, checking the oracle is silent.
+fn accepted(src: &str) -> Value {
+    let program = parse_program(src).unwrap();
+    let typed = types::Context::default().check_all(&program).unwrap();
+    let plain = run(&mut Context::default(), &typed.expr);
+    assert_eq!(run(&mut Context::checked(), &typed.expr), plain, "{}", src);
+    plain.unwrap()
+}
+
+/// Mark every read as a copy, the way a program the checker never saw
+/// would run.
+fn copy_all(e: &Expr) -> Expr {
+    let all = |es: &[Expr]| es.iter().map(copy_all).collect();
+    match e {
+        Expr::Lval(lv, _) => Expr::Lval(lv.clone(), true),
+        Expr::Tuple(es) => Expr::Tuple(all(es)),
+        Expr::Box(inner) => Expr::boxx(copy_all(inner)),
+        Expr::Block(stmts, final_e, l) => Expr::Block(
+            stmts.iter().map(copy_all_stmt).collect(),
+            Box::new(copy_all(final_e)),
+            l.clone(),
+        ),
+        Expr::BinOp(op, lhs, rhs) => Expr::binop(*op, copy_all(lhs), copy_all(rhs)),
+        Expr::If(c, t, f) => Expr::iff(copy_all(c), copy_all(t), copy_all(f)),
+        Expr::While(c, body) => Expr::whilee(copy_all(c), copy_all(body)),
+        Expr::Call(f, args) => Expr::Call(f.clone(), all(args)),
+        Expr::Spanned(inner, _) => copy_all(inner),
+        e => e.clone(),
+    }
+}
+
+fn copy_all_stmt(s: &Stmt) -> Stmt {
+    match s.peel() {
+        Stmt::Assign(lv, e) => Stmt::Assign(lv.clone(), copy_all(e)),
+        Stmt::LetMut(x, e) => Stmt::LetMut(x.clone(), copy_all(e)),
+        Stmt::Expr(e) => Stmt::Expr(copy_all(e)),
+        s => s.clone(),
+    }
+}
+
+/// Run a rejected program both ways, returning the unchecked result and
+/// the checked one.
+fn rejected(src: &str) -> (EvalResult<Value>, EvalResult<Value>) {
+    let program = parse_program(src).unwrap();
+    assert!(types::Context::default().check_all(&program).is_err());
+    let e = copy_all(&program);
+    (
+        run(&mut Context::default(), &e),
+        run(&mut Context::checked(), &e),
+    )
+}
+
+#[test]
+fn stacks_pop_conflicting_items() {
+    let var = |x: &str| Location::new(Root::Var(0, String::from(x)));
+    let mut borrows = Borrows::default();
+    let r = borrows.retag(&var("x"), true);
+    let s = borrows.retag(&r, false);
+    // reading through `r` keeps `s`, writing pops it
+    assert_eq!(borrows.access(&r, Access::Read), Ok(()));
+    assert_eq!(borrows.access(&s, Access::Read), Ok(()));
+    assert_eq!(borrows.access(&s, Access::Write), Err(Fault::Invalidated));
+    assert_eq!(borrows.access(&r, Access::Write), Ok(()));
+    assert_eq!(borrows.access(&s, Access::Read), Err(Fault::Invalidated));
+    // disjoint fields don't conflict
+    let a = borrows.retag(&var("t").field(0), true);
+    assert_eq!(borrows.access(&var("t").field(1), Access::Write), Ok(()));
+    assert_eq!(borrows.access(&a, Access::Write), Ok(()));
+    assert_eq!(borrows.access(&var("t"), Access::Read), Ok(()));
+    assert_eq!(borrows.access(&a, Access::Read), Err(Fault::Invalidated));
+    borrows.free(&var("x").root);
+    assert_eq!(borrows.access(&r, Access::Read), Err(Fault::Dangling));
+}
+
+#[test]
+fn accepted_programs_run_clean() {
+    // `r` is dead once `x` is written, though its value stays in the store
+    assert_eq!(
+        accepted("let mut x = 1; let mut r = &mut x; *r = 2; x = x + 1; let mut s = &x; *s"),
+        Value::Int(3)
+    );
+    assert_eq!(
+        accepted(
+            "let mut x = 1;
+                 let mut r = &mut x;
+                 let mut s = &mut *r;
+                 *s = 2;
+                 *r = *r + 1;
+                 x"
+        ),
+        Value::Int(3)
+    );
+    assert_eq!(
+        accepted(
+            "let mut t = (1, box 2);
+                 let mut a = &mut t.0;
+                 let mut b = &mut *t.1;
+                 *a = 3;
+                 *b = 4;
+                 t.0 + *t.1"
+        ),
+        Value::Int(7)
+    );
+    assert_eq!(
+        accepted(
+            "fn bump(r: &mut int, by: int) { *r = *r + by; }
+                 fn pick(a: &int, b: &int) -> &int { a }
+                 let mut x = 1;
+                 let mut y = 5;
+                 bump(&mut x, 2);
+                 let mut p = pick(&x, &y);
+                 *p + y"
+        ),
+        Value::Int(8)
+    );
+    assert_eq!(
+        accepted(
+            "let mut n = 0;
+                 let mut acc = box 0;
+                 while n < 3 { let mut r = &mut *acc; *r = *r + n; n = n + 1; }
+                 *acc"
+        ),
+        Value::Int(3)
+    );
+    assert_eq!(
+        accepted(
+            "enum Opt { None, Some(box int) }
+                 fn bump(o: &mut Opt) {
+                     match *o { Opt::Some(ref mut b) => { **b = **b + 1; } Opt::None => {} }
+                 }
+                 let mut o = Opt::Some(box 1);
+                 let mut r = &mut o;
+                 bump(r);
+                 match o { Opt::Some(ref b) => **b, Opt::None => 0 }"
+        ),
+        Value::Int(2)
+    );
+}
+
+#[test]
+fn rejected_programs_are_caught() {
+    // the unchecked evaluator happily runs each of these
+    let (plain, checked) = rejected("let mut x = 1; let mut r = &mut x; x = 2; *r = 3; x");
+    assert_eq!(plain, Ok(Value::Int(3)));
+    assert_eq!(
+        checked,
+        Err(RuntimeError::AliasingViolation(Lval::new("r", 1)))
+    );
+
+    let (plain, checked) = rejected("let mut x = 1; let mut r = &x; *r = 2; x");
+    assert_eq!(plain, Ok(Value::Int(2)));
+    assert_eq!(
+        checked,
+        Err(RuntimeError::AliasingViolation(Lval::new("r", 1)))
+    );
+
+    // a new `y` takes the old one's location
+    let (plain, checked) = rejected(
+        "let mut x = 0;
+             let mut r = &x;
+             { let mut y = 1; r = &y; }
+             let mut y = 5;
+             *r",
+    );
+    assert_eq!(plain, Ok(Value::Int(5)));
+    assert_eq!(
+        checked,
+        Err(RuntimeError::DanglingReference(Lval::new("r", 1)))
+    );
+    assert!(checked.unwrap_err().is_unsafe());
+}
+
+#[test]
+fn borrowing_moved_place() {
+    let program = parse_program("let mut x = box 1; let mut y = x; y").unwrap();
+    let typed = types::Context::default().elaborate(&program).unwrap();
+    let stmts = match typed.expr {
+        Expr::Block(stmts, _, _) => stmts,
+        _ => panic!("expected a block"),
+    };
+    let mut ctxt = Context::checked();
+    for s in &stmts {
+        ctxt.eval_stmt(s, &Lifetime::global()).unwrap();
+    }
+    assert_eq!(
+        run(&mut ctxt, &Expr::Borrow(Lval::new("x", 0), false)),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 0)))
+    );
+}
@@ -0,0 +1,688 @@
+use crate::lexer::{self, Position, Spanned, Token};
+use crate::utils::{
+    BinOp, Binding, EnumDef, Expr, FnDef, Ident, Lifetime, Lval, Pattern, Span, Stmt, TypeAnn,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Lex(lexer::Error),
+    UnexpectedToken(Token, Position, &'static str),
+    UnexpectedEof(&'static str),
+    NestingLimit(Position),
+}
+
+impl From<lexer::Error> for Error {
+    fn from(e: lexer::Error) -> Self {
+        Error::Lex(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Lex(e) => write!(f, "{}", e),
+            Error::UnexpectedToken(tok, pos, expected) => {
+                write!(f, "{}: expected {}, found `{}`", pos, expected, tok)
+            }
+            Error::UnexpectedEof(expected) => {
+                write!(f, "unexpected end of input, expected {}", expected)
+            }
+            Error::NestingLimit(pos) => {
+                write!(f, "{}: nested more than {} deep", pos, MAX_NESTING)
+            }
+        }
+    }
+}
+
+pub type ParseResult<T> = Result<T, Error>;
+
+/// How deep expressions and types may nest. The parser and the passes
+/// after it recurse on the tree, so input nested much deeper would
+/// overflow even the interpreter's large stack.
+pub const MAX_NESTING: usize = 256;
+
+pub struct Parser {
+    tokens: Vec<Spanned>,
+    idx: usize,
+    next_lifetime: usize,
+    depth: usize,
+    /// How many expressions or types the parser is inside of.
+    nesting: usize,
+}
+
+impl Parser {
+    pub fn new(src: &str) -> ParseResult<Self> {
+        Ok(Parser {
+            tokens: lexer::tokenize(src)?,
+            idx: 0,
+            next_lifetime: 1,
+            depth: 0,
+            nesting: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx).map(|s| &s.token)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.idx + n).map(|s| &s.token)
+    }
+
+    fn next(&mut self, expected: &'static str) -> ParseResult<Spanned> {
+        let tok = self
+            .tokens
+            .get(self.idx)
+            .cloned()
+            .ok_or(Error::UnexpectedEof(expected))?;
+        self.idx += 1;
+        Ok(tok)
+    }
+
+    fn unexpected(&self, expected: &'static str) -> Error {
+        match self.tokens.get(self.idx) {
+            Some(s) => Error::UnexpectedToken(s.token.clone(), s.span.start, expected),
+            None => Error::UnexpectedEof(expected),
+        }
+    }
+
+    fn expect(&mut self, tok: Token, expected: &'static str) -> ParseResult<()> {
+        if self.peek() == Some(&tok) {
+            self.idx += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    /// Start of the next token, or the end of the input.
+    fn here(&self) -> Position {
+        match self.tokens.get(self.idx) {
+            Some(s) => s.span.start,
+            None => self.tokens.last().map_or(Position::start(), |s| s.span.end),
+        }
+    }
+
+    /// Span from `start` to the end of the last token consumed.
+    fn span_from(&self, start: Position) -> Span {
+        let end = match self.idx {
+            0 => start,
+            i => self.tokens[i - 1].span.end,
+        };
+        Span { start, end }
+    }
+
+    /// Tag `e` with its span. Places already carry theirs.
+    fn tag(&self, e: Expr, start: Position) -> Expr {
+        match e {
+            Expr::Lval(..) | Expr::Borrow(..) | Expr::Spanned(..) => e,
+            e => Expr::spanned(e, self.span_from(start)),
+        }
+    }
+
+    /// Run `parse` one level deeper, failing past `MAX_NESTING`.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.nesting == MAX_NESTING {
+            return Err(Error::NestingLimit(self.here()));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.idx == self.tokens.len()
+    }
+
+    fn fresh_lifetime(&mut self) -> Lifetime {
+        let l = Lifetime(self.next_lifetime);
+        self.next_lifetime += 1;
+        l
+    }
+
+    /// Parse a whole program: a block body without the surrounding braces.
+    pub fn parse_program(&mut self) -> ParseResult<Expr> {
+        let lt = self.fresh_lifetime();
+        let (stmts, final_e) = self.parse_body()?;
+        if !self.at_end() {
+            return Err(self.unexpected("end of input"));
+        }
+        Ok(Expr::block(stmts, final_e, lt))
+    }
+
+    /// Statements followed by an optional trailing expression, which defaults
+    /// to `()`. Stops at `}` or end of input.
+    pub fn parse_body(&mut self) -> ParseResult<(Vec<Stmt>, Expr)> {
+        let mut stmts = vec![];
+        loop {
+            let start = self.here();
+            match self.peek() {
+                None | Some(Token::Rbracket) => return Ok((stmts, Expr::Unit)),
+                Some(Token::Semicolon) => {
+                    self.idx += 1;
+                }
+                Some(Token::Let) => {
+                    let stmt = self.parse_let()?;
+                    stmts.push(Stmt::spanned(stmt, self.span_from(start)));
+                }
+                Some(Token::Fn) if self.depth == 0 => {
+                    let stmt = self.parse_fn()?;
+                    stmts.push(Stmt::spanned(stmt, self.span_from(start)));
+                }
+                Some(Token::Enum) if self.depth == 0 => {
+                    let stmt = self.parse_enum()?;
+                    stmts.push(Stmt::spanned(stmt, self.span_from(start)));
+                }
+                _ => {
+                    // like Rust, a statement starting with a block ends with it
+                    let e = if matches!(
+                        self.peek(),
+                        Some(Token::Lbracket | Token::If | Token::While | Token::Match)
+                    ) {
+                        self.parse_atom()?
+                    } else {
+                        self.parse_expr()?
+                    };
+                    match self.peek() {
+                        Some(Token::Semicolon) => {
+                            self.idx += 1;
+                            stmts.push(Stmt::spanned(Stmt::Expr(e), self.span_from(start)));
+                        }
+                        Some(Token::Eq) => {
+                            let lv = match e {
+                                Expr::Lval(lv, _) => lv,
+                                _ => return Err(self.unexpected("`;`")),
+                            };
+                            self.idx += 1;
+                            let rhs = self.parse_expr()?;
+                            self.expect(Token::Semicolon, "`;`")?;
+                            let stmt = Stmt::Assign(lv, rhs);
+                            stmts.push(Stmt::spanned(stmt, self.span_from(start)));
+                        }
+                        None | Some(Token::Rbracket) => return Ok((stmts, e)),
+                        // blocks used as statements don't need a `;`
+                        _ if matches!(
+                            e.peel(),
+                            Expr::Block(..) | Expr::If(..) | Expr::While(..) | Expr::Match(..)
+                        ) =>
+                        {
+                            stmts.push(Stmt::spanned(Stmt::Expr(e), self.span_from(start)))
+                        }
+                        _ => return Err(self.unexpected("`;`")),
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_ident(&mut self) -> ParseResult<Ident> {
+        match self.next("identifier")? {
+            Spanned {
+                token: Token::Var(x),
+                ..
+            } => Ok(x),
+            s => Err(Error::UnexpectedToken(s.token, s.span.start, "identifier")),
+        }
+    }
+
+    fn parse_lifetime(&mut self) -> ParseResult<Ident> {
+        match self.next("lifetime")? {
+            Spanned {
+                token: Token::Lifetime(a),
+                ..
+            } => Ok(a),
+            s => Err(Error::UnexpectedToken(s.token, s.span.start, "lifetime")),
+        }
+    }
+
+    fn parse_let(&mut self) -> ParseResult<Stmt> {
+        self.expect(Token::Let, "`let`")?;
+        self.expect(Token::Mut, "`mut`")?;
+        let ident = self.parse_ident()?;
+        self.expect(Token::Eq, "`=`")?;
+        let rhs = self.parse_expr()?;
+        self.expect(Token::Semicolon, "`;`")?;
+        Ok(Stmt::LetMut(ident, rhs))
+    }
+
+    /// `fn name<'a, 'b: 'a>(x: T, ...) -> T { ... }`, only allowed at the
+    /// top level.
+    fn parse_fn(&mut self) -> ParseResult<Stmt> {
+        self.expect(Token::Fn, "`fn`")?;
+        let name = self.parse_ident()?;
+        let mut lifetimes = vec![];
+        let mut bounds = vec![];
+        if self.peek() == Some(&Token::Lt) {
+            self.idx += 1;
+            while self.peek() != Some(&Token::Gt) {
+                let a = self.parse_lifetime()?;
+                if self.peek() == Some(&Token::Colon) {
+                    self.idx += 1;
+                    bounds.push((a.clone(), self.parse_lifetime()?));
+                    while self.peek() == Some(&Token::Plus) {
+                        self.idx += 1;
+                        bounds.push((a.clone(), self.parse_lifetime()?));
+                    }
+                }
+                lifetimes.push((a, self.fresh_lifetime()));
+                if self.peek() != Some(&Token::Gt) {
+                    self.expect(Token::Comma, "`,` or `>`")?;
+                }
+            }
+            self.idx += 1;
+        }
+        self.expect(Token::Lparen, "`(`")?;
+        let mut params = vec![];
+        while self.peek() != Some(&Token::Rparen) {
+            let x = self.parse_ident()?;
+            self.expect(Token::Colon, "`:`")?;
+            params.push((x, self.parse_type()?));
+            if self.peek() != Some(&Token::Rparen) {
+                self.expect(Token::Comma, "`,` or `)`")?;
+            }
+        }
+        self.idx += 1;
+        let ret = if self.peek() == Some(&Token::Arrow) {
+            self.idx += 1;
+            self.parse_type()?
+        } else {
+            TypeAnn::Unit
+        };
+        let lifetime = self.fresh_lifetime();
+        let body = self.parse_block()?;
+        for (_, ann) in &params {
+            for _ in 0..ann.elided_refs() {
+                lifetimes.push((String::from("'_"), self.fresh_lifetime()));
+            }
+        }
+        Ok(Stmt::Fn(FnDef {
+            name,
+            params,
+            ret,
+            lifetimes,
+            bounds,
+            body,
+            lifetime,
+        }))
+    }
+
+    /// `enum Name { A, B(T, ...), ... }`, only allowed at the top level.
+    fn parse_enum(&mut self) -> ParseResult<Stmt> {
+        self.expect(Token::Enum, "`enum`")?;
+        let name = self.parse_ident()?;
+        self.expect(Token::Lbracket, "`{`")?;
+        let mut variants = vec![];
+        while self.peek() != Some(&Token::Rbracket) {
+            let variant = self.parse_ident()?;
+            let mut fields = vec![];
+            if self.peek() == Some(&Token::Lparen) {
+                self.idx += 1;
+                while self.peek() != Some(&Token::Rparen) {
+                    fields.push(self.parse_type()?);
+                    if self.peek() != Some(&Token::Rparen) {
+                        self.expect(Token::Comma, "`,` or `)`")?;
+                    }
+                }
+                self.idx += 1;
+            }
+            variants.push((variant, fields));
+            if self.peek() != Some(&Token::Rbracket) {
+                self.expect(Token::Comma, "`,` or `}`")?;
+            }
+        }
+        self.idx += 1;
+        Ok(Stmt::Enum(EnumDef { name, variants }))
+    }
+
+    /// `Enum::Variant`, the start of a variant expression or pattern.
+    fn parse_variant_name(&mut self) -> ParseResult<(Ident, Ident)> {
+        let name = self.parse_ident()?;
+        self.expect(Token::ColonColon, "`::`")?;
+        Ok((name, self.parse_ident()?))
+    }
+
+    /// `_`, or `Enum::Variant` with a binding for each field.
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        if matches!(self.peek(), Some(Token::Var(x)) if x == "_") {
+            self.idx += 1;
+            return Ok(Pattern::Wildcard);
+        }
+        let (name, variant) = self.parse_variant_name()?;
+        let mut bindings = vec![];
+        if self.peek() == Some(&Token::Lparen) {
+            self.idx += 1;
+            while self.peek() != Some(&Token::Rparen) {
+                let binding = match self.peek() {
+                    Some(Token::Ref) => {
+                        self.idx += 1;
+                        let is_mut = self.peek() == Some(&Token::Mut);
+                        if is_mut {
+                            self.idx += 1;
+                        }
+                        Binding::Ref(self.parse_ident()?, is_mut)
+                    }
+                    _ => match self.parse_ident()? {
+                        x if x == "_" => Binding::Ignore,
+                        x => Binding::Move(x, false),
+                    },
+                };
+                bindings.push(binding);
+                if self.peek() != Some(&Token::Rparen) {
+                    self.expect(Token::Comma, "`,` or `)`")?;
+                }
+            }
+            self.idx += 1;
+        }
+        Ok(Pattern::Variant(name, variant, bindings))
+    }
+
+    pub fn parse_type(&mut self) -> ParseResult<TypeAnn> {
+        self.nested(Self::parse_bare_type)
+    }
+
+    fn parse_bare_type(&mut self) -> ParseResult<TypeAnn> {
+        match self.peek() {
+            Some(Token::Var(x)) if x == "int" => {
+                self.idx += 1;
+                Ok(TypeAnn::Int)
+            }
+            Some(Token::Var(x)) if x == "bool" => {
+                self.idx += 1;
+                Ok(TypeAnn::Bool)
+            }
+            Some(Token::Lparen) if self.peek_at(1) == Some(&Token::Rparen) => {
+                self.idx += 2;
+                Ok(TypeAnn::Unit)
+            }
+            Some(Token::Lparen) => {
+                self.idx += 1;
+                let first = self.parse_type()?;
+                if self.peek() == Some(&Token::Rparen) {
+                    self.idx += 1;
+                    return Ok(first);
+                }
+                let mut elems = vec![first];
+                while self.peek() != Some(&Token::Rparen) {
+                    self.expect(Token::Comma, "`,` or `)`")?;
+                    if self.peek() == Some(&Token::Rparen) {
+                        break;
+                    }
+                    elems.push(self.parse_type()?);
+                }
+                self.idx += 1;
+                Ok(TypeAnn::Tuple(elems))
+            }
+            Some(Token::Box) => {
+                self.idx += 1;
+                Ok(TypeAnn::Box(Box::new(self.parse_type()?)))
+            }
+            Some(Token::Ampersand) => {
+                self.idx += 1;
+                let name = match self.peek() {
+                    Some(Token::Lifetime(_)) => Some(self.parse_lifetime()?),
+                    _ => None,
+                };
+                let is_mut = self.peek() == Some(&Token::Mut);
+                if is_mut {
+                    self.idx += 1;
+                }
+                Ok(TypeAnn::Ref(Box::new(self.parse_type()?), is_mut, name))
+            }
+            Some(Token::Var(_)) => Ok(TypeAnn::Enum(self.parse_ident()?)),
+            _ => Err(self.unexpected("type")),
+        }
+    }
+
+    fn parse_block(&mut self) -> ParseResult<Expr> {
+        if self.peek() != Some(&Token::Lbracket) {
+            return Err(self.unexpected("`{`"));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_args(&mut self) -> ParseResult<Vec<Expr>> {
+        self.expect(Token::Lparen, "`(`")?;
+        let mut args = vec![];
+        while self.peek() != Some(&Token::Rparen) {
+            args.push(self.parse_expr()?);
+            if self.peek() != Some(&Token::Rparen) {
+                self.expect(Token::Comma, "`,` or `)`")?;
+            }
+        }
+        self.idx += 1;
+        Ok(args)
+    }
+
+    /// `*...*x.0.1`: the fields come first, as in `Lval`, so a field behind
+    /// a reference, as in `(*r).1`, is not a place.
+    pub fn parse_lval(&mut self) -> ParseResult<Lval> {
+        let start = self.here();
+        let mut derefs = 0;
+        while self.peek() == Some(&Token::Star) {
+            self.idx += 1;
+            derefs += 1;
+        }
+        match self.peek() {
+            Some(Token::Var(x)) => {
+                let mut lv = Lval::new(x, derefs);
+                self.idx += 1;
+                while self.peek() == Some(&Token::Dot) {
+                    self.idx += 1;
+                    match self.peek() {
+                        Some(&Token::Int(n)) if n >= 0 => lv.fields.push(n as usize),
+                        _ => return Err(self.unexpected("field index")),
+                    }
+                    self.idx += 1;
+                }
+                Ok(lv.at(self.span_from(start)))
+            }
+            _ => Err(self.unexpected("place expression")),
+        }
+    }
+
+    /// Comparisons bind loosest and don't chain.
+    pub fn parse_expr(&mut self) -> ParseResult<Expr> {
+        let start = self.here();
+        let lhs = self.parse_sum()?;
+        let op = match self.peek() {
+            Some(Token::Lt) => BinOp::Lt,
+            Some(Token::Le) => BinOp::Le,
+            Some(Token::Gt) => BinOp::Gt,
+            Some(Token::Ge) => BinOp::Ge,
+            Some(Token::EqEq) => BinOp::Eq,
+            Some(Token::Ne) => BinOp::Ne,
+            _ => return Ok(lhs),
+        };
+        self.idx += 1;
+        let e = Expr::binop(op, lhs, self.parse_sum()?);
+        Ok(self.tag(e, start))
+    }
+
+    fn parse_sum(&mut self) -> ParseResult<Expr> {
+        let start = self.here();
+        let mut lhs = self.parse_product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinOp::Add,
+                Some(Token::Minus) => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.idx += 1;
+            let e = Expr::binop(op, lhs, self.parse_product()?);
+            lhs = self.tag(e, start);
+        }
+    }
+
+    fn parse_product(&mut self) -> ParseResult<Expr> {
+        let start = self.here();
+        let mut lhs = self.parse_atom()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinOp::Mul,
+                Some(Token::Slash) => BinOp::Div,
+                Some(Token::Percent) => BinOp::Rem,
+                _ => return Ok(lhs),
+            };
+            self.idx += 1;
+            let e = Expr::binop(op, lhs, self.parse_atom()?);
+            lhs = self.tag(e, start);
+        }
+    }
+
+    fn parse_atom(&mut self) -> ParseResult<Expr> {
+        let start = self.here();
+        let e = self.nested(|p| p.parse_bare_atom(start))?;
+        Ok(self.tag(e, start))
+    }
+
+    fn parse_bare_atom(&mut self, start: Position) -> ParseResult<Expr> {
+        match self.peek() {
+            Some(Token::Int(n)) => {
+                let n = *n;
+                self.idx += 1;
+                Ok(Expr::Int(n))
+            }
+            Some(Token::Lparen) if self.peek_at(1) == Some(&Token::Rparen) => {
+                self.idx += 2;
+                Ok(Expr::Unit)
+            }
+            Some(Token::Lparen) => {
+                self.idx += 1;
+                let e = self.parse_expr()?;
+                if self.peek() != Some(&Token::Comma) {
+                    self.expect(Token::Rparen, "`)`")?;
+                    return Ok(e);
+                }
+                // a tuple, where `(e,)` has one element
+                let mut elems = vec![e];
+                while self.peek() != Some(&Token::Rparen) {
+                    self.expect(Token::Comma, "`,` or `)`")?;
+                    if self.peek() == Some(&Token::Rparen) {
+                        break;
+                    }
+                    elems.push(self.parse_expr()?);
+                }
+                self.idx += 1;
+                Ok(Expr::Tuple(elems))
+            }
+            Some(Token::Box) => {
+                self.idx += 1;
+                Ok(Expr::boxx(self.parse_atom()?))
+            }
+            // `-e` for anything but a literal, which the lexer already signs
+            Some(Token::Minus) => {
+                self.idx += 1;
+                Ok(Expr::binop(BinOp::Sub, Expr::Int(0), self.parse_atom()?))
+            }
+            Some(Token::Ampersand) => {
+                self.idx += 1;
+                let is_mut = self.peek() == Some(&Token::Mut);
+                if is_mut {
+                    self.idx += 1;
+                }
+                let lv = self.parse_lval()?;
+                // a borrow's place spans the whole `&mut x`, so that types
+                // holding it point back at where the borrow was taken
+                Ok(Expr::Borrow(lv.at(self.span_from(start)), is_mut))
+            }
+            Some(Token::True) => {
+                self.idx += 1;
+                Ok(Expr::Bool(true))
+            }
+            Some(Token::False) => {
+                self.idx += 1;
+                Ok(Expr::Bool(false))
+            }
+            Some(Token::If) => {
+                self.idx += 1;
+                let cond = self.parse_expr()?;
+                let then_e = self.parse_block()?;
+                let else_e = match (self.peek(), self.peek_at(1)) {
+                    (Some(Token::Else), Some(Token::If)) => {
+                        self.idx += 1;
+                        // `else if` is sugar for `else { if ... }`
+                        let lt = self.fresh_lifetime();
+                        Expr::block(vec![], self.parse_atom()?, lt)
+                    }
+                    (Some(Token::Else), _) => {
+                        self.idx += 1;
+                        self.parse_block()?
+                    }
+                    _ => Expr::block(vec![], Expr::Unit, self.fresh_lifetime()),
+                };
+                Ok(Expr::iff(cond, then_e, else_e))
+            }
+            Some(Token::While) => {
+                self.idx += 1;
+                let cond = self.parse_expr()?;
+                Ok(Expr::whilee(cond, self.parse_block()?))
+            }
+            Some(Token::Lbracket) => {
+                self.idx += 1;
+                let lt = self.fresh_lifetime();
+                self.depth += 1;
+                let (stmts, final_e) = self.parse_body()?;
+                self.depth -= 1;
+                self.expect(Token::Rbracket, "`}`")?;
+                Ok(Expr::block(stmts, final_e, lt))
+            }
+            Some(Token::Match) => {
+                self.idx += 1;
+                let lt = self.fresh_lifetime();
+                let scrutinee = self.parse_expr()?;
+                self.expect(Token::Lbracket, "`{`")?;
+                let mut arms = vec![];
+                while self.peek() != Some(&Token::Rbracket) {
+                    let pattern = self.parse_pattern()?;
+                    self.expect(Token::FatArrow, "`=>`")?;
+                    let body = self.parse_expr()?;
+                    // as in Rust, a block arm needs no `,`
+                    let block = matches!(body.peel(), Expr::Block(..));
+                    arms.push((pattern, body));
+                    match self.peek() {
+                        Some(Token::Comma) => self.idx += 1,
+                        Some(Token::Rbracket) => {}
+                        _ if block => {}
+                        _ => return Err(self.unexpected("`,` or `}`")),
+                    }
+                }
+                self.idx += 1;
+                Ok(Expr::Match(Box::new(scrutinee), arms, lt))
+            }
+            Some(Token::Var(_)) if self.peek_at(1) == Some(&Token::ColonColon) => {
+                let (name, variant) = self.parse_variant_name()?;
+                let args = if self.peek() == Some(&Token::Lparen) {
+                    self.parse_args()?
+                } else {
+                    vec![]
+                };
+                Ok(Expr::Variant(name, variant, args))
+            }
+            Some(Token::Var(f)) if self.peek_at(1) == Some(&Token::Lparen) => {
+                let f = f.clone();
+                self.idx += 1;
+                Ok(Expr::Call(f, self.parse_args()?))
+            }
+            Some(Token::Star) | Some(Token::Var(_)) => Ok(Expr::Lval(self.parse_lval()?, false)),
+            _ => Err(self.unexpected("expression")),
+        }
+    }
+}
+
+/// Lex and parse `src` as a top-level program block.
+pub fn parse_program(src: &str) -> ParseResult<Expr> {
+    Parser::new(src)?.parse_program()
+}
+
+/// Lex and parse `src` as a bare list of statements and a trailing expression,
+/// without wrapping them in a block.
+pub fn parse_body(src: &str) -> ParseResult<(Vec<Stmt>, Expr)> {
+    let mut parser = Parser::new(src)?;
+    let body = parser.parse_body()?;
+    if !parser.at_end() {
+        return Err(parser.unexpected("end of input"));
+    }
+    Ok(body)
+}
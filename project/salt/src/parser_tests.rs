@@ -0,0 +1,476 @@
+use crate::lexer::{self, Position, Token};
+use crate::parser::{parse_program, Error, MAX_NESTING};
+use crate::utils::{BinOp, Binding, EnumDef, Expr, FnDef, Lifetime, Lval, Pattern, Stmt, TypeAnn};
+
+#[test]
+fn parse_let_box() {
+    assert_eq!(
+        parse_program("let mut x = box 5;"),
+        Ok(Expr::block(
+            vec![Stmt::LetMut(String::from("x"), Expr::boxx(Expr::Int(5)))],
+            Expr::Unit,
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_assign_deref() {
+    assert_eq!(
+        parse_program("*x = 3; **y = -1;"),
+        Ok(Expr::block(
+            vec![
+                Stmt::Assign(Lval::new("x", 1), Expr::Int(3)),
+                Stmt::Assign(Lval::new("y", 2), Expr::Int(-1)),
+            ],
+            Expr::Unit,
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_borrows() {
+    assert_eq!(
+        parse_program("let mut z = &mut *y; let mut w = &x; z"),
+        Ok(Expr::block(
+            vec![
+                Stmt::LetMut(String::from("z"), Expr::Borrow(Lval::new("y", 1), true)),
+                Stmt::LetMut(String::from("w"), Expr::Borrow(Lval::new("x", 0), false)),
+            ],
+            Expr::Lval(Lval::new("z", 0), false),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_unit_and_parens() {
+    assert_eq!(
+        parse_program("let mut u = (); (box (7))"),
+        Ok(Expr::block(
+            vec![Stmt::LetMut(String::from("u"), Expr::Unit)],
+            Expr::boxx(Expr::Int(7)),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_nested_blocks() {
+    let src = "let mut x = box 1;
+                   { let mut y = &x; { *y } }
+                   { () };
+                   x";
+    assert_eq!(
+        parse_program(src),
+        Ok(Expr::block(
+            vec![
+                Stmt::LetMut(String::from("x"), Expr::boxx(Expr::Int(1))),
+                Stmt::Expr(Expr::block(
+                    vec![Stmt::LetMut(
+                        String::from("y"),
+                        Expr::Borrow(Lval::new("x", 0), false)
+                    )],
+                    Expr::block(vec![], Expr::Lval(Lval::new("y", 1), false), Lifetime(3)),
+                    Lifetime(2)
+                )),
+                Stmt::Expr(Expr::block(vec![], Expr::Unit, Lifetime(4))),
+            ],
+            Expr::Lval(Lval::new("x", 0), false),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_err_token() {
+    assert_eq!(
+        parse_program("let mut x = 1\nlet mut y = 2;"),
+        Err(Error::UnexpectedToken(
+            Token::Let,
+            Position { line: 2, col: 1 },
+            "`;`"
+        ))
+    );
+    assert_eq!(
+        parse_program("let x = 1;"),
+        Err(Error::UnexpectedToken(
+            Token::Var(String::from("x")),
+            Position { line: 1, col: 5 },
+            "`mut`"
+        ))
+    );
+    assert_eq!(
+        parse_program("box 1 = 2;"),
+        Err(Error::UnexpectedToken(
+            Token::Eq,
+            Position { line: 1, col: 7 },
+            "`;`"
+        ))
+    );
+    // tokens are shown as written
+    let message = |src| parse_program(src).unwrap_err().to_string();
+    assert_eq!(message("*x.0 0"), "1:6: expected `;`, found `0`");
+    assert_eq!(
+        message("let mut x = 1 let"),
+        "1:15: expected `;`, found `let`"
+    );
+}
+
+#[test]
+fn parse_err_eof() {
+    assert_eq!(
+        parse_program("{ let mut x = &"),
+        Err(Error::UnexpectedEof("place expression"))
+    );
+    assert_eq!(parse_program("{ 1 "), Err(Error::UnexpectedEof("`}`")));
+}
+
+#[test]
+fn parse_err_unbalanced() {
+    assert_eq!(
+        parse_program("1 }"),
+        Err(Error::UnexpectedToken(
+            Token::Rbracket,
+            Position { line: 1, col: 3 },
+            "end of input"
+        ))
+    );
+}
+
+#[test]
+fn parse_err_too_deep() {
+    // the deepest input allowed parses on a stack of the size the
+    // interpreter runs on, and anything deeper fails where it gets too deep
+    let deepest = std::thread::Builder::new()
+        .stack_size(256 << 20)
+        .spawn(|| {
+            let parens = |n| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+            let boxes = |n| format!("fn f(x: {}int) {{}}", "box ".repeat(n));
+            (
+                parse_program(&parens(MAX_NESTING - 1)).is_ok(),
+                parse_program(&parens(200000)),
+                parse_program(&boxes(MAX_NESTING - 1)).is_ok(),
+                parse_program(&boxes(200000)),
+            )
+        })
+        .unwrap();
+    let (parens_ok, parens, boxes_ok, boxes) = deepest.join().unwrap();
+    assert!(parens_ok && boxes_ok);
+    assert_eq!(
+        parens,
+        Err(Error::NestingLimit(Position {
+            line: 1,
+            col: MAX_NESTING as u32 + 1
+        }))
+    );
+    assert!(matches!(boxes, Err(Error::NestingLimit(_))));
+}
+
+#[test]
+fn parse_err_lex() {
+    assert_eq!(
+        parse_program("let mut x = 1 # 2;"),
+        Err(Error::Lex(lexer::Error::UnexpectedChar(
+            '#',
+            Position { line: 1, col: 15 }
+        )))
+    );
+}
+
+#[test]
+fn parse_fn_and_call() {
+    assert_eq!(
+        parse_program("fn f(x: &mut box int, y: ()) -> &int { &**x } f(&mut a, ())"),
+        Ok(Expr::block(
+            vec![Stmt::Fn(FnDef {
+                name: String::from("f"),
+                params: vec![
+                    (
+                        String::from("x"),
+                        TypeAnn::Ref(Box::new(TypeAnn::Box(Box::new(TypeAnn::Int))), true, None)
+                    ),
+                    (String::from("y"), TypeAnn::Unit),
+                ],
+                ret: TypeAnn::Ref(Box::new(TypeAnn::Int), false, None),
+                lifetimes: vec![(String::from("'_"), Lifetime(4))],
+                bounds: vec![],
+                body: Expr::block(vec![], Expr::Borrow(Lval::new("x", 2), false), Lifetime(3)),
+                lifetime: Lifetime(2),
+            })],
+            Expr::Call(
+                String::from("f"),
+                vec![Expr::Borrow(Lval::new("a", 0), true), Expr::Unit]
+            ),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_err_nested_fn() {
+    assert_eq!(
+        parse_program("{ fn f() {} }"),
+        Err(Error::UnexpectedToken(
+            Token::Fn,
+            Position { line: 1, col: 3 },
+            "expression"
+        ))
+    );
+}
+
+#[test]
+fn parse_precedence() {
+    let n = |k| Expr::Int(k);
+    assert_eq!(
+        parse_program("1 + 2 * *x - -3 < 4 % (5 - 6)"),
+        Ok(Expr::block(
+            vec![],
+            Expr::binop(
+                BinOp::Lt,
+                Expr::binop(
+                    BinOp::Sub,
+                    Expr::binop(
+                        BinOp::Add,
+                        n(1),
+                        Expr::binop(BinOp::Mul, n(2), Expr::Lval(Lval::new("x", 1), false))
+                    ),
+                    n(-3)
+                ),
+                Expr::binop(BinOp::Rem, n(4), Expr::binop(BinOp::Sub, n(5), n(6)))
+            ),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_block_stmt_then_deref() {
+    assert_eq!(
+        parse_program("{ 1 } *x = 2;"),
+        Ok(Expr::block(
+            vec![
+                Stmt::Expr(Expr::block(vec![], Expr::Int(1), Lifetime(2))),
+                Stmt::Assign(Lval::new("x", 1), Expr::Int(2)),
+            ],
+            Expr::Unit,
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_if_while() {
+    assert_eq!(
+        parse_program("while x < 3 { x = x + 1; } if b { 1 } else if true { 2 }"),
+        Ok(Expr::block(
+            vec![Stmt::Expr(Expr::whilee(
+                Expr::binop(
+                    BinOp::Lt,
+                    Expr::Lval(Lval::new("x", 0), false),
+                    Expr::Int(3)
+                ),
+                Expr::block(
+                    vec![Stmt::Assign(
+                        Lval::new("x", 0),
+                        Expr::binop(
+                            BinOp::Add,
+                            Expr::Lval(Lval::new("x", 0), false),
+                            Expr::Int(1)
+                        )
+                    )],
+                    Expr::Unit,
+                    Lifetime(2)
+                )
+            ))],
+            Expr::iff(
+                Expr::Lval(Lval::new("b", 0), false),
+                Expr::block(vec![], Expr::Int(1), Lifetime(3)),
+                Expr::block(
+                    vec![],
+                    Expr::iff(
+                        Expr::Bool(true),
+                        Expr::block(vec![], Expr::Int(2), Lifetime(5)),
+                        Expr::block(vec![], Expr::Unit, Lifetime(6))
+                    ),
+                    Lifetime(4)
+                )
+            ),
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn spans() {
+    let program = parse_program("let mut x = 1;\n*x = &mut y;").unwrap();
+    let Expr::Block(stmts, _, _) = program.peel() else {
+        panic!("expected a block");
+    };
+    let at = |line, col| Position { line, col };
+    let span = stmts[1].span().unwrap();
+    assert_eq!((span.start, span.end), (at(2, 1), at(2, 13)));
+    let Stmt::Assign(lv, rhs) = stmts[1].peel() else {
+        panic!("expected an assignment");
+    };
+    assert_eq!((lv.span.start, lv.span.end), (at(2, 1), at(2, 3)));
+    // a borrow spans the whole `&mut y`
+    let Expr::Borrow(place, _) = rhs else {
+        panic!("expected a borrow");
+    };
+    assert_eq!((place.span.start, place.span.end), (at(2, 6), at(2, 12)));
+}
+
+#[test]
+fn parse_err_field_behind_deref() {
+    assert_eq!(
+        parse_program("let mut a = (*r).1;"),
+        Err(Error::UnexpectedToken(
+            Token::Dot,
+            Position { line: 1, col: 17 },
+            "`;`"
+        ))
+    );
+    assert_eq!(
+        parse_program("(*r).1 = 2;").unwrap_err().to_string(),
+        "1:5: expected `;`, found `.`"
+    );
+}
+
+#[test]
+fn parse_tuples() {
+    assert_eq!(
+        parse_program("fn f(p: (int, (box int,))) {} *x.1.0 = (1, (2,), ());"),
+        Ok(Expr::block(
+            vec![
+                Stmt::Fn(FnDef {
+                    name: String::from("f"),
+                    params: vec![(
+                        String::from("p"),
+                        TypeAnn::Tuple(vec![
+                            TypeAnn::Int,
+                            TypeAnn::Tuple(vec![TypeAnn::Box(Box::new(TypeAnn::Int))])
+                        ])
+                    )],
+                    ret: TypeAnn::Unit,
+                    lifetimes: vec![],
+                    bounds: vec![],
+                    body: Expr::block(vec![], Expr::Unit, Lifetime(3)),
+                    lifetime: Lifetime(2),
+                }),
+                Stmt::Assign(
+                    Lval::field("x", &[1, 0], 1),
+                    Expr::Tuple(vec![
+                        Expr::Int(1),
+                        Expr::Tuple(vec![Expr::Int(2)]),
+                        Expr::Unit
+                    ])
+                ),
+            ],
+            Expr::Unit,
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_lifetimes() {
+    let a = || Some(String::from("'a"));
+    assert_eq!(
+        parse_program("fn f<'a, 'b: 'a>(x: &'a mut int, y: &int) -> &'a int { x }"),
+        Ok(Expr::block(
+            vec![Stmt::Fn(FnDef {
+                name: String::from("f"),
+                params: vec![
+                    (
+                        String::from("x"),
+                        TypeAnn::Ref(Box::new(TypeAnn::Int), true, a())
+                    ),
+                    (
+                        String::from("y"),
+                        TypeAnn::Ref(Box::new(TypeAnn::Int), false, None)
+                    ),
+                ],
+                ret: TypeAnn::Ref(Box::new(TypeAnn::Int), false, a()),
+                lifetimes: vec![
+                    (String::from("'a"), Lifetime(2)),
+                    (String::from("'b"), Lifetime(3)),
+                    (String::from("'_"), Lifetime(6)),
+                ],
+                bounds: vec![(String::from("'b"), String::from("'a"))],
+                body: Expr::block(vec![], Expr::Lval(Lval::new("x", 0), false), Lifetime(5)),
+                lifetime: Lifetime(4),
+            })],
+            Expr::Unit,
+            Lifetime(1)
+        ))
+    );
+}
+
+#[test]
+fn parse_enums_and_match() {
+    let s = String::from;
+    assert_eq!(
+        parse_program(
+            "enum E { A, B(int, box E), }
+                 match E::B(1, box E::A) { E::B(n, ref r) => n, E::A => { 1 } _ => 0 }"
+        ),
+        Ok(Expr::block(
+            vec![Stmt::Enum(EnumDef {
+                name: s("E"),
+                variants: vec![
+                    (s("A"), vec![]),
+                    (
+                        s("B"),
+                        vec![TypeAnn::Int, TypeAnn::Box(Box::new(TypeAnn::Enum(s("E"))))]
+                    ),
+                ],
+            })],
+            Expr::Match(
+                Box::new(Expr::Variant(
+                    s("E"),
+                    s("B"),
+                    vec![
+                        Expr::Int(1),
+                        Expr::boxx(Expr::Variant(s("E"), s("A"), vec![]))
+                    ]
+                )),
+                vec![
+                    (
+                        Pattern::Variant(
+                            s("E"),
+                            s("B"),
+                            vec![Binding::Move(s("n"), false), Binding::Ref(s("r"), false)]
+                        ),
+                        Expr::Lval(Lval::new("n", 0), false)
+                    ),
+                    (
+                        Pattern::Variant(s("E"), s("A"), vec![]),
+                        Expr::block(vec![], Expr::Int(1), Lifetime(3))
+                    ),
+                    (Pattern::Wildcard, Expr::Int(0)),
+                ],
+                Lifetime(2)
+            ),
+            Lifetime(1)
+        ))
+    );
+    // only an arm that is a block can leave out its `,`
+    assert_eq!(
+        parse_program("match e { _ => 1 _ => 2 }"),
+        Err(Error::UnexpectedToken(
+            Token::Var(s("_")),
+            Position { line: 1, col: 18 },
+            "`,` or `}`"
+        ))
+    );
+    assert_eq!(
+        parse_program("{ enum E { A } }"),
+        Err(Error::UnexpectedToken(
+            Token::Enum,
+            Position { line: 1, col: 3 },
+            "expression"
+        ))
+    );
+}
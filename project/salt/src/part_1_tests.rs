@@ -1,509 +1,407 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::eval::{Context, Store, Value};
-    use crate::utils::{Copyable, Expr, Lifetime, Lval, Mutable, Stmt};
-
-    #[test]
-    fn locate_var() {
-        let mut store = Store::default();
-        store.insert("x", Some(Value::Unit), Lifetime::global());
-        assert_eq!(store.locate(&Lval::var("x")), "x");
-    }
+use crate::eval::{Context, RuntimeError, Store, Value};
+use crate::utils::{Expr, Lifetime, Lval, Stmt};
 
-    #[test]
-    fn locate_ref() {
-        let mut store = Store::default();
-        store.insert("1", Some(Value::Unit), Lifetime::global());
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("1"), true)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("y"), false)),
-            Lifetime::global(),
-        );
-        assert_eq!(store.locate(&Lval::new("x", 2)), "1");
-    }
+#[test]
+fn locate_var() {
+    let mut store = Store::default();
+    store.insert("x", Some(Value::Unit), Lifetime::global());
+    assert_eq!(store.locate(&Lval::new("x", 0)), Ok(store.var("x")));
+}
 
-    #[test]
-    #[should_panic]
-    fn locate_panic() {
-        let mut store = Store::default();
-        store.insert("x", Some(Value::Int(1)), Lifetime::global());
-        store.locate(&Lval::new("x", 1));
-    }
+#[test]
+fn locate_ref() {
+    let mut store = Store::default();
+    let b = store.alloc(Some(Value::Unit));
+    store.insert("y", Some(Value::Ref(b.clone(), true)), Lifetime::global());
+    let y = store.var("y");
+    store.insert("x", Some(Value::Ref(y, false)), Lifetime::global());
+    assert_eq!(store.locate(&Lval::new("x", 2)), Ok(b));
+}
 
-    #[test]
-    fn read_var() {
-        let mut store = Store::default();
-        store.insert("x", Some(Value::Int(42)), Lifetime::global());
-        assert_eq!(store.read(&Lval::var("x")).value, Some(Value::Int(42)));
-    }
+#[test]
+fn locate_error() {
+    let mut store = Store::default();
+    store.insert("x", Some(Value::Int(1)), Lifetime::global());
+    assert_eq!(
+        store.locate(&Lval::new("x", 1)),
+        Err(RuntimeError::BadDeref(Lval::new("x", 1)))
+    );
+}
 
-    #[test]
-    fn read_ref_owned() {
-        let mut store = Store::default();
-        store.insert("1", Some(Value::Int(-30)), Lifetime::global());
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("1"), Owned::Yes)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("y"), Owned::No)),
-            Lifetime::global(),
-        );
-        assert_eq!(store.read(&Lval::new("x", 2)).value, Some(Value::Int(-30)));
-    }
+#[test]
+fn read_var() {
+    let mut store = Store::default();
+    store.insert("x", Some(Value::Int(42)), Lifetime::global());
+    assert_eq!(store.read(&Lval::new("x", 0)), Ok(&Some(Value::Int(42))));
+}
 
-    #[test]
-    #[should_panic]
-    fn read_panic() {
-        let mut store = Store::default();
-        store.insert("1", Some(Value::Int(-30)), Lifetime::global());
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("1"), Owned::Yes)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("y"), Owned::No)),
-            Lifetime::global(),
-        );
-        let _ = store.read(&Lval::new("x", 3)).value;
-    }
+/// `x` borrows `y`, which owns a box holding `n`.
+fn ref_to_box(n: i32) -> Store {
+    let mut store = Store::default();
+    let b = store.alloc(Some(Value::Int(n)));
+    store.insert("y", Some(Value::Ref(b, true)), Lifetime::global());
+    let y = store.var("y");
+    store.insert("x", Some(Value::Ref(y, false)), Lifetime::global());
+    store
+}
 
-    #[test]
-    fn write_two_deref() {
-        let mut store = Store::default();
-        store.insert("2", Some(Value::Int(1)), Lifetime::global());
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("x"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "z",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        assert_eq!(
-            store.write(&Lval::new("y", 2), Some(Value::Int(5))),
-            Some(Value::Int(1))
-        );
-        let slot_2 = store.read(&Lval::new("y", 2));
-        assert_eq!(slot_2.value, Some(Value::Int(5)));
-    }
+#[test]
+fn read_ref_owned() {
+    let store = ref_to_box(-30);
+    assert_eq!(store.read(&Lval::new("x", 2)), Ok(&Some(Value::Int(-30))));
+}
 
-    #[test]
-    fn write_deref_read_diff() {
-        let mut store = Store::default();
-        store.insert("2", Some(Value::Int(1)), Lifetime::global());
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("x"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "z",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        assert_eq!(
-            store.write(&Lval::new("y", 2), Some(Value::Int(5))),
-            Some(Value::Int(1))
-        );
-        let slot_2 = store.read(&Lval::new("z", 1));
-        assert_eq!(slot_2.value, Some(Value::Int(5)));
-    }
+#[test]
+fn read_error() {
+    let store = ref_to_box(-30);
+    assert_eq!(
+        store.read(&Lval::new("x", 3)),
+        Err(RuntimeError::BadDeref(Lval::new("x", 3)))
+    );
+}
 
-    #[test]
-    #[should_panic]
-    fn write_panic() {
-        let mut store = Store::default();
-        store.insert("2", Some(Value::Int(1)), Lifetime::global());
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("x"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert(
-            "z",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.write(&Lval::new("y", 3), Some(Value::Int(5)));
-    }
+/// `x` and `z` borrow the same box, and `y` borrows `x`.
+fn shared_box() -> Store {
+    let mut store = Store::default();
+    let b = store.alloc(Some(Value::Int(1)));
+    store.insert("x", Some(Value::Ref(b.clone(), false)), Lifetime::global());
+    let x = store.var("x");
+    store.insert("y", Some(Value::Ref(x, false)), Lifetime::global());
+    store.insert("z", Some(Value::Ref(b, false)), Lifetime::global());
+    store
+}
 
-    #[test]
-    fn drop_owned() {
-        let mut store = Store::default();
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("1"), Owned::Yes)),
-            Lifetime::global(),
-        );
-        store.insert("1", Some(Value::Int(1)), Lifetime::global());
-        store.drop(vec![Some(Value::Ref(String::from("x"), Owned::Yes))]);
-        assert_eq!(store, Store::default());
-    }
+#[test]
+fn write_two_deref() {
+    let mut store = shared_box();
+    assert_eq!(
+        store.write(&Lval::new("y", 2), Some(Value::Int(5))),
+        Ok(Some(Value::Int(1)))
+    );
+    assert_eq!(store.read(&Lval::new("y", 2)), Ok(&Some(Value::Int(5))));
+}
 
-    #[test]
-    fn drop_unowned() {
-        let mut store = Store::default();
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("1"), Owned::No)),
-            Lifetime::global(),
-        );
-        store.insert("1", Some(Value::Int(1)), Lifetime::global());
-        let mut store_2 = Store::default();
-        store_2.insert("1", Some(Value::Int(1)), Lifetime::global());
-        store.drop(vec![Some(Value::Ref(String::from("x"), Owned::Yes))]);
-        assert_eq!(store, store_2);
-    }
+#[test]
+fn write_deref_read_diff() {
+    let mut store = shared_box();
+    assert_eq!(
+        store.write(&Lval::new("y", 2), Some(Value::Int(5))),
+        Ok(Some(Value::Int(1)))
+    );
+    assert_eq!(store.read(&Lval::new("z", 1)), Ok(&Some(Value::Int(5))));
+}
 
-    #[test]
-    fn drop_larger_example() {
-        let mut store = Store::default();
-        store.insert(
-            "x",
-            Some(Value::Ref(String::from("1"), Owned::Yes)),
-            Lifetime(1),
-        );
-        store.insert(
-            "1",
-            Some(Value::Ref(String::from("2"), Owned::No)),
-            Lifetime(2),
-        );
-        store.insert("2", Some(Value::Int(1)), Lifetime(1));
-        store.insert(
-            "y",
-            Some(Value::Ref(String::from("x"), Owned::No)),
-            Lifetime(2),
-        );
-        store.insert(
-            "z",
-            Some(Value::Ref(String::from("2"), Owned::Yes)),
-            Lifetime(2),
-        );
-        store.insert(
-            "w",
-            Some(Value::Ref(String::from("3"), Owned::Yes)),
-            Lifetime(1),
-        );
-        store.insert("3", Some(Value::Int(2)), Lifetime(2));
-        store.insert(
-            "v",
-            Some(Value::Ref(String::from("1"), Owned::No)),
-            Lifetime(1),
-        );
-        store.drop(store.locs_by_lifetime(Lifetime(1)));
-        let mut store_2 = Store::default();
-        store_2.insert(
-            "y",
-            Some(Value::Ref(String::from("x"), Owned::No)),
-            Lifetime(2),
-        );
-        store_2.insert(
-            "z",
-            Some(Value::Ref(String::from("2"), Owned::Yes)),
-            Lifetime(2),
-        );
-        assert_eq!(store, store_2);
-    }
+#[test]
+fn write_error() {
+    let mut store = shared_box();
+    assert_eq!(
+        store.write(&Lval::new("y", 3), Some(Value::Int(5))),
+        Err(RuntimeError::BadDeref(Lval::new("y", 3)))
+    );
+}
 
-    #[test]
-    fn eval_lits() {
-        let mut context = Context::default();
-        assert_eq!(
-            context.eval_expr(&Expr::Unit, Lifetime::global()),
-            Value::Unit
-        );
-        assert_eq!(
-            context.eval_expr(&Expr::Int(234), Lifetime::global()),
-            Value::Int(234)
-        );
-        assert_eq!(context.store, Store::default());
-    }
+#[test]
+fn drop_owned() {
+    let mut store = Store::default();
+    let b = store.alloc(Some(Value::Int(1)));
+    store.insert("x", Some(Value::Ref(b, true)), Lifetime::global());
+    let x = store.var("x");
+    store.drop(vec![Some(Value::Ref(x, true))]);
+    assert_eq!(store, Store::default());
+}
 
-    #[test]
-    fn eval_copy() {
-        let mut context = Context::default();
-        context.store.insert("x", Some(Value::Int(34)), Lifetime(1));
-        let store_2 = context.store.clone();
-        assert_eq!(
-            context.eval_expr(
-                &Expr::Lval(Lval::new("x", 0), Copyable::Yes),
-                Lifetime::global()
-            ),
-            Value::Int(34)
-        );
-        assert_eq!(context.store, store_2);
-    }
+#[test]
+fn drop_unowned() {
+    let mut store = Store::default();
+    let b = store.alloc(Some(Value::Int(1)));
+    store.insert("x", Some(Value::Ref(b, false)), Lifetime::global());
+    let x = store.var("x");
+    let mut store_2 = Store::default();
+    store_2.alloc(Some(Value::Int(1)));
+    store.drop(vec![Some(Value::Ref(x, true))]);
+    assert_eq!(store, store_2);
+}
+
+// Boxes have no lifetime of their own: they go when their owner does.
+#[test]
+fn drop_larger_example() {
+    let mut store = Store::default();
+    let two = store.alloc(Some(Value::Int(1)));
+    let one = store.alloc(Some(Value::Ref(two.clone(), false)));
+    let three = store.alloc(Some(Value::Int(2)));
+    store.insert("x", Some(Value::Ref(one.clone(), true)), Lifetime(1));
+    let x = store.var("x");
+    store.insert("y", Some(Value::Ref(x, false)), Lifetime(2));
+    store.insert("z", Some(Value::Ref(two, true)), Lifetime(2));
+    store.insert("w", Some(Value::Ref(three, true)), Lifetime(1));
+    store.insert("v", Some(Value::Ref(one, false)), Lifetime(1));
+    store.drop(store.locs_by_lifetime(Lifetime(1)));
+
+    let mut store_2 = Store::default();
+    let two = store_2.alloc(Some(Value::Int(1)));
+    let x = store_2.var("x");
+    store_2.insert("y", Some(Value::Ref(x, false)), Lifetime(2));
+    store_2.insert("z", Some(Value::Ref(two, true)), Lifetime(2));
+    assert_eq!(store, store_2);
+}
+
+/// The programs the eval tests below run, which `vm_tests` runs on the VM
+/// too.
+pub(crate) mod programs {
+    use crate::utils::{Expr, Lifetime, Lval, Stmt};
 
-    #[test]
-    fn eval_move() {
-        let mut context = Context::default();
-        context.store.insert("x", Some(Value::Int(5)), Lifetime(1));
-        let mut store_2 = Store::default();
-        store_2.insert("x", None, Lifetime(1));
-        assert_eq!(
-            context.eval_expr(
-                &Expr::Lval(Lval::new("x", 0), Copyable::No),
-                Lifetime::global()
-            ),
-            Value::Int(5)
-        );
-        assert_eq!(context.store, store_2);
+    pub fn let_int(x: &str, n: i32) -> Stmt {
+        Stmt::LetMut(String::from(x), Expr::Int(n))
     }
 
-    #[test]
-    fn eval_box() {
-        let mut context = Context::default();
-        if let Value::Ref(loc, _) =
-            context.eval_expr(&Expr::Box(Box::new(Expr::Int(-1))), Lifetime(5))
-        {
-            let mut store_2 = Store::default();
-            store_2.insert(&loc, Some(Value::Int(-1)), Lifetime::global());
-            assert_eq!(
-                context.store.read(&Lval::new(&loc, 0)).value,
-                Some(Value::Int(-1))
-            );
-            assert_eq!(context.store, store_2);
-        } else {
-            assert!(false);
-        }
+    pub fn let_box(x: &str, n: i32) -> Stmt {
+        Stmt::LetMut(String::from(x), Expr::boxx(Expr::Int(n)))
     }
 
-    #[test]
-    fn eval_box_box() {
-        let mut context = Context::default();
-        let box_box = Expr::Box(Box::new(Expr::Box(Box::new(Expr::Int(12)))));
-        if let Value::Ref(loc, _) = context.eval_expr(&box_box, Lifetime(34)) {
-            assert_eq!(
-                context.store.read(&Lval::new(&loc, 1)).value,
-                Some(Value::Int(12))
-            );
-        }
+    /// `let mut x = box 14; let mut y = box 15;`
+    pub fn two_boxes() -> Vec<Stmt> {
+        vec![let_box("x", 14), let_box("y", 15)]
     }
 
-    #[test]
-    fn eval_let_mut() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("x", 1)).value,
-            Some(Value::Int(14))
-        );
+    pub fn box_box() -> Expr {
+        Expr::boxx(Expr::boxx(Expr::Int(12)))
     }
 
-    #[test]
-    fn eval_assign_copy() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("y"), Expr::Box(Box::new(Expr::Int(15)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Assign(
-                Lval::new("x", 0),
-                Expr::Lval(Lval::new("y", 0), Copyable::Yes),
-            ),
-            Lifetime(4),
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("x", 1)).value,
-            Some(Value::Int(15))
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("y", 1)).value,
-            Some(Value::Int(15))
-        );
+    /// `x = y;`
+    pub fn assign_copy() -> Stmt {
+        Stmt::Assign(Lval::new("x", 0), Expr::Lval(Lval::new("y", 0), true))
     }
 
-    #[test]
-    fn eval_assign_move() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("y"), Expr::Box(Box::new(Expr::Int(15)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Assign(
-                Lval::new("x", 0),
-                Expr::Lval(Lval::new("y", 0), Copyable::No),
-            ),
-            Lifetime(4),
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("x", 1)).value,
-            Some(Value::Int(15))
-        );
-        assert_eq!(context.store.read(&Lval::new("y", 0)).value, None);
+    /// `x = y;`, moving the box
+    pub fn assign_move() -> Stmt {
+        Stmt::Assign(Lval::new("x", 0), Expr::Lval(Lval::new("y", 0), false))
     }
 
-    #[test]
-    fn eval_assign_replace() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("y"), Expr::Box(Box::new(Expr::Int(15)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Assign(
-                Lval::new("x", 0),
-                Expr::Lval(Lval::new("y", 0), Copyable::No),
-            ),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Assign(Lval::new("y", 0), Expr::Box(Box::new(Expr::Int(16)))),
-            Lifetime(4),
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("x", 1)).value,
-            Some(Value::Int(15))
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("y", 1)).value,
-            Some(Value::Int(16))
-        );
+    /// `x = y; y = box 16;`
+    pub fn assign_replace() -> Vec<Stmt> {
+        vec![
+            assign_move(),
+            Stmt::Assign(Lval::new("y", 0), Expr::boxx(Expr::Int(16))),
+        ]
     }
 
-    #[test]
-    fn eval_assign_move_deref() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("y"), Expr::Box(Box::new(Expr::Int(15)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Assign(
-                Lval::new("x", 1),
-                Expr::Lval(Lval::new("y", 1), Copyable::No),
-            ),
-            Lifetime(4),
-        );
-        assert_eq!(
-            context.store.read(&Lval::new("x", 1)).value,
-            Some(Value::Int(15))
-        );
-        assert_eq!(context.store.read(&Lval::new("y", 1)).value, None);
+    /// `*x = *y;`, moving out of `y`'s box
+    pub fn assign_move_deref() -> Stmt {
+        Stmt::Assign(Lval::new("x", 1), Expr::Lval(Lval::new("y", 1), false))
     }
 
-    #[test]
-    fn eval_expr_stmt() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(14)))),
-            Lifetime(4),
-        );
-        context.eval_stmt(
-            &Stmt::Expr(Expr::Lval(Lval::new("x", 1), Copyable::No)),
-            Lifetime(4),
-        );
-        assert_eq!(context.store.read(&Lval::new("x", 1)).value, None);
+    /// `*x;`, moving out of `x`'s box
+    pub fn move_deref() -> Stmt {
+        Stmt::Expr(Expr::Lval(Lval::new("x", 1), false))
     }
 
-    #[test]
-    fn eval_block() {
-        let mut context = Context::default();
-        let e = Expr::Block(
-            vec![Stmt::LetMut(
-                String::from("x"),
-                Expr::Box(Box::new(Expr::Int(23))),
-            )],
-            Box::new(Expr::Unit),
-            Lifetime(3),
-        );
-        context.eval_expr(&e, Lifetime(5));
-        assert_eq!(context.store, Store::default());
+    /// `{ let mut x = box 23; }`
+    pub fn block() -> Expr {
+        Expr::block(vec![let_box("x", 23)], Expr::Unit, Lifetime(3))
     }
 
-    #[test]
-    fn eval_block_ref() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(203)))),
-            Lifetime(4),
-        );
-        let store_2 = context.store.clone();
-        let e = Expr::Block(
-            vec![Stmt::LetMut(
-                String::from("y"),
-                Expr::Borrow(Lval::new("x", 1), Mutable::No),
-            )],
-            Box::new(Expr::Unit),
+    /// `{ let mut y = &*x; }`
+    pub fn block_ref() -> Expr {
+        let borrow = Expr::Borrow(Lval::new("x", 1), false);
+        Expr::block(
+            vec![Stmt::LetMut(String::from("y"), borrow)],
+            Expr::Unit,
             Lifetime(6),
-        );
-        context.eval_expr(&e, Lifetime(4));
-        assert_eq!(context.store, store_2);
+        )
     }
 
-    #[test]
-    fn eval_block_mut_ref() {
-        let mut context = Context::default();
-        context.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(203)))),
-            Lifetime(4),
-        );
-        let e = Expr::Block(
+    /// `{ let mut y = &mut *x; *y = -150; }`
+    pub fn block_mut_ref() -> Expr {
+        let borrow = Expr::Borrow(Lval::new("x", 1), true);
+        Expr::block(
             vec![
-                Stmt::LetMut(
-                    String::from("y"),
-                    Expr::Borrow(Lval::new("x", 1), Mutable::Yes),
-                ),
+                Stmt::LetMut(String::from("y"), borrow),
                 Stmt::Assign(Lval::new("y", 1), Expr::Int(-150)),
             ],
-            Box::new(Expr::Unit),
+            Expr::Unit,
             Lifetime(6),
-        );
-        context.eval_expr(&e, Lifetime(4));
-        let mut context_2 = Context::default();
-        context_2.eval_stmt(
-            &Stmt::LetMut(String::from("x"), Expr::Box(Box::new(Expr::Int(-150)))),
-            Lifetime(4),
-        );
-        assert_eq!(context.store, context_2.store);
+        )
     }
 }
+
+#[test]
+fn eval_lits() {
+    let mut context = Context::default();
+    assert_eq!(
+        context.eval_expr(&Expr::Unit, &Lifetime::global()),
+        Ok(Value::Unit)
+    );
+    assert_eq!(
+        context.eval_expr(&Expr::Int(234), &Lifetime::global()),
+        Ok(Value::Int(234))
+    );
+    assert_eq!(context.store, Store::default());
+}
+
+#[test]
+fn eval_copy() {
+    let mut context = Context::default();
+    context
+        .eval_stmt(&programs::let_int("x", 34), &Lifetime(1))
+        .unwrap();
+    let store_2 = context.store.clone();
+    assert_eq!(
+        context.eval_expr(&Expr::Lval(Lval::new("x", 0), true), &Lifetime::global()),
+        Ok(Value::Int(34))
+    );
+    assert_eq!(context.store, store_2);
+}
+
+#[test]
+fn eval_move() {
+    let mut context = Context::default();
+    context
+        .eval_stmt(&programs::let_int("x", 5), &Lifetime(1))
+        .unwrap();
+    let mut store_2 = Store::default();
+    store_2.insert("x", None, Lifetime(1));
+    assert_eq!(
+        context.eval_expr(&Expr::Lval(Lval::new("x", 0), false), &Lifetime::global()),
+        Ok(Value::Int(5))
+    );
+    assert_eq!(context.store, store_2);
+}
+
+#[test]
+fn eval_box() {
+    let mut context = Context::default();
+    let mut store_2 = Store::default();
+    let loc = store_2.alloc(Some(Value::Int(-1)));
+    assert_eq!(
+        context.eval_expr(&Expr::boxx(Expr::Int(-1)), &Lifetime(5)),
+        Ok(Value::Ref(loc, true))
+    );
+    assert_eq!(context.store, store_2);
+}
+
+#[test]
+fn eval_box_box() {
+    let mut context = Context::default();
+    let b = context
+        .eval_expr(&programs::box_box(), &Lifetime(34))
+        .unwrap();
+    context.store.insert("b", Some(b), Lifetime(34));
+    assert_eq!(
+        context.store.read(&Lval::new("b", 2)),
+        Ok(&Some(Value::Int(12)))
+    );
+}
+
+/// A context after running `stmts`.
+fn run(stmts: &[Stmt]) -> Context {
+    let mut context = Context::default();
+    for stmt in stmts {
+        context.eval_stmt(stmt, &Lifetime(4)).unwrap();
+    }
+    context
+}
+
+/// A context after `let mut x = box 14; let mut y = box 15;`.
+fn two_boxes() -> Context {
+    run(&programs::two_boxes())
+}
+
+#[test]
+fn eval_let_mut() {
+    let context = run(&[programs::let_box("x", 14)]);
+    assert_eq!(
+        context.store.read(&Lval::new("x", 1)),
+        Ok(&Some(Value::Int(14)))
+    );
+}
+
+#[test]
+fn eval_assign_copy() {
+    let mut context = two_boxes();
+    context
+        .eval_stmt(&programs::assign_copy(), &Lifetime(4))
+        .unwrap();
+    assert_eq!(
+        context.store.read(&Lval::new("x", 1)),
+        Ok(&Some(Value::Int(15)))
+    );
+    assert_eq!(
+        context.store.read(&Lval::new("y", 1)),
+        Ok(&Some(Value::Int(15)))
+    );
+}
+
+#[test]
+fn eval_assign_move() {
+    let mut context = two_boxes();
+    context
+        .eval_stmt(&programs::assign_move(), &Lifetime(4))
+        .unwrap();
+    assert_eq!(
+        context.store.read(&Lval::new("x", 1)),
+        Ok(&Some(Value::Int(15)))
+    );
+    assert_eq!(context.store.read(&Lval::new("y", 0)), Ok(&None));
+}
+
+#[test]
+fn eval_assign_replace() {
+    let mut context = two_boxes();
+    for stmt in programs::assign_replace() {
+        context.eval_stmt(&stmt, &Lifetime(4)).unwrap();
+    }
+    assert_eq!(
+        context.store.read(&Lval::new("x", 1)),
+        Ok(&Some(Value::Int(15)))
+    );
+    assert_eq!(
+        context.store.read(&Lval::new("y", 1)),
+        Ok(&Some(Value::Int(16)))
+    );
+}
+
+#[test]
+fn eval_assign_move_deref() {
+    let mut context = two_boxes();
+    context
+        .eval_stmt(&programs::assign_move_deref(), &Lifetime(4))
+        .unwrap();
+    assert_eq!(
+        context.store.read(&Lval::new("x", 1)),
+        Ok(&Some(Value::Int(15)))
+    );
+    assert_eq!(context.store.read(&Lval::new("y", 1)), Ok(&None));
+}
+
+#[test]
+fn eval_expr_stmt() {
+    let context = run(&[programs::let_box("x", 14), programs::move_deref()]);
+    assert_eq!(context.store.read(&Lval::new("x", 1)), Ok(&None));
+}
+
+#[test]
+fn eval_block() {
+    let mut context = Context::default();
+    context.eval_expr(&programs::block(), &Lifetime(5)).unwrap();
+    assert_eq!(context.store, Store::default());
+}
+
+#[test]
+fn eval_block_ref() {
+    let mut context = run(&[programs::let_box("x", 203)]);
+    let store_2 = context.store.clone();
+    context
+        .eval_expr(&programs::block_ref(), &Lifetime(4))
+        .unwrap();
+    assert_eq!(context.store, store_2);
+}
+
+#[test]
+fn eval_block_mut_ref() {
+    let mut context = run(&[programs::let_box("x", 203)]);
+    context
+        .eval_expr(&programs::block_mut_ref(), &Lifetime(4))
+        .unwrap();
+    let context_2 = run(&[programs::let_box("x", -150)]);
+    assert_eq!(context.store, context_2.store);
+}
@@ -0,0 +1,288 @@
+use crate::utils::{BinOp, Binding, EnumDef, Expr, FnDef, Pattern, Stmt, TypeAnn};
+use std::fmt;
+
+const INDENT: &str = "    ";
+
+// Binding strength of each kind of expression, loosest first.
+const CMP: u8 = 1;
+const SUM: u8 = 2;
+const PRODUCT: u8 = 3;
+const ATOM: u8 = 4;
+
+/// Print a program the way `parse_program` reads it: the body of the
+/// outermost block, without braces.
+pub fn program(e: &Expr) -> String {
+    match e.peel() {
+        Expr::Block(stmts, final_e, _) => body(stmts, final_e, 0),
+        e => expr(e, CMP, 0),
+    }
+}
+
+fn precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Add | BinOp::Sub => SUM,
+        BinOp::Mul | BinOp::Div | BinOp::Rem => PRODUCT,
+        _ => CMP,
+    }
+}
+
+/// Expressions that end a statement without a `;`.
+fn is_block_like(e: &Expr) -> bool {
+    matches!(
+        e.peel(),
+        Expr::Block(..) | Expr::If(..) | Expr::While(..) | Expr::Match(..)
+    )
+}
+
+/// The lines of a block body, each indented `indent` levels.
+fn body(stmts: &[Stmt], final_e: &Expr, indent: usize) -> String {
+    let mut items: Vec<String> = stmts.iter().map(|s| stmt(s, indent)).collect();
+    if !matches!(final_e.peel(), Expr::Unit) {
+        items.push(expr(final_e, CMP, indent));
+    }
+    // a block used as a statement needs its `;` if it would otherwise be
+    // read as the final expression, and the lexer takes `} -1` for a
+    // subtraction rather than a negative literal
+    for i in 0..stmts.len() {
+        let bare = matches!(stmts[i].peel(), Stmt::Expr(e) if is_block_like(e));
+        let signed_next = items.get(i + 1).is_some_and(|next| next.starts_with('-'));
+        if (bare && i + 1 == items.len()) || (items[i].ends_with('}') && signed_next) {
+            items[i].push(';');
+        }
+    }
+    let pad = INDENT.repeat(indent);
+    items
+        .iter()
+        .map(|item| format!("{}{}", pad, item))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn block(stmts: &[Stmt], final_e: &Expr, indent: usize) -> String {
+    if stmts.is_empty() && matches!(final_e.peel(), Expr::Unit) {
+        return String::from("{}");
+    }
+    format!(
+        "{{\n{}\n{}}}",
+        body(stmts, final_e, indent + 1),
+        INDENT.repeat(indent)
+    )
+}
+
+/// `e` at `indent` levels, parenthesised if it binds looser than `prec`.
+fn expr(e: &Expr, prec: u8, indent: usize) -> String {
+    match e.peel() {
+        Expr::Unit => String::from("()"),
+        Expr::Int(n) => n.to_string(),
+        Expr::Bool(b) => b.to_string(),
+        Expr::Lval(lv, _) => lv.to_string(),
+        Expr::Box(inner) => format!("box {}", expr(inner, ATOM, indent)),
+        Expr::Borrow(lv, is_mut) => format!("&{}{}", if *is_mut { "mut " } else { "" }, lv),
+        Expr::Block(stmts, final_e, _) => block(stmts, final_e, indent),
+        Expr::Call(f, args) => format!("{}({})", f, list(args, indent)),
+        Expr::BinOp(op, lhs, rhs) => {
+            let p = precedence(*op);
+            // comparisons don't chain, the others associate to the left
+            let lhs_prec = if p == CMP { SUM } else { p };
+            let rhs_prec = if p == CMP { SUM } else { p + 1 };
+            let mut l = expr(lhs, lhs_prec, indent);
+            // `{ .. } + 1` at the start of a statement would end at the `}`
+            if is_block_like(lhs) {
+                l = format!("({})", l);
+            }
+            let s = format!("{} {} {}", l, op, expr(rhs, rhs_prec, indent));
+            if p < prec {
+                format!("({})", s)
+            } else {
+                s
+            }
+        }
+        Expr::If(cond, then_e, else_e) => {
+            let mut s = format!(
+                "if {} {}",
+                expr(cond, CMP, indent),
+                expr(then_e, CMP, indent)
+            );
+            match else_e.peel() {
+                Expr::Block(stmts, final_e, _) if stmts.is_empty() => match final_e.peel() {
+                    Expr::Unit => {}
+                    Expr::If(..) => s += &format!(" else {}", expr(final_e, CMP, indent)),
+                    _ => s += &format!(" else {}", expr(else_e, CMP, indent)),
+                },
+                _ => s += &format!(" else {}", expr(else_e, CMP, indent)),
+            }
+            s
+        }
+        Expr::While(cond, body) => format!(
+            "while {} {}",
+            expr(cond, CMP, indent),
+            expr(body, CMP, indent)
+        ),
+        Expr::Tuple(elems) if elems.len() == 1 => format!("({},)", expr(&elems[0], CMP, indent)),
+        Expr::Tuple(elems) => format!("({})", list(elems, indent)),
+        Expr::Variant(name, variant, args) if args.is_empty() => format!("{}::{}", name, variant),
+        Expr::Variant(name, variant, args) => {
+            format!("{}::{}({})", name, variant, list(args, indent))
+        }
+        Expr::Match(scrutinee, arms, _) => {
+            let pad = INDENT.repeat(indent + 1);
+            let arms: Vec<String> = arms
+                .iter()
+                .map(|(p, body)| format!("{}{} => {},\n", pad, p, expr(body, CMP, indent + 1)))
+                .collect();
+            format!(
+                "match {} {{\n{}{}}}",
+                expr(scrutinee, CMP, indent),
+                arms.concat(),
+                INDENT.repeat(indent)
+            )
+        }
+        Expr::Spanned(..) => unreachable!(),
+    }
+}
+
+fn list(elems: &[Expr], indent: usize) -> String {
+    elems
+        .iter()
+        .map(|e| expr(e, CMP, indent))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `s` at `indent` levels. A block used as a statement is left without its
+/// `;`, see `body`.
+fn stmt(s: &Stmt, indent: usize) -> String {
+    match s.peel() {
+        Stmt::Assign(lv, e) => format!("{} = {};", lv, expr(e, CMP, indent)),
+        Stmt::LetMut(x, e) => format!("let mut {} = {};", x, expr(e, CMP, indent)),
+        Stmt::Expr(e) if is_block_like(e) => expr(e, CMP, indent),
+        Stmt::Expr(e) => format!("{};", expr(e, CMP, indent)),
+        Stmt::Fn(def) => fn_def(def, indent),
+        Stmt::Enum(def) => enum_def(def, indent),
+        Stmt::Spanned(..) => unreachable!(),
+    }
+}
+
+fn enum_def(def: &EnumDef, indent: usize) -> String {
+    let pad = INDENT.repeat(indent + 1);
+    let variants: Vec<String> = def
+        .variants
+        .iter()
+        .map(|(v, anns)| {
+            if anns.is_empty() {
+                return format!("{}{},\n", pad, v);
+            }
+            let anns: Vec<String> = anns.iter().map(|a| a.to_string()).collect();
+            format!("{}{}({}),\n", pad, v, anns.join(", "))
+        })
+        .collect();
+    format!(
+        "enum {} {{\n{}{}}}",
+        def.name,
+        variants.concat(),
+        INDENT.repeat(indent)
+    )
+}
+
+fn fn_def(def: &FnDef, indent: usize) -> String {
+    let mut s = format!("fn {}", def.name);
+    let named: Vec<String> = def
+        .lifetimes
+        .iter()
+        .filter(|(a, _)| a != "'_")
+        .map(|(a, _)| {
+            let bounds: Vec<&str> = def
+                .bounds
+                .iter()
+                .filter(|(b, _)| a == b)
+                .map(|(_, c)| c.as_str())
+                .collect();
+            if bounds.is_empty() {
+                a.clone()
+            } else {
+                format!("{}: {}", a, bounds.join(" + "))
+            }
+        })
+        .collect();
+    if !named.is_empty() {
+        s += &format!("<{}>", named.join(", "));
+    }
+    let params: Vec<String> = def
+        .params
+        .iter()
+        .map(|(x, ann)| format!("{}: {}", x, ann))
+        .collect();
+    s += &format!("({})", params.join(", "));
+    if def.ret != TypeAnn::Unit {
+        s += &format!(" -> {}", def.ret);
+    }
+    format!("{} {}", s, expr(&def.body, CMP, indent))
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", expr(self, CMP, 0))
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", stmt(self, 0))
+    }
+}
+
+impl fmt::Display for FnDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", fn_def(self, 0))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, variant, bindings) = match self {
+            Pattern::Wildcard => return write!(f, "_"),
+            Pattern::Variant(name, variant, bindings) => (name, variant, bindings),
+        };
+        write!(f, "{}::{}", name, variant)?;
+        if bindings.is_empty() {
+            return Ok(());
+        }
+        let bindings: Vec<String> = bindings
+            .iter()
+            .map(|b| match b {
+                Binding::Ignore => String::from("_"),
+                Binding::Move(x, _) => x.clone(),
+                Binding::Ref(x, true) => format!("ref mut {}", x),
+                Binding::Ref(x, false) => format!("ref {}", x),
+            })
+            .collect();
+        write!(f, "({})", bindings.join(", "))
+    }
+}
+
+impl fmt::Display for TypeAnn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeAnn::Unit => write!(f, "()"),
+            TypeAnn::Int => write!(f, "int"),
+            TypeAnn::Bool => write!(f, "bool"),
+            TypeAnn::Box(inner) => write!(f, "box {}", inner),
+            TypeAnn::Ref(inner, is_mut, name) => {
+                write!(f, "&")?;
+                if let Some(name) = name {
+                    write!(f, "{} ", name)?;
+                }
+                if *is_mut {
+                    write!(f, "mut ")?;
+                }
+                write!(f, "{}", inner)
+            }
+            TypeAnn::Tuple(anns) if anns.len() == 1 => write!(f, "({},)", anns[0]),
+            TypeAnn::Tuple(anns) => {
+                let anns: Vec<String> = anns.iter().map(|a| a.to_string()).collect();
+                write!(f, "({})", anns.join(", "))
+            }
+            TypeAnn::Enum(name) => write!(f, "{}", name),
+        }
+    }
+}
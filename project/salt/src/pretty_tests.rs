@@ -0,0 +1,264 @@
+use crate::parser::parse_program;
+use crate::pretty::program;
+use crate::test_rng::Rng;
+use crate::types::Type;
+use crate::utils::{BinOp, Binding, EnumDef, Expr, FnDef, Lifetime, Lval, Pattern, Stmt, TypeAnn};
+
+fn round_trip(src: &str) -> String {
+    let e = parse_program(src).unwrap();
+    let printed = program(&e);
+    assert_eq!(parse_program(&printed), Ok(e), "printed as:\n{}", printed);
+    printed
+}
+
+#[test]
+fn print_exprs() {
+    assert_eq!(
+        round_trip("let mut y = &mut **x; box box 5"),
+        "let mut y = &mut **x;\nbox box 5"
+    );
+    assert_eq!(
+        round_trip("(1 + 2) * -3 - (4 - 5) < 6"),
+        "(1 + 2) * -3 - (4 - 5) < 6"
+    );
+    assert_eq!(round_trip("box (1, (2,), *t.0)"), "box (1, (2,), *t.0)");
+    assert_eq!(
+        round_trip("if a { 1 } else if b { f(2, &c) } let mut x = { 3 };"),
+        "if a {\n    1\n} else if b {\n    f(2, &c)\n}\nlet mut x = {\n    3\n};"
+    );
+}
+
+#[test]
+fn print_blocks_as_statements() {
+    // the `;` keeps the loop a statement rather than the final expression
+    assert_eq!(
+        round_trip("while x < 3 { x = x + 1; };"),
+        "while x < 3 {\n    x = x + 1;\n};"
+    );
+    assert_eq!(round_trip("{ 1 }; -1"), "{\n    1\n};\n-1");
+    assert_eq!(round_trip("{ 1 } *x"), "{\n    1\n}\n*x");
+    assert_eq!(round_trip("({ 1 }) + 2"), "({\n    1\n}) + 2");
+}
+
+#[test]
+fn print_fns() {
+    assert_eq!(
+        round_trip("fn f<'a, 'b: 'a>(x: &'a mut (int, box int), y: &bool) -> &'a int { &x.0 }"),
+        "fn f<'a, 'b: 'a>(x: &'a mut (int, box int), y: &bool) -> &'a int {\n    &x.0\n}"
+    );
+    assert_eq!(round_trip("fn g(u: ()) {}"), "fn g(u: ()) {}");
+}
+
+#[test]
+fn print_types() {
+    let t = Type::Tuple(vec![
+        Type::boxx(Type::mut_ref(Lval::field("x", &[1], 1))),
+        Type::undefined(Type::Int),
+    ]);
+    assert_eq!(t.to_string(), "(box &mut *x.1, <moved int>)");
+    assert_eq!(Type::Tuple(vec![Type::Unit]).to_string(), "((),)");
+    let s = String::from;
+    let t = Type::Variant(s("E"), s("B"), vec![Type::Int, Type::Enum(s("E"))]);
+    assert_eq!(t.to_string(), "E::B(int, E)");
+}
+
+#[test]
+fn print_enums_and_match() {
+    assert_eq!(
+        round_trip("enum E { A, B(int, box E) } match x { E::B(_, ref mut r) => { 1 } _ => E::A }"),
+        "enum E {\n    A,\n    B(int, box E),\n}\nmatch x {\n    E::B(_, ref mut r) => {\n        1\n    },\n    _ => E::A,\n}"
+    );
+}
+
+const VARS: [&str; 4] = ["x", "y", "t", "_n2"];
+const ENUMS: [&str; 2] = ["E", "Opt"];
+const VARIANTS: [&str; 3] = ["A", "Some", "None"];
+const OPS: [BinOp; 11] = [
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Rem,
+    BinOp::Lt,
+    BinOp::Le,
+    BinOp::Gt,
+    BinOp::Ge,
+    BinOp::Eq,
+    BinOp::Ne,
+];
+
+fn lval(rng: &mut Rng) -> Lval {
+    let fields: Vec<usize> = (0..rng.below(3)).map(|_| rng.below(3)).collect();
+    Lval::field(rng.pick(&VARS), &fields, rng.below(3))
+}
+
+fn block(rng: &mut Rng, depth: usize) -> Expr {
+    let stmts = (0..rng.below(3)).map(|_| stmt(rng, depth)).collect();
+    let final_e = if rng.below(2) == 0 {
+        Expr::Unit
+    } else {
+        expr(rng, depth)
+    };
+    Expr::block(stmts, final_e, Lifetime(0))
+}
+
+fn expr(rng: &mut Rng, depth: usize) -> Expr {
+    let choice = if depth == 0 {
+        rng.below(5)
+    } else {
+        rng.below(14)
+    };
+    let depth = depth.saturating_sub(1);
+    match choice {
+        0 => Expr::Unit,
+        1 => Expr::Int(rng.below(21) as i32 - 10),
+        2 => Expr::Bool(rng.below(2) == 0),
+        3 => Expr::Lval(lval(rng), false),
+        4 => Expr::Borrow(lval(rng), rng.below(2) == 0),
+        5 => Expr::boxx(expr(rng, depth)),
+        6 => block(rng, depth),
+        7 => Expr::Call(
+            String::from(rng.pick(&["f", "g"])),
+            (0..rng.below(3)).map(|_| expr(rng, depth)).collect(),
+        ),
+        8 | 9 => Expr::binop(
+            OPS[rng.below(OPS.len())],
+            expr(rng, depth),
+            expr(rng, depth),
+        ),
+        10 => {
+            let else_e = match rng.below(3) {
+                0 => Expr::block(vec![], Expr::Unit, Lifetime(0)),
+                1 => Expr::block(
+                    vec![],
+                    Expr::iff(expr(rng, depth), block(rng, depth), block(rng, depth)),
+                    Lifetime(0),
+                ),
+                _ => block(rng, depth),
+            };
+            Expr::iff(expr(rng, depth), block(rng, depth), else_e)
+        }
+        11 if rng.below(2) == 0 => Expr::whilee(expr(rng, depth), block(rng, depth)),
+        12 => Expr::Variant(
+            String::from(rng.pick(&ENUMS)),
+            String::from(rng.pick(&VARIANTS)),
+            (0..rng.below(3)).map(|_| expr(rng, depth)).collect(),
+        ),
+        13 => Expr::Match(
+            Box::new(expr(rng, depth)),
+            (0..rng.below(3))
+                .map(|_| (pattern(rng), expr(rng, depth)))
+                .collect(),
+            Lifetime(0),
+        ),
+        _ => Expr::Tuple((0..rng.below(3) + 1).map(|_| expr(rng, depth)).collect()),
+    }
+}
+
+fn pattern(rng: &mut Rng) -> Pattern {
+    if rng.below(4) == 0 {
+        return Pattern::Wildcard;
+    }
+    let bindings = (0..rng.below(3))
+        .map(|_| {
+            let x = String::from(rng.pick(&VARS));
+            match rng.below(3) {
+                0 => Binding::Ignore,
+                1 => Binding::Move(x, false),
+                _ => Binding::Ref(x, rng.below(2) == 0),
+            }
+        })
+        .collect();
+    Pattern::Variant(
+        String::from(rng.pick(&ENUMS)),
+        String::from(rng.pick(&VARIANTS)),
+        bindings,
+    )
+}
+
+fn stmt(rng: &mut Rng, depth: usize) -> Stmt {
+    match rng.below(3) {
+        0 => Stmt::Assign(lval(rng), expr(rng, depth)),
+        1 => Stmt::LetMut(String::from(rng.pick(&VARS)), expr(rng, depth)),
+        _ => Stmt::Expr(expr(rng, depth)),
+    }
+}
+
+fn type_ann(rng: &mut Rng, depth: usize) -> TypeAnn {
+    let depth = depth.saturating_sub(1);
+    match rng.below(if depth == 0 { 4 } else { 7 }) {
+        0 => TypeAnn::Int,
+        1 => TypeAnn::Bool,
+        2 => TypeAnn::Unit,
+        3 => TypeAnn::Enum(String::from(rng.pick(&ENUMS))),
+        4 => TypeAnn::Box(Box::new(type_ann(rng, depth))),
+        5 => {
+            let name = ["'a", "'b", ""][rng.below(3)];
+            let name = (!name.is_empty()).then(|| String::from(name));
+            TypeAnn::Ref(Box::new(type_ann(rng, depth)), rng.below(2) == 0, name)
+        }
+        _ => TypeAnn::Tuple(
+            (0..rng.below(3) + 1)
+                .map(|_| type_ann(rng, depth))
+                .collect(),
+        ),
+    }
+}
+
+fn enum_def(rng: &mut Rng) -> Stmt {
+    Stmt::Enum(EnumDef {
+        name: String::from(rng.pick(&ENUMS)),
+        variants: (0..rng.below(3))
+            .map(|_| {
+                let fields = (0..rng.below(3)).map(|_| type_ann(rng, 2)).collect();
+                (String::from(rng.pick(&VARIANTS)), fields)
+            })
+            .collect(),
+    })
+}
+
+fn fn_def(rng: &mut Rng) -> Stmt {
+    let lifetimes = match rng.below(3) {
+        0 => vec![],
+        1 => vec![(String::from("'a"), Lifetime(0))],
+        _ => vec![
+            (String::from("'a"), Lifetime(0)),
+            (String::from("'b"), Lifetime(0)),
+        ],
+    };
+    let bounds = if lifetimes.len() == 2 && rng.below(2) == 0 {
+        vec![(String::from("'b"), String::from("'a"))]
+    } else {
+        vec![]
+    };
+    Stmt::Fn(FnDef {
+        name: String::from(rng.pick(&["f", "g"])),
+        params: (0..rng.below(3))
+            .map(|_| (String::from(rng.pick(&VARS)), type_ann(rng, 3)))
+            .collect(),
+        ret: type_ann(rng, 2),
+        lifetimes,
+        bounds,
+        body: block(rng, 3),
+        lifetime: Lifetime(0),
+    })
+}
+
+/// Printing any program and parsing it back gives the same AST, which
+/// prints the same way again.
+#[test]
+fn round_trip_random_programs() {
+    let mut rng = Rng::new(0x5eed_5a17);
+    for _ in 0..500 {
+        let mut stmts: Vec<Stmt> = (0..rng.below(2)).map(|_| enum_def(&mut rng)).collect();
+        stmts.extend((0..rng.below(3)).map(|_| fn_def(&mut rng)));
+        stmts.extend((0..rng.below(4)).map(|_| stmt(&mut rng, 4)));
+        let program_e = Expr::block(stmts, expr(&mut rng, 3), Lifetime(1));
+        let printed = program(&program_e);
+        let parsed = parse_program(&printed)
+            .unwrap_or_else(|e| panic!("{:?} when parsing:\n{}", e, printed));
+        let reprinted = program(&parsed);
+        assert_eq!(printed, reprinted);
+        assert_eq!(parse_program(&reprinted), Ok(parsed));
+    }
+}
@@ -0,0 +1,29 @@
+// The random source shared by the tests that generate programs.
+
+/// A xorshift generator, so runs are reproducible without extra crates.
+pub struct Rng(u64);
+
+impl Rng {
+    /// A generator for `seed`, which may be any number. Xorshift never
+    /// leaves a state of 0, so the seed is scrambled with splitmix64's
+    /// finalizer, which also keeps small seeds from starting out alike.
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // the one seed that scrambles to 0
+        Rng(if z == 0 { 0x9e37_79b9_7f4a_7c15 } else { z })
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    pub fn pick<'a>(&mut self, xs: &[&'a str]) -> &'a str {
+        xs[self.below(xs.len())]
+    }
+}
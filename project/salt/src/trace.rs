@@ -0,0 +1,242 @@
+// A step-by-step record of how a run changes the store, for teaching and
+// replaying ownership.
+//
+// A traced run records a step after each statement it executes, statements
+// of blocks and calls nested in another included, along with the lifetime
+// of the block the statement is in, and a last one once the program's block
+// has dropped its variables. The slots are the variables of every frame and
+// the boxes on the heap. The store notes which of them it creates, writes or
+// drops as it does, so a slot written with the value it already held is
+// still written, and a box freed and allocated again in the same cell is
+// dropped and created: its generation differs. Each step keeps the store it
+// left as JSON, and serialises to one line of it.
+
+use crate::eval::{Location, Root, Store, Value};
+use crate::utils::{Lifetime, Span};
+use std::fmt::Write;
+
+/// The slots created, written and dropped since the last step, in the order
+/// it happened. A slot already created or written is not written again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub created: Vec<Root>,
+    pub written: Vec<Root>,
+    pub dropped: Vec<Root>,
+}
+
+impl Changes {
+    pub fn create(&mut self, root: Root) {
+        self.created.push(root);
+    }
+
+    pub fn write(&mut self, root: Root) {
+        if !self.created.contains(&root) && !self.written.contains(&root) {
+            self.written.push(root);
+        }
+    }
+
+    pub fn drop(&mut self, root: Root) {
+        self.dropped.push(root);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// The statement's span, unknown if it wasn't parsed.
+    pub span: Span,
+    pub lifetime: Lifetime,
+    pub created: Vec<Root>,
+    pub written: Vec<Root>,
+    pub dropped: Vec<Root>,
+    /// The store after the step, as the `"frames"` and `"heap"` members of
+    /// its JSON.
+    pub store: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    /// Record that the statement at `span`, in a block with lifetime `l`,
+    /// made the changes `store` has collected since the last step.
+    pub fn record(&mut self, span: Span, l: &Lifetime, store: &mut Store) {
+        let changes = store.changes.as_mut().map(std::mem::take);
+        let changes = changes.unwrap_or_default();
+        self.steps.push(Step {
+            span,
+            lifetime: l.clone(),
+            created: changes.created,
+            written: changes.written,
+            dropped: changes.dropped,
+            store: write_store(store),
+        });
+    }
+
+    /// The steps as JSON, one per line.
+    pub fn to_json_lines(&self) -> String {
+        let mut out = String::new();
+        for step in &self.steps {
+            out.push_str(&step.to_json());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Step {
+    /// The step as a single line of JSON:
+    ///
+    /// ```text
+    /// {"span":{"start":[1,1],"end":[1,15]},"lifetime":0,
+    ///  "created":[{"frame":0,"var":"x"}],"written":[],"dropped":[],
+    ///  "frames":[{"id":0,"vars":[{"name":"x","lifetime":0,"value":1}]}],
+    ///  "heap":[]}
+    /// ```
+    ///
+    /// A heap slot is `{"cell":0,"generation":0}`. Values are numbers,
+    /// booleans, `"()"`, `null` once moved out, `{"tuple":[...]}`,
+    /// `{"enum":"E","variant":"V","fields":[...]}` and
+    /// `{"ref":{...slot,"fields":[...]},"owned":true}`, where a reference made
+    /// by a checked run also has a `"tag"`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"span\":");
+        if self.span.is_known() {
+            let (start, end) = (self.span.start, self.span.end);
+            write!(
+                out,
+                "{{\"start\":[{},{}],\"end\":[{},{}]}}",
+                start.line, start.col, end.line, end.col
+            )
+            .unwrap();
+        } else {
+            out.push_str("null");
+        }
+        write!(out, ",\"lifetime\":{}", self.lifetime.0).unwrap();
+        for (key, roots) in [
+            ("created", &self.created),
+            ("written", &self.written),
+            ("dropped", &self.dropped),
+        ] {
+            write!(out, ",\"{}\":", key).unwrap();
+            write_list(&mut out, roots, |out, root| {
+                out.push('{');
+                write_root(out, root);
+                out.push('}');
+            });
+        }
+        out.push(',');
+        out.push_str(&self.store);
+        out.push('}');
+        out
+    }
+}
+
+/// The `"frames"` and `"heap"` members of a step, without the braces.
+fn write_store(store: &Store) -> String {
+    let mut out = String::from("\"frames\":");
+    write_list(&mut out, &store.frames, |out, frame| {
+        write!(out, "{{\"id\":{},\"vars\":", frame.id).unwrap();
+        let mut vars: Vec<_> = frame.vars.iter().collect();
+        vars.sort_by_key(|(x, _)| *x);
+        write_list(out, &vars, |out, (x, slot)| {
+            out.push_str("{\"name\":");
+            write_string(out, x);
+            write!(out, ",\"lifetime\":{},\"value\":", slot.lifetime.0).unwrap();
+            write_value(out, &slot.value);
+            out.push('}');
+        });
+        out.push('}');
+    });
+    out.push_str(",\"heap\":");
+    let heap: Vec<_> = store.heap.iter().collect();
+    write_list(&mut out, &heap, |out, (i, value)| {
+        write!(
+            out,
+            "{{\"cell\":{},\"generation\":{},\"value\":",
+            i.cell, i.generation
+        )
+        .unwrap();
+        write_value(out, value);
+        out.push('}');
+    });
+    out
+}
+
+/// The members of a slot's object, without the braces.
+fn write_root(out: &mut String, root: &Root) {
+    match root {
+        Root::Var(id, x) => {
+            write!(out, "\"frame\":{},\"var\":", id).unwrap();
+            write_string(out, x);
+        }
+        Root::Heap(i) => {
+            write!(out, "\"cell\":{},\"generation\":{}", i.cell, i.generation).unwrap();
+        }
+    }
+}
+
+fn write_location(out: &mut String, loc: &Location) {
+    out.push('{');
+    write_root(out, &loc.root);
+    out.push_str(",\"fields\":");
+    write_list(out, &loc.fields, |out, f| write!(out, "{}", f).unwrap());
+    if let Some(tag) = loc.tag {
+        write!(out, ",\"tag\":{}", tag).unwrap();
+    }
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &Option<Value>) {
+    match value {
+        None => out.push_str("null"),
+        Some(Value::Unit) => out.push_str("\"()\""),
+        Some(Value::Int(n)) => write!(out, "{}", n).unwrap(),
+        Some(Value::Bool(b)) => write!(out, "{}", b).unwrap(),
+        Some(Value::Ref(loc, owned)) => {
+            out.push_str("{\"ref\":");
+            write_location(out, loc);
+            write!(out, ",\"owned\":{}}}", owned).unwrap();
+        }
+        Some(Value::Tuple(vs)) => {
+            out.push_str("{\"tuple\":");
+            write_list(out, vs, write_value);
+            out.push('}');
+        }
+        Some(Value::Enum(name, variant, vs)) => {
+            out.push_str("{\"enum\":");
+            write_string(out, name);
+            out.push_str(",\"variant\":");
+            write_string(out, variant);
+            out.push_str(",\"fields\":");
+            write_list(out, vs, write_value);
+            out.push('}');
+        }
+    }
+}
+
+fn write_list<T>(out: &mut String, items: &[T], write_item: impl Fn(&mut String, &T)) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_item(out, item);
+    }
+    out.push(']');
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,110 @@
+use crate::eval::{Context, Index, Root};
+use crate::parser::parse_program;
+use crate::types;
+use crate::utils::Lifetime;
+
+fn trace(src: &str) -> Context {
+    let program = parse_program(src).unwrap();
+    let typed = types::Context::default().elaborate(&program).unwrap();
+    let mut ctxt = Context::tracing();
+    ctxt.eval_expr(&typed.expr, &Lifetime::global()).unwrap();
+    ctxt
+}
+
+fn var(frame: usize, x: &str) -> Root {
+    Root::Var(frame, String::from(x))
+}
+
+fn heap(cell: usize, generation: u32) -> Root {
+    Root::Heap(Index { cell, generation })
+}
+
+#[test]
+fn steps_follow_statements() {
+    let ctxt = trace(
+        "let mut x = box 1;
+             let mut y = x;
+             { let mut z = &y; }
+             fn f(b: &int) -> int { let mut c = *b; c }
+             let mut n = f(&*y);
+             { let mut a = box 2; }
+             let mut w = box 3;
+             n",
+    );
+    let steps = ctxt.trace.unwrap().steps;
+    let changes: Vec<_> = steps
+        .iter()
+        .map(|s| {
+            let lines = (s.span.start.line, s.span.end.line);
+            (lines, s.lifetime.0, &s.created, &s.written, &s.dropped)
+        })
+        .collect();
+    // statements in a nested block or a call come before the statement
+    // around them, which drops their variables, the body's before the
+    // parameters, and the program's block drops its own in a last step
+    assert_eq!(
+        changes,
+        [
+            ((1, 1), 1, &vec![heap(0, 0), var(0, "x")], &vec![], &vec![]),
+            ((2, 2), 1, &vec![var(0, "y")], &vec![var(0, "x")], &vec![]),
+            ((3, 3), 2, &vec![var(0, "z")], &vec![], &vec![]),
+            ((3, 3), 1, &vec![], &vec![], &vec![var(0, "z")]),
+            ((4, 4), 4, &vec![var(1, "b"), var(1, "c")], &vec![], &vec![]),
+            (
+                (5, 5),
+                1,
+                &vec![var(0, "n")],
+                &vec![],
+                &vec![var(1, "c"), var(1, "b")]
+            ),
+            ((6, 6), 6, &vec![heap(1, 0), var(0, "a")], &vec![], &vec![]),
+            ((6, 6), 1, &vec![], &vec![], &vec![var(0, "a"), heap(1, 0)]),
+            // the freed cell comes back under a new generation
+            ((7, 7), 1, &vec![heap(1, 1), var(0, "w")], &vec![], &vec![]),
+            (
+                (8, 8),
+                1,
+                &vec![],
+                &vec![],
+                &vec![
+                    var(0, "n"),
+                    var(0, "w"),
+                    heap(1, 1),
+                    var(0, "x"),
+                    var(0, "y"),
+                    heap(0, 0)
+                ]
+            ),
+        ]
+    );
+    assert!(steps[8].store.ends_with(
+        r#""heap":[{"cell":0,"generation":0,"value":1},{"cell":1,"generation":1,"value":3}]"#
+    ));
+    assert!(steps[9].store.ends_with(r#""vars":[]}],"heap":[]"#));
+    assert!(ctxt.store.heap.is_empty());
+}
+
+#[test]
+fn steps_serialise_as_json_lines() {
+    let ctxt = trace("let mut t = (true, box ());\nlet mut r = &t.1;\nr = r;\n0");
+    let json = ctxt.trace.unwrap().to_json_lines();
+    let lines: Vec<_> = json.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[1],
+        concat!(
+            r#"{"span":{"start":[2,1],"end":[2,18]},"lifetime":1,"#,
+            r#""created":[{"frame":0,"var":"r"}],"written":[],"dropped":[],"#,
+            r#""frames":[{"id":0,"vars":["#,
+            r#"{"name":"r","lifetime":1,"value":{"ref":{"frame":0,"var":"t","fields":[1]},"owned":false}},"#,
+            r#"{"name":"t","lifetime":1,"value":{"tuple":[true,{"ref":{"cell":0,"generation":0,"fields":[]},"owned":true}]}}"#,
+            r#"]}],"heap":[{"cell":0,"generation":0,"value":"()"}]}"#
+        )
+    );
+    // writing a value over an equal one is still a write
+    assert!(lines[2].contains(r#""created":[],"written":[{"frame":0,"var":"r"}],"dropped":[]"#));
+    assert!(lines[3].starts_with(concat!(
+        r#"{"span":{"start":[4,1],"end":[4,2]},"lifetime":1,"created":[],"written":[],"#,
+        r#""dropped":[{"frame":0,"var":"r"},{"frame":0,"var":"t"},{"cell":0,"generation":0}]"#
+    )));
+}
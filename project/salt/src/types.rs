@@ -1,12 +1,25 @@
-use crate::utils::{Expr, Ident, Lifetime, Lval, Mutable, Stmt};
-use std::collections::HashMap;
+// `Error` carries whole types and places so they can be reported; it is
+// only built on the failure path, so its size doesn't matter.
+#![allow(clippy::result_large_err)]
+
+use crate::utils::{
+    Binding, EnumDef, Expr, FnDef, Ident, Lifetime, Lval, Mutable, Pattern, Span, Stmt, TypeAnn,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Unit,
     Int,
+    Bool,
     Box(Box<Type>),
     Ref(Lval, Mutable),
+    Tuple(Vec<Type>),
+    Enum(Ident),
+    /// An enum whose variant is known, with the types of its fields: the
+    /// type of the place a match arm is looking at.
+    Variant(Ident, Ident, Vec<Type>),
     Undefined(Box<Type>),
 }
 
@@ -23,6 +36,44 @@ impl Type {
     pub fn mut_ref(lval: Lval) -> Self {
         Type::Ref(lval, true)
     }
+
+    /// Values of scalar types, and tuples of them, are copied rather than
+    /// moved.
+    pub fn is_copy(&self) -> bool {
+        match self {
+            Type::Unit | Type::Int | Type::Bool => true,
+            Type::Tuple(ts) => ts.iter().all(Type::is_copy),
+            _ => false,
+        }
+    }
+}
+
+/// Salt syntax where there is some: a reference is shown as a borrow of the
+/// place it points at, e.g. `&mut *x`.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Unit => write!(f, "()"),
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Box(inner) => write!(f, "box {}", inner),
+            Type::Ref(lv, is_mut) => write!(f, "&{}{}", if *is_mut { "mut " } else { "" }, lv),
+            Type::Tuple(ts) if ts.len() == 1 => write!(f, "({},)", ts[0]),
+            Type::Tuple(ts) => {
+                let ts: Vec<String> = ts.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", ts.join(", "))
+            }
+            Type::Enum(name) => write!(f, "{}", name),
+            Type::Variant(name, variant, ts) if ts.is_empty() => {
+                write!(f, "{}::{}", name, variant)
+            }
+            Type::Variant(name, variant, ts) => {
+                let ts: Vec<String> = ts.iter().map(|t| t.to_string()).collect();
+                write!(f, "{}::{}({})", name, variant, ts.join(", "))
+            }
+            Type::Undefined(inner) => write!(f, "<moved {}>", inner),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,11 +92,12 @@ impl Slot {
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Env(pub HashMap<Ident, Slot>);
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Error {
     UnknownVar(String),
     CannotDeref(Type),
     MovedOut(Lval),
+    MoveInLoop(Ident),
     MoveBehindRef(Lval),
     UpdateBehindImmRef(Lval),
     CopyAfterMutBorrow(Lval),
@@ -53,10 +105,294 @@ pub enum Error {
     MutBorrowBehindImmRef(Lval),
     MutBorrowAfterBorrow(Lval),
     BorrowAfterMutBorrow(Lval),
+    MutBorrowAfterMutBorrow(Lval),
     Shadowing(String),
     IncompatibleTypes(Type, Type),
     LifetimeTooShort(Expr),
     AssignAfterBorrow(Lval),
+    UnknownFn(Ident),
+    ArityMismatch(Ident, usize, usize),
+    UnresolvedReturn(Ident),
+    UnknownField(Lval),
+    UndeclaredLifetime(Ident),
+    /// A reference with the first lifetime is used where the second is
+    /// required, but isn't known to outlive it.
+    DoesNotOutlive(Ident, Ident),
+    UnknownEnum(Ident),
+    UnknownVariant(Ident, Ident),
+    RefInEnum(Ident),
+    /// A match on the enum misses the listed variants.
+    NonExhaustive(Ident, Vec<Ident>),
+    /// A pattern binds a field of an enum behind a box by move or by
+    /// reference, which has no place to name it.
+    BindingBehindBox(Lval),
+    /// An error located in the source, with secondary labels pointing at
+    /// related places such as a conflicting borrow.
+    Located(Box<Error>, Span, Vec<(Span, String)>),
+}
+
+impl Error {
+    /// The error under its location.
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Located(e, _, _) => e.kind(),
+            e => e,
+        }
+    }
+
+    /// Where the error occurred, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Located(_, span, _) if span.is_known() => Some(*span),
+            e => e.kind().own_span(),
+        }
+    }
+
+    pub fn labels(&self) -> &[(Span, String)] {
+        match self {
+            Error::Located(_, _, labels) => labels,
+            _ => &[],
+        }
+    }
+
+    /// The variable the error is about, if any.
+    fn subject(&self) -> Option<&Ident> {
+        use Error::*;
+        match self.kind() {
+            UnknownVar(x) | MoveInLoop(x) | Shadowing(x) => Some(x),
+            MovedOut(lv)
+            | MoveBehindRef(lv)
+            | UpdateBehindImmRef(lv)
+            | CopyAfterMutBorrow(lv)
+            | MoveAfterBorrow(lv)
+            | MutBorrowBehindImmRef(lv)
+            | MutBorrowAfterBorrow(lv)
+            | BorrowAfterMutBorrow(lv)
+            | MutBorrowAfterMutBorrow(lv)
+            | AssignAfterBorrow(lv)
+            | UnknownField(lv)
+            | BindingBehindBox(lv) => Some(&lv.ident),
+            _ => None,
+        }
+    }
+
+    /// The function whose failure to check would explain the error, if any.
+    fn callee(&self) -> Option<&Ident> {
+        use Error::*;
+        match self.kind() {
+            UnknownFn(name) | UnresolvedReturn(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The span carried by the error's own payload.
+    fn own_span(&self) -> Option<Span> {
+        use Error::*;
+        match self {
+            MovedOut(lv)
+            | MoveBehindRef(lv)
+            | UpdateBehindImmRef(lv)
+            | CopyAfterMutBorrow(lv)
+            | MoveAfterBorrow(lv)
+            | MutBorrowBehindImmRef(lv)
+            | MutBorrowAfterBorrow(lv)
+            | BorrowAfterMutBorrow(lv)
+            | MutBorrowAfterMutBorrow(lv)
+            | AssignAfterBorrow(lv)
+            | UnknownField(lv)
+            | BindingBehindBox(lv)
+                if lv.span.is_known() =>
+            {
+                Some(lv.span)
+            }
+            LifetimeTooShort(e) => e.span(),
+            _ => None,
+        }
+    }
+
+    /// Locate the error at `span` unless it already knows a better place.
+    pub fn at(self, span: Span) -> Error {
+        match self {
+            Error::Located(e, own, labels) if !own.is_known() => {
+                let span = e.own_span().unwrap_or(span);
+                Error::Located(e, span, labels)
+            }
+            Error::Located(..) => self,
+            e => {
+                let span = e.own_span().unwrap_or(span);
+                Error::Located(Box::new(e), span, vec![])
+            }
+        }
+    }
+
+    /// Attach a secondary label, e.g. where a conflicting borrow was taken.
+    pub fn label(self, span: Span, msg: &str) -> Error {
+        let (e, at, mut labels) = match self {
+            Error::Located(e, at, labels) => (e, at, labels),
+            e => {
+                let at = e.own_span().unwrap_or_default();
+                (Box::new(e), at, vec![])
+            }
+        };
+        if span.is_known() {
+            labels.push((span, msg.to_string()));
+        }
+        Error::Located(e, at, labels)
+    }
+}
+
+/// Locations are ignored, like spans in the AST.
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        use Error::*;
+        match (self.kind(), other.kind()) {
+            (UnknownVar(a), UnknownVar(b))
+            | (MoveInLoop(a), MoveInLoop(b))
+            | (Shadowing(a), Shadowing(b))
+            | (UnknownFn(a), UnknownFn(b))
+            | (UnresolvedReturn(a), UnresolvedReturn(b))
+            | (UndeclaredLifetime(a), UndeclaredLifetime(b))
+            | (UnknownEnum(a), UnknownEnum(b))
+            | (RefInEnum(a), RefInEnum(b)) => a == b,
+            (DoesNotOutlive(a, b), DoesNotOutlive(c, d))
+            | (UnknownVariant(a, b), UnknownVariant(c, d)) => a == c && b == d,
+            (NonExhaustive(a, xs), NonExhaustive(b, ys)) => a == b && xs == ys,
+            (MovedOut(a), MovedOut(b))
+            | (MoveBehindRef(a), MoveBehindRef(b))
+            | (UpdateBehindImmRef(a), UpdateBehindImmRef(b))
+            | (CopyAfterMutBorrow(a), CopyAfterMutBorrow(b))
+            | (MoveAfterBorrow(a), MoveAfterBorrow(b))
+            | (MutBorrowBehindImmRef(a), MutBorrowBehindImmRef(b))
+            | (MutBorrowAfterBorrow(a), MutBorrowAfterBorrow(b))
+            | (BorrowAfterMutBorrow(a), BorrowAfterMutBorrow(b))
+            | (MutBorrowAfterMutBorrow(a), MutBorrowAfterMutBorrow(b))
+            | (AssignAfterBorrow(a), AssignAfterBorrow(b))
+            | (UnknownField(a), UnknownField(b))
+            | (BindingBehindBox(a), BindingBehindBox(b)) => a == b,
+            (CannotDeref(a), CannotDeref(b)) => a == b,
+            (IncompatibleTypes(a, b), IncompatibleTypes(c, d)) => a == c && b == d,
+            (LifetimeTooShort(a), LifetimeTooShort(b)) => a == b,
+            (ArityMismatch(f, a, b), ArityMismatch(g, c, d)) => f == g && a == c && b == d,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            UnknownVar(x) => write!(f, "cannot find variable `{}`", x),
+            CannotDeref(t) => write!(f, "type `{}` cannot be dereferenced", t),
+            MovedOut(lv) => write!(f, "use of moved value `{}`", lv),
+            MoveInLoop(x) => write!(f, "value moved out of `{}` in a previous loop iteration", x),
+            MoveBehindRef(lv) => write!(
+                f,
+                "cannot move out of `{}`, which is behind a reference",
+                lv
+            ),
+            UpdateBehindImmRef(lv) => write!(
+                f,
+                "cannot assign to `{}`, which is behind a `&` reference",
+                lv
+            ),
+            CopyAfterMutBorrow(lv) => {
+                write!(f, "cannot use `{}` because it is mutably borrowed", lv)
+            }
+            MoveAfterBorrow(lv) => write!(f, "cannot move out of `{}` because it is borrowed", lv),
+            MutBorrowBehindImmRef(lv) => write!(
+                f,
+                "cannot borrow `{}` as mutable, as it is behind a `&` reference",
+                lv
+            ),
+            MutBorrowAfterBorrow(lv) => write!(
+                f,
+                "cannot borrow `{}` as mutable because it is also borrowed as immutable",
+                lv
+            ),
+            BorrowAfterMutBorrow(lv) => write!(
+                f,
+                "cannot borrow `{}` as immutable because it is also borrowed as mutable",
+                lv
+            ),
+            MutBorrowAfterMutBorrow(lv) => write!(
+                f,
+                "cannot borrow `{}` as mutable more than once at a time",
+                lv
+            ),
+            Shadowing(x) => write!(f, "variable `{}` is already declared", x),
+            IncompatibleTypes(t1, t2) => {
+                write!(f, "mismatched types: expected `{}`, found `{}`", t1, t2)
+            }
+            LifetimeTooShort(e) => write!(f, "{} does not live long enough", describe(e)),
+            AssignAfterBorrow(lv) => write!(f, "cannot assign to `{}` because it is borrowed", lv),
+            UnknownFn(name) => write!(f, "cannot find function `{}`", name),
+            ArityMismatch(name, expected, found) => write!(
+                f,
+                "function `{}` takes {} arguments but {} were supplied",
+                name, expected, found
+            ),
+            UnresolvedReturn(name) => write!(
+                f,
+                "cannot tell what the reference returned by `{}` borrows from",
+                name
+            ),
+            UnknownField(lv) => write!(
+                f,
+                "no field `{}` in `{}`",
+                lv.fields.last().unwrap_or(&0),
+                lv.prefix(lv.fields.len().saturating_sub(1))
+            ),
+            UndeclaredLifetime(a) => write!(f, "use of undeclared lifetime name `{}`", a),
+            DoesNotOutlive(a, b) => write!(
+                f,
+                "lifetime may not live long enough: `{}` must outlive `{}`",
+                a, b
+            ),
+            UnknownEnum(name) => write!(f, "cannot find enum `{}`", name),
+            UnknownVariant(name, variant) => {
+                write!(f, "no variant `{}` in enum `{}`", variant, name)
+            }
+            RefInEnum(name) => write!(f, "enum `{}` cannot hold references", name),
+            NonExhaustive(name, missing) => {
+                let missing: Vec<String> = missing
+                    .iter()
+                    .map(|v| format!("`{}::{}`", name, v))
+                    .collect();
+                write!(
+                    f,
+                    "non-exhaustive patterns: {} not covered",
+                    missing.join(", ")
+                )
+            }
+            BindingBehindBox(lv) => write!(
+                f,
+                "cannot bind fields of `{}` by move or by reference, as it is behind a box",
+                lv
+            ),
+            Located(e, _, _) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// What kind of expression `e` is, in one line however big it is.
+fn describe(e: &Expr) -> String {
+    match e.peel() {
+        Expr::Unit | Expr::Int(_) | Expr::Bool(_) => String::from("the value"),
+        Expr::Lval(lv, _) => format!("`{}`", lv),
+        Expr::Borrow(lv, false) => format!("the borrow `&{}`", lv),
+        Expr::Borrow(lv, true) => format!("the borrow `&mut {}`", lv),
+        Expr::Box(_) => String::from("the box"),
+        Expr::Block(..) => String::from("the value of the block"),
+        Expr::Call(name, _) => format!("the value of the call to `{}`", name),
+        Expr::BinOp(..) => String::from("the value of the operation"),
+        Expr::If(..) => String::from("the value of the `if`"),
+        Expr::While(..) => String::from("the value of the `while`"),
+        Expr::Tuple(_) => String::from("the tuple"),
+        Expr::Variant(name, variant, _) => format!("the `{}::{}`", name, variant),
+        Expr::Match(..) => String::from("the value of the `match`"),
+        Expr::Spanned(..) => unreachable!("peeled"),
+    }
 }
 
 pub type TypeResult<T> = Result<T, Error>;
@@ -66,12 +402,23 @@ impl Env {
         self.0.insert(var.to_string(), Slot { tipe, lifetime });
     }
 
-    pub fn type_lval(&self, lval: &Lval) -> TypeResult<Slot> {
-        let mut slot = self
+    /// The slot of `lval`'s variable, with its type projected to the field
+    /// `lval` names before any dereferences.
+    fn base(&self, lval: &Lval) -> TypeResult<Slot> {
+        let slot = self
             .0
             .get(&lval.ident)
-            .ok_or_else(|| Error::UnknownVar(lval.ident.clone()))?
-            .clone();
+            .ok_or_else(|| Error::UnknownVar(lval.ident.clone()).at(lval.span))?;
+        let tipe = project(&slot.tipe, &lval.fields)
+            .ok_or_else(|| Error::UnknownField(lval.prefix(lval.fields.len())))?;
+        Ok(Slot {
+            tipe,
+            lifetime: slot.lifetime.clone(),
+        })
+    }
+
+    pub fn type_lval(&self, lval: &Lval) -> TypeResult<Slot> {
+        let mut slot = self.base(lval)?;
         for _ in 0..lval.derefs {
             slot = match slot.tipe {
                 Type::Box(inner) => {
@@ -83,13 +430,84 @@ impl Env {
                         lifetime: slot.lifetime,
                     }
                 }
+                // a join slot lives no longer than either place it borrows
+                Type::Ref(ref inner, _) if is_join_slot(&lval.ident) => Slot {
+                    tipe: self.type_lval(inner)?.tipe,
+                    lifetime: slot.lifetime,
+                },
                 Type::Ref(ref inner, _) => self.type_lval(inner)?,
-                other => return Err(Error::CannotDeref(other)),
+                Type::Undefined(_) => return Err(Error::MovedOut(lval.clone())),
+                other => return Err(Error::CannotDeref(other).at(lval.span)),
             };
         }
         Ok(slot)
     }
 
+    /// Lifetime of the slot `lval` names. Unlike `type_lval` this looks
+    /// through moved-out boxes, since assigning to them is allowed.
+    pub fn lifetime_of(&self, lval: &Lval) -> TypeResult<Lifetime> {
+        let slot = self.base(lval)?;
+        let mut t = &slot.tipe;
+        for i in 0..lval.derefs {
+            while let Type::Undefined(inner) = t {
+                t = inner.as_ref();
+            }
+            match t {
+                Type::Box(inner) => t = inner.as_ref(),
+                Type::Ref(..) if is_join_slot(&lval.ident) => break,
+                Type::Ref(inner, _) => {
+                    return self.lifetime_of(&inner.deref_by(lval.derefs - i - 1));
+                }
+                other => return Err(Error::CannotDeref(other.clone()).at(lval.span)),
+            }
+        }
+        Ok(slot.lifetime)
+    }
+
+    /// Rewrite `lval` to start from the target of the last reference it
+    /// goes through, e.g. `**x` becomes `*y` when `x: &y`. References
+    /// stored in a join slot could point at either of two places, so the
+    /// rewrite stops there.
+    pub fn canonical(&self, lval: &Lval) -> Lval {
+        let mut cur = lval.clone();
+        'follow: loop {
+            if is_join_slot(&cur.ident) {
+                return cur;
+            }
+            let Ok(slot) = self.base(&cur) else {
+                return cur;
+            };
+            let mut t = &slot.tipe;
+            for i in 0..cur.derefs {
+                match t {
+                    Type::Box(inner) => t = inner.as_ref(),
+                    Type::Ref(inner, _) => {
+                        cur = inner.deref_by(cur.derefs - i - 1);
+                        continue 'follow;
+                    }
+                    _ => return cur,
+                }
+            }
+            return cur;
+        }
+    }
+
+    /// `tipe` with every reference target made canonical.
+    pub fn canonical_type(&self, tipe: &Type) -> Type {
+        match tipe {
+            Type::Unit | Type::Int | Type::Bool | Type::Enum(_) => tipe.clone(),
+            Type::Box(inner) => Type::boxx(self.canonical_type(inner)),
+            Type::Variant(name, variant, ts) => Type::Variant(
+                name.clone(),
+                variant.clone(),
+                ts.iter().map(|t| self.canonical_type(t)).collect(),
+            ),
+            Type::Undefined(inner) => Type::undefined(self.canonical_type(inner)),
+            Type::Ref(lv, is_mut) => Type::Ref(self.canonical(lv), *is_mut),
+            Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| self.canonical_type(t)).collect()),
+        }
+    }
+
     pub fn contained(&self, var: &str) -> Option<&Type> {
         self.0.get(var).and_then(|slot| {
             let mut t = &slot.tipe;
@@ -116,11 +534,13 @@ impl Env {
     }
 
     pub fn moove(&mut self, lval: &Lval) -> TypeResult<()> {
-        let mut slot = self
+        let slot = self
             .0
             .get_mut(&lval.ident)
-            .ok_or_else(|| Error::UnknownVar(lval.ident.clone()))?;
-        let mut t: &mut Type = &mut slot.tipe;
+            .ok_or_else(|| Error::UnknownVar(lval.ident.clone()).at(lval.span))?;
+        // moving a field leaves the rest of the tuple in place
+        let mut t = project_mut(&mut slot.tipe, &lval.fields)
+            .ok_or_else(|| Error::MovedOut(lval.clone()))?;
         for _ in 0..lval.derefs {
             if let Type::Box(inner) = t {
                 t = inner.as_mut();
@@ -128,18 +548,14 @@ impl Env {
                 return Err(Error::MoveBehindRef(lval.clone()));
             }
         }
-        if let Type::Ref(_, _) = t {
-            return Err(Error::MoveBehindRef(lval.clone()));
-        }
         *t = Type::Undefined(Box::new(t.clone()));
         Ok(())
     }
 
     pub fn muut(&self, lval: &Lval) -> bool {
-        let mut ident = lval.ident.clone();
-        let mut t = match self.0.get(&ident) {
-            Some(slot) => slot.tipe.clone(),
-            None => return false,
+        let mut t = match self.base(lval) {
+            Ok(slot) => slot.tipe,
+            Err(_) => return false,
         };
         let mut rem = lval.derefs;
 
@@ -151,9 +567,8 @@ impl Env {
                     rem -= 1;
                 }
                 Type::Ref(ref inner, true) => {
-                    ident = inner.ident.clone();
-                    if let Some(s2) = self.0.get(&ident) {
-                        t = s2.tipe.clone();
+                    if let Ok(s2) = self.base(inner) {
+                        t = s2.tipe;
                         rem -= 1;
                     } else {
                         return false;
@@ -177,45 +592,92 @@ impl Env {
         match (t1, t2) {
             (Type::Undefined(a), _) => self.compatible(a, t2),
             (_, Type::Undefined(b)) => self.compatible(t1, b),
-            (Type::Int, Type::Int) | (Type::Unit, Type::Unit) => true,
+            (Type::Int, Type::Int) | (Type::Unit, Type::Unit) | (Type::Bool, Type::Bool) => true,
             (Type::Box(a), Type::Box(b)) => self.compatible(a, b),
             (Type::Ref(_, m1), Type::Ref(_, m2)) => m1 == m2,
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.compatible(a, b))
+            }
+            (Type::Enum(a) | Type::Variant(a, _, _), Type::Enum(b) | Type::Variant(b, _, _)) => {
+                a == b
+            }
             _ => false,
         }
     }
 
+    /// Does an access to `place` overlap the borrow of `loan`? Places
+    /// overlap when one's path is a prefix of the other's, as with Rust's
+    /// borrowck, so distinct fields are disjoint. A `shallow` access, i.e. an
+    /// assignment, only reaches the part of a longer loan behind fields and
+    /// boxes it owns: overwriting a reference leaves its target intact.
+    pub fn overlaps(&self, loan: &Lval, place: &Lval, shallow: bool) -> bool {
+        if !loan.is_prefix_of(place) && !place.is_prefix_of(loan) {
+            return false;
+        }
+        if !shallow || loan.path_len() <= place.path_len() {
+            return true;
+        }
+        (place.path_len()..loan.path_len()).all(|n| {
+            n < loan.fields.len()
+                || !matches!(
+                    self.type_lval(&loan.prefix(n)).map(|s| s.tipe),
+                    Ok(Type::Ref(..))
+                )
+        })
+    }
+
     pub fn write(&mut self, lval: &Lval, new_t: Type) -> TypeResult<()> {
         use Error::*;
 
-        // 1) Forbid if there's any outstanding borrow on the base variable
-        for slot in self.0.values() {
-            if let Type::Ref(ref tgt, _) = slot.tipe {
-                if tgt.ident == lval.ident {
-                    return Err(AssignAfterBorrow(lval.clone()));
+        // 1) Forbid if there's any outstanding borrow overlapping the place.
+        //    Loans that only reach past it through a reference survive the
+        //    write, and are pinned to the place they borrow now.
+        let mut survivors = vec![];
+        for (x, slot) in &self.0 {
+            for (tgt, _) in loans(&slot.tipe) {
+                if self.overlaps(tgt, lval, true) {
+                    return Err(AssignAfterBorrow(lval.clone())
+                        .label(tgt.span, &format!("`{}` is borrowed here", tgt)));
+                }
+                if lval.is_prefix_of(tgt) {
+                    survivors.push((x.clone(), self.canonical_type(&slot.tipe)));
                 }
             }
         }
+        for (x, tipe) in survivors {
+            self.0.get_mut(&x).unwrap().tipe = tipe;
+        }
+        // so is a reborrow through the place itself, as in `r = &mut *r`,
+        // or the new type would refer to itself
+        let new_t = if refs_in(&new_t).iter().any(|tgt| lval.is_prefix_of(tgt)) {
+            self.canonical_type(&new_t)
+        } else {
+            new_t
+        };
 
-        // 2) Flatten any leading &mut chains and remember if we did
+        // 2) Follow the derefs through boxes to any &mut, flattening it into
+        //    the place it borrows, and remember if we did
         let mut flat = lval.clone();
         let mut behind_mut = false;
-        while let Some(s) = self.0.get(&flat.ident) {
-            if let Type::Ref(inner, true) = &s.tipe {
-                behind_mut = true;
-                flat.ident = inner.ident.clone();
-                flat.derefs = inner.derefs + (flat.derefs - 1);
-            } else {
-                break;
+        'flatten: while let Ok(slot) = self.base(&flat) {
+            let mut t = slot.tipe;
+            for i in 0..flat.derefs {
+                match t {
+                    Type::Box(inner) => t = *inner,
+                    Type::Ref(inner, true) => {
+                        behind_mut = true;
+                        flat = inner.deref_by(flat.derefs - i - 1);
+                        continue 'flatten;
+                    }
+                    _ => break,
+                }
             }
+            break;
         }
 
         // 3) Immutably borrow to extract the “old” type beneath exactly flat.derefs Boxes
         let old_t = {
-            let s = self
-                .0
-                .get(&flat.ident)
-                .ok_or_else(|| UnknownVar(flat.ident.clone()))?;
-            let mut t = s.tipe.clone();
+            let mut t = self.base(&flat)?.tipe;
             for _ in 0..flat.derefs {
                 if let Type::Box(inner) = t {
                     t = *inner;
@@ -226,9 +688,11 @@ impl Env {
             t
         };
 
-        // 4) If the final target is an immutable & and we never passed through &mut, error
+        // 4) If the final target is an immutable & reached through derefs that
+        //    never passed a &mut, error; rebinding a variable or field that
+        //    holds one is fine
         if let Type::Ref(_, false) = old_t {
-            if !behind_mut {
+            if lval.derefs > 0 && !behind_mut {
                 return Err(UpdateBehindImmRef(lval.clone()));
             }
         }
@@ -243,7 +707,9 @@ impl Env {
             .0
             .get_mut(&flat.ident)
             .ok_or_else(|| UnknownVar(flat.ident.clone()))?;
-        let mut ptr: &mut Type = &mut slot.tipe;
+        // fields of a moved-out tuple can't be assigned one by one
+        let mut ptr =
+            project_mut(&mut slot.tipe, &flat.fields).ok_or_else(|| MovedOut(lval.clone()))?;
         for _ in 0..flat.derefs {
             if let Type::Box(inner) = ptr {
                 ptr = inner.as_mut();
@@ -258,63 +724,568 @@ impl Env {
     pub fn drop(&mut self, l: Lifetime) {
         self.0.retain(|_, slot| slot.lifetime != l);
     }
+
+    /// The environment after the branches `self` and `other` merge:
+    /// anything moved on either path counts as moved. A variable that
+    /// borrows different places on the two paths borrows both, through a
+    /// slot of its own that holds the two loans, see `join_loans`.
+    pub fn join(&self, other: &Env) -> TypeResult<Env> {
+        let mut joined = Env::default();
+        let mut held = vec![];
+        for (x, slot) in &self.0 {
+            if let Some(theirs) = other.0.get(x) {
+                let tipe = join_by(&slot.tipe, &theirs.tipe, &mut |a, b, is_mut| {
+                    let referents = (self.type_lval(a)?.tipe, other.type_lval(b)?.tipe);
+                    if !self.compatible(&referents.0, &referents.1) {
+                        return Err(Error::IncompatibleTypes(
+                            Type::Ref(a.clone(), is_mut),
+                            Type::Ref(b.clone(), is_mut),
+                        ));
+                    }
+                    let place = fresh_join_slot(x, |y| {
+                        self.0.contains_key(y)
+                            || other.0.contains_key(y)
+                            || held.iter().any(|(z, _, _)| z == y)
+                    });
+                    held.push((
+                        place.clone(),
+                        join_loans(a, b, is_mut),
+                        slot.lifetime.clone(),
+                    ));
+                    Ok(Type::Ref(Lval::field(&place, &[0], 1), is_mut))
+                })?;
+                joined.insert(x, tipe, slot.lifetime.clone());
+            }
+        }
+        // slots holding loans joined by an inner branch stay with the
+        // variables that point through them
+        for (x, slot) in self.0.iter().chain(&other.0) {
+            if is_join_slot(x) && !joined.0.contains_key(x) {
+                joined.0.insert(x.clone(), slot.clone());
+            }
+        }
+        for (x, tipe, lifetime) in held {
+            joined.insert(&x, tipe, lifetime);
+        }
+        Ok(joined)
+    }
+
+    /// The environment after a loop's back edge from `other` meets `self`.
+    /// Unlike `join`, a variable must borrow the same place on both paths,
+    /// since the body was only checked with what it borrowed on entry.
+    pub fn join_loop(&self, other: &Env) -> TypeResult<Env> {
+        let mut joined = Env::default();
+        for (x, slot) in &self.0 {
+            if let Some(theirs) = other.0.get(x) {
+                let tipe = join(&slot.tipe, &theirs.tipe)?;
+                joined.insert(x, tipe, slot.lifetime.clone());
+            }
+        }
+        Ok(joined)
+    }
+}
+
+/// A checked program, annotated for the evaluator, and its type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typed {
+    pub expr: Expr,
+    pub tipe: Type,
+}
+
+/// A checked function signature. References in parameter types point at
+/// placeholder places named `param#n`, standing for `*...*param` with `n`
+/// derefs; `ret` is the body's type in terms of those placeholders, and is
+/// `None` until the body has been checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FnSig {
+    pub params: Vec<(Ident, Type)>,
+    pub decl_ret: Type,
+    pub ret: Option<Type>,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct Context {
     pub env: Env,
     pub lifetime_stack: Vec<Lifetime>,
+    pub fns: HashMap<Ident, FnSig>,
+    /// Whether failing statements are recorded in `errors` and skipped
+    /// rather than aborting the check, see `check_all`.
+    pub recover: bool,
+    pub errors: Vec<Error>,
+    /// Bindings whose declaration failed while recovering.
+    pub poisoned: HashSet<Ident>,
+    /// Functions whose signature or body failed to check while recovering.
+    pub poisoned_fns: HashSet<Ident>,
+    /// Variables used after the statement being checked, in enclosing
+    /// blocks and later loop iterations.
+    pub live_after: HashSet<Ident>,
+    /// Lifetime parameters of the function being checked, see `FnDef`.
+    pub lifetime_params: Vec<(Ident, Lifetime)>,
+    /// The variants of each enum in scope, with the types of their fields.
+    pub enums: HashMap<Ident, Vec<(Ident, Vec<Type>)>>,
+    /// Known outlives relations between lifetime parameters, declared or
+    /// implied by nested references: `(a, b)` when `a` outlives `b`.
+    pub bounds: Vec<(Lifetime, Lifetime)>,
+}
+
+/// The lifetimes a function signature refers to, see `FnDef::lifetimes`.
+/// Elided references take the `'_` entries in order.
+struct SigScope<'a> {
+    lifetimes: &'a [(Ident, Lifetime)],
+    elided: usize,
+    bounds: Vec<(Lifetime, Lifetime)>,
+}
+
+impl SigScope<'_> {
+    fn new(def: &FnDef) -> SigScope<'_> {
+        SigScope {
+            lifetimes: &def.lifetimes,
+            elided: 0,
+            bounds: vec![],
+        }
+    }
+
+    /// The lifetime of a reference written with `name`. References in the
+    /// return type without one are checked by what the body returns, so
+    /// they get the global lifetime once the elided ones run out.
+    fn lifetime(&mut self, name: &Option<Ident>) -> TypeResult<Lifetime> {
+        if let Some(name) = name {
+            return named_lifetime(self.lifetimes, name);
+        }
+        let mut elided = self.lifetimes.iter().filter(|(b, _)| b == "'_");
+        let found = elided.nth(self.elided);
+        self.elided += 1;
+        Ok(found.map_or_else(Lifetime::global, |(_, l)| l.clone()))
+    }
+}
+
+fn named_lifetime(lifetimes: &[(Ident, Lifetime)], name: &Ident) -> TypeResult<Lifetime> {
+    if name == "'static" {
+        return Ok(Lifetime::global());
+    }
+    match lifetimes.iter().find(|(a, _)| a == name) {
+        Some((_, l)) => Ok(l.clone()),
+        None => Err(Error::UndeclaredLifetime(name.clone())),
+    }
 }
 
 impl Context {
+    /// Does `l` outlive `m`? The global lifetime outlives everything, and an
+    /// enclosing block outlives the blocks nested inside it. A lifetime
+    /// parameter outlives the function body, and whatever it's bounded by.
     fn lifetime_contains(&self, l: Lifetime, m: Lifetime) -> bool {
+        if l == m || l == Lifetime::global() {
+            return true;
+        }
+        if self.lifetime_params.iter().any(|(_, p)| *p == l) {
+            if self.lifetime_stack.contains(&m) {
+                return true;
+            }
+            let mut reached = vec![l];
+            let mut i = 0;
+            while let Some(k) = reached.get(i).cloned() {
+                if k == m {
+                    return true;
+                }
+                for (a, b) in &self.bounds {
+                    if *a == k && !reached.contains(b) {
+                        reached.push(b.clone());
+                    }
+                }
+                i += 1;
+            }
+            return false;
+        }
         let mut found = false;
         for lt in self.lifetime_stack.iter().rev() {
             if *lt == m {
-                return found;
-            }
-            if *lt == l {
                 found = true;
+            } else if found && *lt == l {
+                return true;
             }
         }
         false
     }
 
+    /// Can a value of type `tipe` be stored in a slot with lifetime `l`?
+    /// Every place it refers to must outlive `l`.
     fn well_formed(&self, tipe: &Type, l: Lifetime) -> bool {
         match tipe {
-            Type::Unit | Type::Int => true,
-            Type::Box(inner) | Type::Undefined(inner) => self.well_formed(inner, l.clone()),
-            Type::Ref(_, _) => self.lifetime_contains(l.clone(), l),
+            Type::Unit | Type::Int | Type::Bool | Type::Enum(_) => true,
+            Type::Box(inner) | Type::Undefined(inner) => self.well_formed(inner, l),
+            Type::Ref(lv, _) => match self.env.type_lval(lv) {
+                Ok(slot) => self.lifetime_contains(slot.lifetime, l),
+                Err(_) => false,
+            },
+            Type::Tuple(ts) | Type::Variant(_, _, ts) => {
+                ts.iter().all(|t| self.well_formed(t, l.clone()))
+            }
+        }
+    }
+
+    /// Convert a written type for the parameter `owner`, reached through
+    /// `depth` derefs. The target of each reference becomes a placeholder
+    /// variable that outlives the whole call, inserted into `env`. Tuple
+    /// fields extend the owner, as in `x.0#1` for `*x.0`; places in tuples
+    /// behind a reference or box have no such name, and get `x/0#1`.
+    /// A placeholder lives as long as the lifetime of its reference.
+    fn param_type(
+        ann: &TypeAnn,
+        owner: &str,
+        depth: usize,
+        env: &mut Env,
+        scope: &mut SigScope,
+    ) -> TypeResult<Type> {
+        Ok(match ann {
+            TypeAnn::Unit => Type::Unit,
+            TypeAnn::Int => Type::Int,
+            TypeAnn::Bool => Type::Bool,
+            TypeAnn::Enum(name) => Type::Enum(name.clone()),
+            TypeAnn::Box(inner) => {
+                Type::boxx(Self::param_type(inner, owner, depth + 1, env, scope)?)
+            }
+            TypeAnn::Ref(inner, is_mut, name) => {
+                let lifetime = scope.lifetime(name)?;
+                let place = format!("{}#{}", owner, depth + 1);
+                let tipe = Self::param_type(inner, owner, depth + 1, env, scope)?;
+                // what `tipe` refers to outlives the reference to it
+                for lv in refs_in(&tipe) {
+                    let target = env.0[&lv.ident].lifetime.clone();
+                    scope.bounds.push((target, lifetime.clone()));
+                }
+                env.insert(&place, tipe, lifetime);
+                Type::Ref(Lval::new(&place, 0), *is_mut)
+            }
+            TypeAnn::Tuple(anns) => Type::Tuple(
+                anns.iter()
+                    .enumerate()
+                    .map(|(i, ann)| {
+                        let sep = if depth == 0 { '.' } else { '/' };
+                        let owner = format!("{}{}{}", owner, sep, i);
+                        Self::param_type(ann, &owner, depth, env, scope)
+                    })
+                    .collect::<TypeResult<_>>()?,
+            ),
+        })
+    }
+
+    fn signature(def: &FnDef) -> TypeResult<FnSig> {
+        let mut scratch = Env::default();
+        let mut scope = SigScope::new(def);
+        let params = def
+            .params
+            .iter()
+            .map(|(x, ann)| {
+                Ok((
+                    x.clone(),
+                    Self::param_type(ann, x, 0, &mut scratch, &mut scope)?,
+                ))
+            })
+            .collect::<TypeResult<_>>()?;
+        scope.elided = def.lifetimes.len();
+        Ok(FnSig {
+            params,
+            decl_ret: Self::param_type(&def.ret, "#ret", 0, &mut scratch, &mut scope)?,
+            ret: None,
+        })
+    }
+
+    /// Check that the references in `tipe`, returned where `ann` is
+    /// declared, point at places living as long as the lifetimes it names.
+    fn check_outlives(&self, ann: &TypeAnn, tipe: &Type) -> TypeResult<()> {
+        match (ann, strip_undefined(tipe)) {
+            (TypeAnn::Box(ann), Type::Box(tipe)) => self.check_outlives(ann, tipe),
+            (TypeAnn::Tuple(anns), Type::Tuple(ts)) => anns
+                .iter()
+                .zip(ts)
+                .try_for_each(|(ann, t)| self.check_outlives(ann, t)),
+            (TypeAnn::Ref(ann, _, name), Type::Ref(lv, _)) => {
+                let slot = self.env.type_lval(lv)?;
+                if let Some(name) = name {
+                    let required = named_lifetime(&self.lifetime_params, name)?;
+                    if !self.lifetime_contains(slot.lifetime.clone(), required) {
+                        return Err(Error::DoesNotOutlive(
+                            self.lifetime_name(&slot.lifetime),
+                            name.clone(),
+                        ));
+                    }
+                }
+                self.check_outlives(ann, &slot.tipe)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn lifetime_name(&self, l: &Lifetime) -> Ident {
+        match self.lifetime_params.iter().find(|(_, p)| p == l) {
+            Some((name, _)) => name.clone(),
+            None => String::from("'static"),
+        }
+    }
+
+    /// Check a function body in an environment holding only its parameters.
+    fn type_fn(&mut self, def: &mut FnDef) -> TypeResult<Type> {
+        let mut ctxt = Context {
+            env: Env::default(),
+            lifetime_stack: vec![def.lifetime.clone()],
+            fns: self.fns.clone(),
+            enums: self.enums.clone(),
+            recover: self.recover,
+            lifetime_params: def.lifetimes.clone(),
+            ..Context::default()
+        };
+        let mut scope = SigScope::new(def);
+        for (x, ann) in &def.params {
+            if ctxt.env.0.contains_key(x) {
+                return Err(Error::Shadowing(x.clone()));
+            }
+            let tipe = Self::param_type(ann, x, 0, &mut ctxt.env, &mut scope)?;
+            ctxt.env.insert(x, tipe, def.lifetime.clone());
+        }
+        for (a, b) in &def.bounds {
+            let bound = (
+                named_lifetime(&def.lifetimes, a)?,
+                named_lifetime(&def.lifetimes, b)?,
+            );
+            scope.bounds.push(bound);
+        }
+        ctxt.bounds = scope.bounds;
+        let ret = ctxt.annotate(&mut def.body);
+        self.errors.append(&mut ctxt.errors);
+        let ret = ctxt.env.canonical_type(&ret?);
+        let decl_ret = &self.fns[&def.name].decl_ret;
+        if !ctxt.env.compatible(decl_ret, &ret) {
+            return Err(Error::IncompatibleTypes(decl_ret.clone(), ret));
+        }
+        // only the placeholders outlive the call, not the parameters
+        let escapes = |lv: &&Lval| {
+            let slot = ctxt.env.type_lval(lv);
+            slot.is_ok_and(|s| !ctxt.lifetime_stack.contains(&s.lifetime))
+        };
+        if !refs_in(&ret).iter().all(escapes) {
+            return Err(Error::LifetimeTooShort(def.body.tail().clone()));
+        }
+        let body = def.body.span().unwrap_or_default();
+        ctxt.check_outlives(&def.ret, &ret)
+            .map_err(|e| e.at(body))?;
+        Ok(ret)
+    }
+
+    /// The type of an enum field written as `ann`. Enums own their fields
+    /// outright, so they can't hold references.
+    fn field_type(&self, ann: &TypeAnn, owner: &Ident) -> TypeResult<Type> {
+        Ok(match ann {
+            TypeAnn::Unit => Type::Unit,
+            TypeAnn::Int => Type::Int,
+            TypeAnn::Bool => Type::Bool,
+            TypeAnn::Box(inner) => Type::boxx(self.field_type(inner, owner)?),
+            TypeAnn::Ref(..) => return Err(Error::RefInEnum(owner.clone())),
+            TypeAnn::Tuple(anns) => Type::Tuple(
+                anns.iter()
+                    .map(|ann| self.field_type(ann, owner))
+                    .collect::<TypeResult<_>>()?,
+            ),
+            TypeAnn::Enum(name) => {
+                self.variants(name)?;
+                Type::Enum(name.clone())
+            }
+        })
+    }
+
+    fn variants(&self, name: &Ident) -> TypeResult<&[(Ident, Vec<Type>)]> {
+        match self.enums.get(name) {
+            Some(variants) => Ok(variants),
+            None => Err(Error::UnknownEnum(name.clone())),
+        }
+    }
+
+    /// The types of the fields of `name::variant`.
+    fn fields(&self, name: &Ident, variant: &Ident) -> TypeResult<Vec<Type>> {
+        match self.variants(name)?.iter().find(|(v, _)| v == variant) {
+            Some((_, fields)) => Ok(fields.clone()),
+            None => Err(Error::UnknownVariant(name.clone(), variant.clone())),
+        }
+    }
+
+    /// Check that the enums named in `def`'s signature exist.
+    fn check_enums(&self, def: &FnDef) -> TypeResult<()> {
+        fn walk(ctxt: &Context, ann: &TypeAnn) -> TypeResult<()> {
+            match ann {
+                TypeAnn::Box(inner) | TypeAnn::Ref(inner, _, _) => walk(ctxt, inner),
+                TypeAnn::Tuple(anns) => anns.iter().try_for_each(|ann| walk(ctxt, ann)),
+                TypeAnn::Enum(name) => ctxt.variants(name).map(|_| ()),
+                _ => Ok(()),
+            }
+        }
+        let mut anns = def.params.iter().map(|(_, ann)| ann);
+        anns.try_for_each(|ann| walk(self, ann))?;
+        walk(self, &def.ret)
+    }
+
+    /// Register the enums defined in `stmts`. Their names come first, so
+    /// fields can refer to any of them.
+    fn hoist_enums(&mut self, stmts: &[Stmt]) -> TypeResult<()> {
+        let mut defs: Vec<(&EnumDef, Span)> = vec![];
+        for stmt in stmts {
+            if let Stmt::Enum(def) = stmt.peel() {
+                let span = stmt.span().unwrap_or_default();
+                if self.enums.contains_key(&def.name) {
+                    self.fail(Error::Shadowing(def.name.clone()).at(span))?;
+                    continue;
+                }
+                self.enums.insert(def.name.clone(), vec![]);
+                defs.push((def, span));
+            }
+        }
+        for (def, span) in defs {
+            let variants = def
+                .variants
+                .iter()
+                .map(|(v, anns)| {
+                    let fields = anns
+                        .iter()
+                        .map(|ann| self.field_type(ann, &def.name))
+                        .collect::<TypeResult<_>>()?;
+                    Ok((v.clone(), fields))
+                })
+                .collect::<TypeResult<_>>();
+            match variants {
+                Ok(variants) => {
+                    self.enums.insert(def.name.clone(), variants);
+                }
+                Err(e) => self.fail(e.at(span))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Register the enums and functions defined in `stmts` and check the
+    /// function bodies, so calls can come before definitions.
+    fn hoist_fns(&mut self, stmts: &mut [Stmt]) -> TypeResult<()> {
+        self.hoist_enums(stmts)?;
+        for stmt in stmts.iter() {
+            if let Stmt::Fn(def) = stmt.peel() {
+                if self.fns.contains_key(&def.name) {
+                    let span = stmt.span().unwrap_or_default();
+                    self.fail(Error::Shadowing(def.name.clone()).at(span))?;
+                    continue;
+                }
+                match self.check_enums(def).and_then(|()| Self::signature(def)) {
+                    Ok(sig) => self.fns.insert(def.name.clone(), sig),
+                    Err(e) => {
+                        self.fail(e.at(stmt.span().unwrap_or_default()))?;
+                        self.poisoned_fns.insert(def.name.clone());
+                        continue;
+                    }
+                };
+            }
+        }
+        for stmt in stmts.iter_mut() {
+            let span = stmt.span().unwrap_or_default();
+            if let Stmt::Fn(def) = stmt.peel_mut() {
+                if !self.fns.contains_key(&def.name) {
+                    continue;
+                }
+                match self.type_fn(def) {
+                    Ok(ret) => self.fns.get_mut(&def.name).unwrap().ret = Some(ret),
+                    Err(e) => {
+                        self.fail(e.at(span))?;
+                        self.poisoned_fns.insert(def.name.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The caller's place for the callee placeholder `*...*arg` with `n`
+    /// derefs, given the type of the argument.
+    fn arg_place(&self, arg: &Type, n: usize) -> Option<Lval> {
+        let mut t = arg.clone();
+        for i in 0..n {
+            match t {
+                Type::Box(inner) => t = *inner,
+                Type::Ref(lv, _) => return Some(lv.deref_by(n - i - 1)),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Rewrite the placeholders in a callee's return type into caller places.
+    fn instantiate(&self, ret: &Type, params: &[(Ident, Type)]) -> Option<Type> {
+        Some(match ret {
+            Type::Unit | Type::Int | Type::Bool | Type::Enum(_) | Type::Variant(..) => ret.clone(),
+            Type::Box(inner) => Type::boxx(self.instantiate(inner, params)?),
+            Type::Undefined(inner) => Type::undefined(self.instantiate(inner, params)?),
+            Type::Ref(lv, is_mut) if lv.fields.is_empty() => {
+                let (owner, n) = lv.ident.split_once('#')?;
+                let mut path = owner.split('.');
+                let param = path.next()?;
+                let fields = path.map(|f| f.parse().ok()).collect::<Option<Vec<_>>>()?;
+                let (_, arg) = params.iter().find(|(x, _)| x == param)?;
+                let place = self.arg_place(&project(arg, &fields)?, n.parse().ok()?)?;
+                Type::Ref(place.deref_by(lv.derefs), *is_mut)
+            }
+            Type::Ref(..) => return None,
+            Type::Tuple(ts) => Type::Tuple(
+                ts.iter()
+                    .map(|t| self.instantiate(t, params))
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
+
+    /// The type of `expr`, which is left as it is; see `elaborate`.
+    pub fn type_expr(&mut self, expr: &Expr) -> TypeResult<Type> {
+        self.annotate(&mut expr.clone())
+    }
+
+    pub fn type_stmt(&mut self, stmt: &Stmt) -> TypeResult<()> {
+        self.annotate_stmt(&mut stmt.clone())
+    }
+
+    /// Check `expr`, and return it annotated for the evaluator: every place
+    /// read is marked as a copy, or left as a move.
+    pub fn elaborate(&mut self, expr: &Expr) -> TypeResult<Typed> {
+        let mut expr = expr.clone();
+        let tipe = self.annotate(&mut expr)?;
+        Ok(Typed { expr, tipe })
+    }
+
+    /// Check top-level statements, such as a line of the playground, in
+    /// the current environment and return them annotated as by `elaborate`.
+    pub fn elaborate_stmts(&mut self, stmts: &[Stmt]) -> TypeResult<Vec<Stmt>> {
+        let mut stmts = stmts.to_vec();
+        self.hoist_fns(&mut stmts)?;
+        for stmt in stmts.iter_mut() {
+            self.annotate_stmt(stmt)?;
         }
+        Ok(stmts)
     }
 
-    pub fn type_expr(&mut self, expr: &mut Expr) -> TypeResult<Type> {
+    /// The type checker proper. It records in `expr` which place reads copy.
+    fn annotate(&mut self, expr: &mut Expr) -> TypeResult<Type> {
         use Expr::*;
         match expr {
             Int(_) => Ok(Type::Int),
+            Bool(_) => Ok(Type::Bool),
             Unit => Ok(Type::Unit),
             Lval(lv, _) => {
                 let slot = self.env.type_lval(lv)?;
-                fn has_undef(ty: &Type) -> bool {
-                    match ty {
-                        Type::Undefined(_) => true,
-                        Type::Box(inner) => has_undef(inner),
-                        _ => false,
-                    }
-                }
                 if has_undef(&slot.tipe) {
                     return Err(Error::MovedOut(lv.clone()));
                 }
-                let is_copy = matches!(slot.tipe, Type::Int | Type::Unit);
-                for other in self.env.0.values() {
-                    if let Type::Ref(ref tgt, mutbl) = &other.tipe {
-                        if tgt.ident == lv.ident {
-                            if *mutbl && is_copy {
-                                return Err(Error::CopyAfterMutBorrow(lv.clone()));
-                            }
-                            if !*mutbl && !is_copy {
-                                return Err(Error::MoveAfterBorrow(lv.clone()));
-                            }
+                let is_copy = slot.tipe.is_copy();
+                for (tgt, mutbl) in self.env.0.values().flat_map(|s| loans(&s.tipe)) {
+                    if self.env.overlaps(tgt, lv, false) {
+                        if mutbl && is_copy {
+                            return Err(Error::CopyAfterMutBorrow(lv.clone())
+                                .label(tgt.span, "mutable borrow occurs here"));
+                        }
+                        if !is_copy {
+                            return Err(Error::MoveAfterBorrow(lv.clone())
+                                .label(tgt.span, "borrow occurs here"));
                         }
                     }
                 }
@@ -326,85 +1297,763 @@ impl Context {
                     Ok(slot.tipe)
                 }
             }
-            Box(inner) => Ok(Type::boxx(self.type_expr(inner)?)),
+            Box(inner) => Ok(Type::boxx(self.annotate(inner)?)),
+            Tuple(elems) => Ok(Type::Tuple(
+                elems
+                    .iter_mut()
+                    .map(|e| self.annotate(e))
+                    .collect::<TypeResult<_>>()?,
+            )),
             Borrow(lv, is_mut) => {
                 let slot = self.env.type_lval(lv)?;
-                if let Type::Undefined(_) = slot.tipe {
+                if has_undef(&slot.tipe) {
                     return Err(Error::MovedOut(lv.clone()));
                 }
-                if let Some(block_lt) = self.lifetime_stack.last() {
-                    if !self.lifetime_contains(slot.lifetime.clone(), block_lt.clone()) {
-                        return Err(Error::LifetimeTooShort(crate::utils::Expr::Borrow(
-                            lv.clone(),
-                            *is_mut,
-                        )));
-                    }
-                }
                 if *is_mut {
                     if !self.env.muut(lv) {
                         return Err(Error::MutBorrowBehindImmRef(lv.clone()));
                     }
-                    for other in self.env.0.values() {
-                        if let Type::Ref(ref tgt, false) = &other.tipe {
-                            if tgt.ident == lv.ident {
-                                return Err(Error::MutBorrowAfterBorrow(lv.clone()));
-                            }
+                    for (tgt, mutbl) in self.env.0.values().flat_map(|s| loans(&s.tipe)) {
+                        if !self.env.overlaps(tgt, lv, false) {
+                            continue;
                         }
+                        return Err(if mutbl {
+                            Error::MutBorrowAfterMutBorrow(lv.clone())
+                                .label(tgt.span, "first mutable borrow occurs here")
+                        } else {
+                            Error::MutBorrowAfterBorrow(lv.clone())
+                                .label(tgt.span, "immutable borrow occurs here")
+                        });
                     }
                 } else {
-                    for other in self.env.0.values() {
-                        if let Type::Ref(ref tgt, true) = &other.tipe {
-                            if tgt.ident == lv.ident {
-                                return Err(Error::BorrowAfterMutBorrow(lv.clone()));
-                            }
+                    for (tgt, mutbl) in self.env.0.values().flat_map(|s| loans(&s.tipe)) {
+                        if mutbl && self.env.overlaps(tgt, lv, false) {
+                            return Err(Error::BorrowAfterMutBorrow(lv.clone())
+                                .label(tgt.span, "mutable borrow occurs here"));
                         }
                     }
                 }
                 Ok(Type::Ref(lv.clone(), *is_mut))
             }
             Block(stmts, final_e, lt) => {
+                self.hoist_fns(stmts)?;
                 self.lifetime_stack.push(lt.clone());
-                for s in stmts {
-                    self.type_stmt(s)?;
+                // live[i]: variables used from statement i on
+                let outer = self.live_after.clone();
+                let mut live = vec![outer.clone()];
+                final_e.uses(&mut live[0]);
+                for s in stmts.iter().rev() {
+                    let mut vars = live.last().unwrap().clone();
+                    if let Some(x) = s.overwrites() {
+                        vars.remove(x);
+                    }
+                    s.uses(&mut vars);
+                    live.push(vars);
                 }
-                let result = self.type_expr(final_e)?;
+                live.reverse();
+                for (i, s) in stmts.iter_mut().enumerate() {
+                    self.live_after = live[i + 1].clone();
+                    let checked = self.annotate_stmt_or_recover(s, &live[i]);
+                    self.live_after = outer.clone();
+                    checked?;
+                }
+                self.kill_dead(&live[stmts.len()]);
+                let result = self.annotate(final_e)?;
                 let popped = self.lifetime_stack.pop().unwrap();
                 self.env.drop(popped);
+                // the result must not refer to anything the block just dropped
+                if !self.well_formed(&result, self.fresh_lifetime()) {
+                    return Err(Error::LifetimeTooShort(*final_e.clone()));
+                }
                 Ok(result)
             }
+            BinOp(op, lhs, rhs) => {
+                for operand in [lhs, rhs] {
+                    let t = self.annotate(operand)?;
+                    if t != Type::Int {
+                        let span = operand.span().unwrap_or_default();
+                        return Err(Error::IncompatibleTypes(Type::Int, t).at(span));
+                    }
+                }
+                Ok(if op.is_comparison() {
+                    Type::Bool
+                } else {
+                    Type::Int
+                })
+            }
+            If(cond, then_e, else_e) => {
+                self.type_cond(cond)?;
+                let mut other = self.clone();
+                other.errors.clear();
+                let then_t = self.annotate(then_e)?;
+                let else_t = other.annotate(else_e);
+                self.errors.append(&mut other.errors);
+                let else_t = else_t?;
+                self.env = self.env.join(&other.env)?;
+                self.join_values(&then_t, &else_t)
+            }
+            While(cond, body) => {
+                // anything the loop uses is live across iterations
+                let outer = self.live_after.clone();
+                cond.uses(&mut self.live_after);
+                body.uses(&mut self.live_after);
+                self.type_cond(cond)?;
+                let before = self.env.clone();
+                self.annotate(body)?;
+                // the condition runs again after every iteration
+                self.type_cond(cond)?;
+                self.live_after = outer;
+                for (x, slot) in &self.env.0 {
+                    if let Some(old) = before.0.get(x) {
+                        if moved_in(&old.tipe, &slot.tipe) {
+                            return Err(Error::MoveInLoop(x.clone()));
+                        }
+                    }
+                }
+                self.env = before.join_loop(&self.env)?;
+                Ok(Type::Unit)
+            }
+            Call(name, args) => {
+                let sig = self
+                    .fns
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| Error::UnknownFn(name.clone()))?;
+                if sig.params.len() != args.len() {
+                    return Err(Error::ArityMismatch(
+                        name.clone(),
+                        sig.params.len(),
+                        args.len(),
+                    ));
+                }
+                // Arguments are held in temporaries until the call returns, so
+                // conflicting borrows between them are caught like any other.
+                // Calls nested in the arguments hold theirs under other names.
+                let lt = self.fresh_lifetime();
+                let mut bound = vec![];
+                let mut temps = vec![];
+                for (arg, (x, param_t)) in args.iter_mut().zip(&sig.params) {
+                    let arg_t = self.annotate(arg)?;
+                    if !self.env.compatible(param_t, &arg_t) {
+                        let span = arg.span().unwrap_or_default();
+                        return Err(Error::IncompatibleTypes(param_t.clone(), arg_t).at(span));
+                    }
+                    let temp = (0..)
+                        .map(|i| format!("#arg{}", i))
+                        .find(|temp| !self.env.0.contains_key(temp))
+                        .unwrap();
+                    self.env.insert(&temp, arg_t.clone(), lt.clone());
+                    temps.push(temp);
+                    bound.push((x.clone(), arg_t));
+                }
+                for temp in temps {
+                    self.env.0.remove(&temp);
+                }
+                match &sig.ret {
+                    Some(ret) => self
+                        .instantiate(ret, &bound)
+                        .ok_or_else(|| Error::UnresolvedReturn(name.clone())),
+                    None if !has_ref(&sig.decl_ret) => Ok(sig.decl_ret.clone()),
+                    None => Err(Error::UnresolvedReturn(name.clone())),
+                }
+            }
+            Variant(name, variant, args) => {
+                let fields = self.fields(name, variant)?;
+                if fields.len() != args.len() {
+                    return Err(Error::ArityMismatch(
+                        format!("{}::{}", name, variant),
+                        fields.len(),
+                        args.len(),
+                    ));
+                }
+                for (arg, field) in args.iter_mut().zip(&fields) {
+                    let arg_t = self.annotate(arg)?;
+                    if !self.env.compatible(field, &arg_t) {
+                        let span = arg.span().unwrap_or_default();
+                        return Err(Error::IncompatibleTypes(field.clone(), arg_t).at(span));
+                    }
+                }
+                Ok(Type::Enum(name.clone()))
+            }
+            Match(scrutinee, arms, lt) => {
+                let lt = lt.clone();
+                self.lifetime_stack.push(lt.clone());
+                // a place is matched where it is, anything else is moved
+                // into a temporary that lives as long as the arms
+                let place = match scrutinee.peel() {
+                    Lval(lv, _) => lv.clone(),
+                    _ => {
+                        let tipe = self.annotate(scrutinee)?;
+                        let temp = format!("#match{}", lt.0);
+                        self.env.insert(&temp, tipe, lt.clone());
+                        crate::utils::Lval::new(&temp, 0)
+                    }
+                };
+                self.check_patterns(&place, arms)?;
+                let base = self.clone();
+                let mut joined: Option<Env> = None;
+                let mut arm_types = vec![];
+                for (pattern, body) in arms.iter_mut() {
+                    let mut arm = base.clone();
+                    arm.errors.clear();
+                    let arm_t = arm.annotate_arm(&place, pattern, body, &lt);
+                    self.errors.append(&mut arm.errors);
+                    arm_types.push(arm_t?);
+                    joined = Some(match joined {
+                        None => arm.env,
+                        Some(env) => env.join(&arm.env)?,
+                    });
+                }
+                self.lifetime_stack.pop();
+                if let Some(env) = joined {
+                    self.env = env;
+                }
+                // the result outlives the arms, and so do its join slots
+                let mut arm_types = arm_types.into_iter();
+                let mut tipe = arm_types.next().unwrap_or(Type::Unit);
+                for arm_t in arm_types {
+                    tipe = self.join_values(&tipe, &arm_t)?;
+                }
+                self.env.drop(lt);
+                Ok(tipe)
+            }
+            Spanned(inner, span) => {
+                let span = *span;
+                self.annotate(inner).map_err(|e| e.at(span))
+            }
+        }
+    }
+
+    /// Check that `arms` can match the value at `place`, and cover every
+    /// variant of its enum. Reading the variant is like copying the place.
+    fn check_patterns(&self, place: &Lval, arms: &[(Pattern, Expr)]) -> TypeResult<()> {
+        let slot = self.env.type_lval(place)?;
+        if has_undef(&slot.tipe) {
+            return Err(Error::MovedOut(place.clone()));
+        }
+        for (tgt, mutbl) in self.env.0.values().flat_map(|s| loans(&s.tipe)) {
+            if mutbl && self.env.overlaps(tgt, place, false) {
+                return Err(Error::CopyAfterMutBorrow(place.clone())
+                    .label(tgt.span, "mutable borrow occurs here"));
+            }
+        }
+        let mut covered = vec![];
+        for (pattern, _) in arms {
+            let Pattern::Variant(name, variant, bindings) = pattern else {
+                return Ok(());
+            };
+            match &slot.tipe {
+                Type::Enum(e) | Type::Variant(e, _, _) if e == name => {}
+                other => {
+                    return Err(Error::IncompatibleTypes(
+                        Type::Enum(name.clone()),
+                        other.clone(),
+                    ))
+                }
+            }
+            let fields = self.fields(name, variant)?;
+            if fields.len() != bindings.len() {
+                return Err(Error::ArityMismatch(
+                    format!("{}::{}", name, variant),
+                    fields.len(),
+                    bindings.len(),
+                ));
+            }
+            covered.push(variant);
+        }
+        if let Type::Enum(name) | Type::Variant(name, _, _) = &slot.tipe {
+            let missing: Vec<Ident> = self
+                .variants(name)?
+                .iter()
+                .map(|(v, _)| v)
+                .filter(|v| !covered.contains(v))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                return Err(Error::NonExhaustive(name.clone(), missing));
+            }
         }
+        Ok(())
     }
 
-    pub fn type_stmt(&mut self, stmt: &mut Stmt) -> TypeResult<()> {
+    /// Check a match arm, run in a context of its own. Inside the arm the
+    /// matched place is known to hold the pattern's variant, so its fields
+    /// are places of their own, `x.0` and so on, which bindings move out of
+    /// or borrow. Past a reference that is the place it points at, and the
+    /// arm holds a borrow of the matched place while it has borrowing
+    /// bindings.
+    fn annotate_arm(
+        &mut self,
+        place: &Lval,
+        pattern: &mut Pattern,
+        body: &mut Expr,
+        lt: &Lifetime,
+    ) -> TypeResult<Type> {
+        let canon = self.env.canonical(place);
+        let behind_ref = canon != *place;
+        // fields behind a box have no name
+        let refined = canon.derefs == 0;
+        let before = self.env.type_lval(&canon)?.tipe;
+        if let Pattern::Variant(name, variant, bindings) = pattern {
+            let fields = self.fields(name, variant)?;
+            if refined {
+                let slot = self.env.0.get_mut(&canon.ident).unwrap();
+                if let Some(t) = project_mut(&mut slot.tipe, &canon.fields) {
+                    *t = Type::Variant(name.clone(), variant.clone(), fields.clone());
+                }
+            }
+            let mut borrowed = None;
+            for (i, binding) in bindings.iter_mut().enumerate() {
+                let mut field = canon.clone();
+                field.fields.push(i);
+                let (x, tipe) = match binding {
+                    Binding::Ignore => continue,
+                    Binding::Move(x, copyable) => {
+                        *copyable = fields[i].is_copy();
+                        if !*copyable && behind_ref {
+                            return Err(Error::MoveBehindRef(place.clone()));
+                        }
+                        if !*copyable && !refined {
+                            return Err(Error::BindingBehindBox(place.clone()));
+                        }
+                        if refined && !behind_ref {
+                            self.annotate(&mut Expr::Lval(field, false))?;
+                        }
+                        (x, fields[i].clone())
+                    }
+                    Binding::Ref(x, is_mut) => {
+                        if !refined {
+                            return Err(Error::BindingBehindBox(place.clone()));
+                        }
+                        let borrow = if behind_ref {
+                            place.clone()
+                        } else {
+                            field.clone()
+                        };
+                        self.annotate(&mut Expr::Borrow(borrow, *is_mut))?;
+                        if behind_ref {
+                            borrowed = Some(borrowed.unwrap_or(false) || *is_mut);
+                        }
+                        (x, Type::Ref(field, *is_mut))
+                    }
+                };
+                if self.env.0.contains_key(x.as_str()) {
+                    return Err(Error::Shadowing(x.clone()));
+                }
+                self.env.insert(x, tipe, lt.clone());
+            }
+            if let Some(is_mut) = borrowed {
+                let hold = format!("#arm{}", lt.0);
+                self.env
+                    .insert(&hold, Type::Ref(place.clone(), is_mut), lt.clone());
+            }
+        }
+        let tipe = self.annotate(body)?;
+        // outside the arm the variant is unknown again, unless an enclosing
+        // arm for the same place knows it
+        if refined && !matches!(before, Type::Variant(..)) {
+            let slot = self.env.0.get_mut(&canon.ident).unwrap();
+            match project_mut(&mut slot.tipe, &canon.fields) {
+                Some(t) => *t = forget_variants(t),
+                None => slot.tipe = forget_variants(&slot.tipe),
+            }
+        }
+        self.lifetime_stack.pop();
+        self.env.drop(lt.clone());
+        let tipe = forget_variants(&tipe);
+        // nothing may keep borrowing the fields or the bindings
+        let dangling = self
+            .env
+            .0
+            .values()
+            .flat_map(|s| loans(&s.tipe))
+            .any(|(tgt, _)| self.env.type_lval(tgt).is_err());
+        if dangling || !self.well_formed(&tipe, self.fresh_lifetime()) {
+            return Err(Error::LifetimeTooShort(body.tail().clone()));
+        }
+        Ok(tipe)
+    }
+
+    fn type_cond(&mut self, cond: &mut Expr) -> TypeResult<()> {
+        match self.annotate(cond)? {
+            Type::Bool => Ok(()),
+            other => {
+                let span = cond.span().unwrap_or_default();
+                Err(Error::IncompatibleTypes(Type::Bool, other).at(span))
+            }
+        }
+    }
+
+    fn annotate_stmt(&mut self, stmt: &mut Stmt) -> TypeResult<()> {
         use crate::utils::Expr::Lval;
         match stmt {
             Stmt::LetMut(var, rhs) => {
                 if self.env.0.contains_key(var) {
                     return Err(Error::Shadowing(var.clone()));
                 }
-                let rhs_ty = self.type_expr(rhs)?;
+                let rhs_ty = self.annotate(rhs)?;
                 if let Type::Undefined(_) = rhs_ty {
-                    if let Lval(ref lv, _) = *rhs {
+                    if let Lval(lv, _) = rhs.peel() {
                         return Err(Error::MovedOut(lv.clone()));
                     }
                 }
-                self.env.insert(var, rhs_ty, self.fresh_lifetime());
+                let lt = self.fresh_lifetime();
+                if !self.well_formed(&rhs_ty, lt.clone()) {
+                    return Err(Error::LifetimeTooShort(rhs.clone()));
+                }
+                self.env.insert(var, rhs_ty, lt);
                 Ok(())
             }
             Stmt::Assign(lv, expr) => {
-                let rhs_ty = self.type_expr(expr)?;
+                let rhs_ty = self.annotate(expr)?;
+                let lt = self.env.lifetime_of(lv)?;
+                if !self.well_formed(&rhs_ty, lt) {
+                    return Err(Error::LifetimeTooShort(expr.clone()));
+                }
                 self.env.write(lv, rhs_ty)?;
                 Ok(())
             }
             Stmt::Expr(expr) => {
-                let _ = self.type_expr(expr)?;
+                let _ = self.annotate(expr)?;
                 Ok(())
             }
+            // checked when the enclosing block hoists it
+            Stmt::Fn(_) | Stmt::Enum(_) => Ok(()),
+            Stmt::Spanned(inner, span) => {
+                let span = *span;
+                self.annotate_stmt(inner).map_err(|e| e.at(span))
+            }
+        }
+    }
+
+    /// Elaborate `expr`, recovering from failed statements to report as many
+    /// errors as possible, sorted by position.
+    pub fn check_all(&mut self, expr: &Expr) -> Result<Typed, Vec<Error>> {
+        self.recover = true;
+        let result = self.elaborate(expr);
+        self.recover = false;
+        let mut errors = std::mem::take(&mut self.errors);
+        match result {
+            Ok(t) if errors.is_empty() => return Ok(t),
+            Ok(_) => {}
+            Err(e) if self.is_cascade(&e) => {}
+            Err(e) => errors.push(e),
+        }
+        // errors without a position go last
+        errors.sort_by_key(|e| (e.span().is_none(), e.span().map(|s| s.start)));
+        // a loop condition is checked twice, and may fail the same way twice
+        errors.dedup_by(|a, b| {
+            a.span().map(|s| s.start) == b.span().map(|s| s.start) && a.to_string() == b.to_string()
+        });
+        Err(errors)
+    }
+
+    /// Record `err` when recovering, otherwise return it.
+    fn fail(&mut self, err: Error) -> TypeResult<()> {
+        if !self.recover {
+            return Err(err);
+        }
+        self.errors.push(err);
+        Ok(())
+    }
+
+    /// Whether `err` only follows from a function that already failed to
+    /// check, such as a call that can't tell what it returns.
+    fn blames_poisoned_fn(&self, err: &Error) -> bool {
+        err.callee().is_some_and(|f| self.poisoned_fns.contains(f))
+    }
+
+    /// Whether `err` was already reported in another form: it is about a
+    /// poisoned binding, or blames a poisoned function.
+    fn is_cascade(&self, err: &Error) -> bool {
+        err.subject().is_some_and(|x| self.poisoned.contains(x)) || self.blames_poisoned_fn(err)
+    }
+
+    /// Like `annotate_stmt` once the borrows not in `live` have ended, but when
+    /// recovering a failed statement is recorded and its effects undone. A
+    /// binding it failed to declare is poisoned as moved-out, and errors about
+    /// poisoned bindings or functions are not reported again.
+    fn annotate_stmt_or_recover(
+        &mut self,
+        stmt: &mut Stmt,
+        live: &HashSet<Ident>,
+    ) -> TypeResult<()> {
+        if !self.recover {
+            self.kill_dead(live);
+            return self.annotate_stmt(stmt);
+        }
+        let (env, stack) = (self.env.clone(), self.lifetime_stack.clone());
+        self.kill_dead(live);
+        let Err(err) = self.annotate_stmt(stmt) else {
+            return Ok(());
+        };
+        // a variable the statement would have overwritten keeps its old
+        // value, so the borrows it holds don't end here after all
+        self.env = env;
+        self.lifetime_stack = stack;
+        let mut live = live.clone();
+        live.extend(stmt.overwrites().cloned());
+        self.kill_dead(&live);
+
+        let target = match stmt.peel() {
+            Stmt::LetMut(x, _) => Some(x),
+            Stmt::Assign(lv, _) => Some(&lv.ident),
+            _ => None,
+        };
+        let cascade = self.is_cascade(&err) || target.is_some_and(|x| self.poisoned.contains(x));
+        if let Stmt::LetMut(x, _) = stmt.peel() {
+            if !self.env.0.contains_key(x) {
+                let lt = self.fresh_lifetime();
+                self.env.insert(x, Type::undefined(Type::Unit), lt);
+                self.poisoned.insert(x.clone());
+            }
+        }
+        if !cascade {
+            self.errors.push(err.at(stmt.span().unwrap_or_default()));
+        }
+        Ok(())
+    }
+
+    /// End the borrows held by references that are never used again, as with
+    /// Rust's non-lexical lifetimes. A reference is live if it is in `live`
+    /// or a live reference points at it; dead ones are marked moved-out, so
+    /// they no longer conflict with other uses of the places they borrow.
+    fn kill_dead(&mut self, live: &HashSet<Ident>) {
+        let mut live = live.clone();
+        loop {
+            let mut grew = false;
+            for (x, slot) in &self.env.0 {
+                if live.contains(x) {
+                    for tgt in refs_in(&slot.tipe) {
+                        grew |= live.insert(tgt.ident.clone());
+                    }
+                }
+            }
+            if !grew {
+                break;
+            }
+        }
+        for (x, slot) in self.env.0.iter_mut() {
+            // placeholders and argument temporaries are never named in
+            // the source, so they are not ours to end; join slots end with
+            // whatever points through them
+            if live.contains(x) || (x.contains('#') && !is_join_slot(x)) {
+                continue;
+            }
+            if has_ref(&slot.tipe) && !matches!(slot.tipe, Type::Undefined(_)) {
+                slot.tipe = Type::undefined(slot.tipe.clone());
+            }
+        }
+    }
+
+    /// The type of a value of type `t1` on one path and `t2` on another.
+    /// A reference to different places on the two paths points through a
+    /// join slot of the innermost block, see `Env::join`.
+    fn join_values(&mut self, t1: &Type, t2: &Type) -> TypeResult<Type> {
+        let mut held = vec![];
+        let tipe = join_by(t1, t2, &mut |a, b, is_mut| {
+            let referents = (self.env.type_lval(a)?.tipe, self.env.type_lval(b)?.tipe);
+            if !self.env.compatible(&referents.0, &referents.1) {
+                return Err(Error::IncompatibleTypes(
+                    Type::Ref(a.clone(), is_mut),
+                    Type::Ref(b.clone(), is_mut),
+                ));
+            }
+            let place = fresh_join_slot("", |y| {
+                self.env.0.contains_key(y) || held.iter().any(|(z, _)| z == y)
+            });
+            held.push((place.clone(), join_loans(a, b, is_mut)));
+            Ok(Type::Ref(Lval::field(&place, &[0], 1), is_mut))
+        })?;
+        let lifetime = self.fresh_lifetime();
+        for (x, t) in held {
+            self.env.insert(&x, t, lifetime.clone());
         }
+        Ok(tipe)
     }
 
+    /// Lifetime given to variables declared in the innermost block.
     pub fn fresh_lifetime(&self) -> Lifetime {
-        Lifetime(self.lifetime_stack.len())
+        self.lifetime_stack
+            .last()
+            .cloned()
+            .unwrap_or_else(Lifetime::global)
+    }
+}
+
+/// The type of the field at `fields` in `t`. Fields of a moved-out tuple
+/// are moved out too.
+fn project(t: &Type, fields: &[usize]) -> Option<Type> {
+    let Some((&f, rest)) = fields.split_first() else {
+        return Some(t.clone());
+    };
+    match t {
+        Type::Tuple(ts) | Type::Variant(_, _, ts) => project(ts.get(f)?, rest),
+        Type::Undefined(inner) => match project(inner, fields)? {
+            t @ Type::Undefined(_) => Some(t),
+            t => Some(Type::undefined(t)),
+        },
+        _ => None,
+    }
+}
+
+/// Like `project`, for updating the field in place. Fails on moved-out
+/// tuples, which can't be split.
+fn project_mut<'a>(t: &'a mut Type, fields: &[usize]) -> Option<&'a mut Type> {
+    let mut t = t;
+    for &f in fields {
+        match t {
+            Type::Tuple(ts) | Type::Variant(_, _, ts) => t = ts.get_mut(f)?,
+            _ => return None,
+        }
+    }
+    Some(t)
+}
+
+fn strip_undefined(t: &Type) -> &Type {
+    match t {
+        Type::Undefined(inner) => strip_undefined(inner),
+        _ => t,
+    }
+}
+
+/// The type of a place that has type `t1` on one path and `t2` on another.
+/// A reference must point at the same place on both paths.
+fn join(t1: &Type, t2: &Type) -> TypeResult<Type> {
+    join_by(t1, t2, &mut |a, b, is_mut| {
+        Err(Error::IncompatibleTypes(
+            Type::Ref(a.clone(), is_mut),
+            Type::Ref(b.clone(), is_mut),
+        ))
+    })
+}
+
+/// Like `join`, with `split` giving the type of a reference to the place
+/// `a` on one path and `b` on the other.
+fn join_by(
+    t1: &Type,
+    t2: &Type,
+    split: &mut dyn FnMut(&Lval, &Lval, Mutable) -> TypeResult<Type>,
+) -> TypeResult<Type> {
+    match (t1, t2) {
+        (Type::Undefined(_), _) | (_, Type::Undefined(_)) => {
+            // a moved-out reference borrows nothing, whatever it pointed at
+            let (a, b) = (strip_undefined(t1), strip_undefined(t2));
+            let tipe = join_by(a, b, &mut |a, _, is_mut| Ok(Type::Ref(a.clone(), is_mut)))?;
+            Ok(Type::undefined(tipe))
+        }
+        (Type::Box(a), Type::Box(b)) => Ok(Type::boxx(join_by(a, b, split)?)),
+        (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => Ok(Type::Tuple(
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| join_by(a, b, split))
+                .collect::<TypeResult<_>>()?,
+        )),
+        (Type::Variant(e, v, a), Type::Variant(f, w, b)) if e == f && v == w => Ok(Type::Variant(
+            e.clone(),
+            v.clone(),
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| join_by(a, b, split))
+                .collect::<TypeResult<_>>()?,
+        )),
+        (Type::Variant(..), _) | (_, Type::Variant(..)) => {
+            join_by(&forget_variants(t1), &forget_variants(t2), split)
+        }
+        (Type::Ref(a, m1), Type::Ref(b, m2)) if a != b && m1 == m2 => split(a, b, *m1),
+        _ if t1 == t2 => Ok(t1.clone()),
+        _ => Err(Error::IncompatibleTypes(t1.clone(), t2.clone())),
+    }
+}
+
+/// The type of a slot made by joining a reference to `a` with one to `b`.
+/// It holds both loans for as long as something points through it, at
+/// its first field.
+fn join_loans(a: &Lval, b: &Lval, is_mut: Mutable) -> Type {
+    Type::Tuple(vec![
+        Type::Ref(a.clone(), is_mut),
+        Type::Ref(b.clone(), is_mut),
+    ])
+}
+
+/// A name for a new join slot of `owner`'s, `owner#or0` or the first of
+/// `owner#or1`, ... that is not `taken`.
+fn fresh_join_slot(owner: &str, taken: impl Fn(&str) -> bool) -> Ident {
+    (0..)
+        .map(|n| format!("{}#or{}", owner, n))
+        .find(|x| !taken(x))
+        .unwrap()
+}
+
+fn is_join_slot(x: &str) -> bool {
+    x.contains("#or")
+}
+
+/// Is some part of `before` that was initialized moved out in `after`?
+fn moved_in(before: &Type, after: &Type) -> bool {
+    match (before, after) {
+        (Type::Undefined(_), _) => false,
+        (_, Type::Undefined(_)) => true,
+        (Type::Box(a), Type::Box(b)) => moved_in(a, b),
+        (Type::Tuple(a), Type::Tuple(b)) | (Type::Variant(_, _, a), Type::Variant(_, _, b)) => {
+            a.iter().zip(b).any(|(a, b)| moved_in(a, b))
+        }
+        _ => false,
+    }
+}
+
+/// Is some part of a value of type `t` moved out?
+fn has_undef(t: &Type) -> bool {
+    match t {
+        Type::Undefined(_) => true,
+        Type::Box(inner) => has_undef(inner),
+        Type::Tuple(ts) | Type::Variant(_, _, ts) => ts.iter().any(has_undef),
+        _ => false,
+    }
+}
+
+/// `t` outside the match arm that knew its variants: they are plain enums
+/// again, moved out as a whole if any of their fields were.
+fn forget_variants(t: &Type) -> Type {
+    match t {
+        Type::Variant(name, _, ts) if ts.iter().any(has_undef) => {
+            Type::undefined(Type::Enum(name.clone()))
+        }
+        Type::Variant(name, _, _) => Type::Enum(name.clone()),
+        Type::Box(inner) => Type::boxx(forget_variants(inner)),
+        Type::Undefined(inner) => Type::undefined(strip_undefined(&forget_variants(inner)).clone()),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(forget_variants).collect()),
+        _ => t.clone(),
+    }
+}
+
+/// The borrows held by a value of type `t`, through boxes and tuples.
+/// Moved-out parts hold nothing.
+fn loans(t: &Type) -> Vec<(&Lval, Mutable)> {
+    match t {
+        Type::Unit | Type::Int | Type::Bool | Type::Enum(_) | Type::Undefined(_) => vec![],
+        Type::Box(inner) => loans(inner),
+        Type::Ref(lv, is_mut) => vec![(lv, *is_mut)],
+        Type::Tuple(ts) | Type::Variant(_, _, ts) => ts.iter().flat_map(loans).collect(),
+    }
+}
+
+/// The places the references in `t` point at.
+fn refs_in(t: &Type) -> Vec<&Lval> {
+    match t {
+        Type::Unit | Type::Int | Type::Bool | Type::Enum(_) => vec![],
+        Type::Box(inner) | Type::Undefined(inner) => refs_in(inner),
+        Type::Ref(lv, _) => vec![lv],
+        Type::Tuple(ts) | Type::Variant(_, _, ts) => ts.iter().flat_map(refs_in).collect(),
+    }
+}
+
+fn has_ref(t: &Type) -> bool {
+    match t {
+        Type::Unit | Type::Int | Type::Bool | Type::Enum(_) => false,
+        Type::Box(inner) | Type::Undefined(inner) => has_ref(inner),
+        Type::Ref(_, _) => true,
+        Type::Tuple(ts) | Type::Variant(_, _, ts) => ts.iter().any(has_ref),
     }
 }
 
@@ -414,4 +2063,63 @@ impl Expr {
             *c = true;
         }
     }
+
+    /// Add the variables `self` reads, writes or borrows to `vars`.
+    pub fn uses(&self, vars: &mut HashSet<Ident>) {
+        match self {
+            Expr::Unit | Expr::Int(_) | Expr::Bool(_) => {}
+            Expr::Lval(lv, _) | Expr::Borrow(lv, _) => {
+                vars.insert(lv.ident.clone());
+            }
+            Expr::Box(e) | Expr::Spanned(e, _) => e.uses(vars),
+            Expr::Block(stmts, e, _) => {
+                stmts.iter().for_each(|s| s.uses(vars));
+                e.uses(vars);
+            }
+            Expr::Call(_, args) | Expr::Tuple(args) | Expr::Variant(_, _, args) => {
+                args.iter().for_each(|a| a.uses(vars))
+            }
+            Expr::Match(e, arms, _) => {
+                e.uses(vars);
+                arms.iter().for_each(|(_, body)| body.uses(vars));
+            }
+            Expr::BinOp(_, a, b) | Expr::While(a, b) => {
+                a.uses(vars);
+                b.uses(vars);
+            }
+            Expr::If(c, a, b) => {
+                c.uses(vars);
+                a.uses(vars);
+                b.uses(vars);
+            }
+        }
+    }
+}
+
+impl Stmt {
+    /// Add the variables `self` reads, writes or borrows to `vars`.
+    /// Function bodies can't see the enclosing block, so they use nothing.
+    pub fn uses(&self, vars: &mut HashSet<Ident>) {
+        match self {
+            Stmt::Assign(lv, e) => {
+                if self.overwrites().is_none() {
+                    vars.insert(lv.ident.clone());
+                }
+                e.uses(vars);
+            }
+            Stmt::LetMut(_, e) | Stmt::Expr(e) => e.uses(vars),
+            Stmt::Fn(_) | Stmt::Enum(_) => {}
+            Stmt::Spanned(s, _) => s.uses(vars),
+        }
+    }
+
+    /// The variable `self` assigns as a whole, whose old value it never
+    /// reads, so that value is dead just before.
+    pub fn overwrites(&self) -> Option<&Ident> {
+        match self {
+            Stmt::Assign(lv, _) if lv.derefs == 0 && lv.fields.is_empty() => Some(&lv.ident),
+            Stmt::Spanned(s, _) => s.overwrites(),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,717 @@
+use crate::parser::parse_program;
+use crate::types::{Context, Error, Type, TypeResult};
+use crate::utils::{Expr, Lval};
+
+#[allow(clippy::result_large_err)]
+fn check(src: &str) -> TypeResult<()> {
+    let e = parse_program(src).unwrap();
+    Context::default().type_expr(&e).map(|_| ())
+}
+
+#[test]
+fn borrow_in_block() {
+    assert_eq!(
+        check("let mut x = 1; { let mut y = &x; let mut z = &mut *y; }"),
+        Err(Error::MutBorrowBehindImmRef(Lval::new("y", 1)))
+    );
+    assert_eq!(
+        check("let mut x = box 1; { let mut y = &mut x; **y = 2; }"),
+        Ok(())
+    );
+}
+
+#[test]
+fn borrow_outlives_block() {
+    assert_eq!(
+        check("let mut r = { let mut z = 1; &z };"),
+        Err(Error::LifetimeTooShort(Expr::Borrow(
+            Lval::new("z", 0),
+            false
+        )))
+    );
+}
+
+#[test]
+fn assign_dangling() {
+    assert_eq!(
+        check("let mut a = 0; let mut r = &mut a; { let mut z = 1; r = &mut z; }"),
+        Err(Error::LifetimeTooShort(Expr::Borrow(
+            Lval::new("z", 0),
+            true
+        )))
+    );
+    assert_eq!(
+        check("let mut a = 0; { let mut b = 1; let mut r = &mut a; r = &mut b; }"),
+        Ok(())
+    );
+}
+
+#[test]
+fn call_moves_and_copies() {
+    assert_eq!(
+        check("fn f(x: box int, n: int) {} let mut a = box 1; let mut n = 2; f(a, n); n"),
+        Ok(())
+    );
+    assert_eq!(
+        check("fn f(x: box int) {} let mut a = box 1; f(a); a"),
+        Err(Error::MovedOut(Lval::new("a", 0)))
+    );
+    assert_eq!(
+        check("fn f(x: box int) {} f(3)"),
+        Err(Error::IncompatibleTypes(Type::boxx(Type::Int), Type::Int))
+    );
+    assert_eq!(
+        check("fn f(x: int) {} f()"),
+        Err(Error::ArityMismatch(String::from("f"), 1, 0))
+    );
+}
+
+#[test]
+fn call_conflicting_args() {
+    assert_eq!(
+        check("fn f(x: &mut int, y: &int) {} let mut a = 1; f(&mut a, &a);"),
+        Err(Error::BorrowAfterMutBorrow(Lval::new("a", 0)))
+    );
+    assert_eq!(
+        check("fn f(x: &mut int, y: &mut int) {} let mut a = 1; f(&mut a, &mut a);"),
+        Err(Error::MutBorrowAfterMutBorrow(Lval::new("a", 0)))
+    );
+    assert_eq!(
+        check("let mut a = 1; let mut r = &mut a; let mut s = &mut a; *r"),
+        Err(Error::MutBorrowAfterMutBorrow(Lval::new("a", 0)))
+    );
+    // a call in an argument doesn't end the borrows of the ones before
+    assert_eq!(
+        check(
+            "fn f(x: &int, y: int) {}
+                 fn g(x: int, y: &mut int) -> int { 0 }
+                 let mut a = 1;
+                 f(&a, g(2, &mut a));"
+        ),
+        Err(Error::MutBorrowAfterBorrow(Lval::new("a", 0)))
+    );
+}
+
+#[test]
+fn call_returns_borrow() {
+    let prog = "fn first(a: &box int, b: &int) -> &int { &**a }
+                    let mut x = box 1;
+                    let mut y = 2;
+                    let mut r = first(&x, &y);";
+    assert_eq!(check(prog), Ok(()));
+    assert_eq!(
+        check(&format!("{} let mut m = &mut x; r;", prog)),
+        Err(Error::MutBorrowAfterBorrow(Lval::new("x", 0)))
+    );
+    assert_eq!(check(&format!("{} let mut m = &mut y;", prog)), Ok(()));
+}
+
+#[test]
+fn cannot_return_local() {
+    assert_eq!(
+        check("fn f(x: int) -> &int { &x }"),
+        Err(Error::LifetimeTooShort(Expr::Borrow(
+            Lval::new("x", 0),
+            false
+        )))
+    );
+    assert_eq!(
+        check("fn f(x: &int) -> &int { let mut y = 1; &y }"),
+        Err(Error::LifetimeTooShort(Expr::Borrow(
+            Lval::new("y", 0),
+            false
+        )))
+    );
+}
+
+#[test]
+fn arith_copies_ints() {
+    assert_eq!(
+        check("let mut x = box 2; let mut y = *x + *x * 3; x"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut x = box 2; let mut y = x + 1;"),
+        Err(Error::IncompatibleTypes(Type::Int, Type::boxx(Type::Int)))
+    );
+    assert_eq!(
+        check("let mut x = 1; let mut r = &mut x; let mut y = x < 2; *r = 3;"),
+        Err(Error::CopyAfterMutBorrow(Lval::new("x", 0)))
+    );
+}
+
+#[test]
+fn if_joins_moves() {
+    assert_eq!(
+        check("let mut x = box 1; let mut y = box 2; if true { y = x; } else { } x"),
+        Err(Error::MovedOut(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check(
+            "let mut x = box 1; if 1 < 2 { let mut y = x; } else { let mut z = x; } x = box 3; x"
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check("if true { 1 } else { false }"),
+        Err(Error::IncompatibleTypes(Type::Int, Type::Bool))
+    );
+    assert_eq!(
+        check("if 1 { }"),
+        Err(Error::IncompatibleTypes(Type::Bool, Type::Int))
+    );
+}
+
+#[test]
+fn if_joins_borrows_of_different_places() {
+    assert_eq!(
+        check("let mut x = 1; let mut y = 2; let mut r = &x; if y < 3 { r = &y; } else { } *r"),
+        Ok(())
+    );
+    // both places stay borrowed until the reference is dead
+    let rebound =
+        "let mut a = 1; let mut b = 2; let mut r = &mut a; if true { r = &mut b; } else {} ";
+    for (place, rest) in [("a", "a = 3; *r"), ("b", "b = 3; *r")] {
+        assert_eq!(
+            check(&format!("{}{}", rebound, rest)),
+            Err(Error::AssignAfterBorrow(Lval::new(place, 0)))
+        );
+    }
+    assert_eq!(check(&format!("{}*r = 5; a = b + 1; a", rebound)), Ok(()));
+    // so do they through a copy, or when the branches are values
+    assert_eq!(
+        check("let mut x = 1; let mut y = 2; let mut r = if x < y { &x } else { &y }; let mut s = r; y = 3; *s"),
+        Err(Error::AssignAfterBorrow(Lval::new("y", 0)))
+    );
+    assert_eq!(
+        check("let mut x = 1; let mut y = 2; let mut z = 3; let mut r = &x; if x < y { if y < z { r = &y; } else { r = &z; } } else {} let mut s = &mut z; *r"),
+        Err(Error::MutBorrowAfterBorrow(Lval::new("z", 0)))
+    );
+    // the places must hold values of the same type
+    assert_eq!(
+        check("let mut x = 1; let mut y = true; let mut r = &x; if true { r = &y; } else {} *r"),
+        Err(Error::IncompatibleTypes(
+            Type::Ref(Lval::new("y", 0), false),
+            Type::Ref(Lval::new("x", 0), false)
+        ))
+    );
+}
+
+#[test]
+fn while_rejects_moves() {
+    assert_eq!(
+        check("let mut x = box 1; while true { let mut y = x; }"),
+        Err(Error::MoveInLoop(String::from("x")))
+    );
+    assert_eq!(
+        check("let mut x = box 1; while true { let mut y = x; x = box 2; }"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut i = 0; let mut x = box 1; while i < 3 { *x = *x + i; i = i + 1; } x"),
+        Ok(())
+    );
+}
+
+#[test]
+fn error_locations() {
+    let err = check("let mut x = box 1;\nlet mut r = &x;\nlet mut m = &mut x;\nr;").unwrap_err();
+    assert_eq!(err, Error::MutBorrowAfterBorrow(Lval::new("x", 0)));
+    let span = err.span().unwrap();
+    assert_eq!((span.start.line, span.start.col), (3, 13));
+    let labels: Vec<_> = err
+        .labels()
+        .iter()
+        .map(|(s, msg)| (s.start.line, s.start.col, msg.as_str()))
+        .collect();
+    assert_eq!(labels, vec![(2, 13, "immutable borrow occurs here")]);
+
+    let err = check("let mut x = 1;\nlet mut y = x + true;").unwrap_err();
+    let span = err.span().unwrap();
+    assert_eq!((span.start.line, span.start.col), (2, 17));
+}
+
+fn check_all(src: &str) -> Result<Type, Vec<Error>> {
+    let e = parse_program(src).unwrap();
+    Context::default().check_all(&e).map(|typed| typed.tipe)
+}
+
+#[test]
+fn collect_errors() {
+    let errors = check_all(
+        "let mut x = 1 + true;\n\
+             let mut y = box 1; let mut r = &y;\n\
+             let mut z = x;\n\
+             y = box 2;\n\
+             let mut w = q; r;",
+    )
+    .unwrap_err();
+    // `x` is poisoned, so `let mut z = x;` doesn't report it again
+    assert_eq!(
+        errors,
+        vec![
+            Error::IncompatibleTypes(Type::Int, Type::Bool),
+            Error::AssignAfterBorrow(Lval::new("y", 0)),
+            Error::UnknownVar("q".to_string()),
+        ]
+    );
+    let lines: Vec<_> = errors
+        .iter()
+        .map(|e| e.span().unwrap().start.line)
+        .collect();
+    assert_eq!(lines, vec![1, 4, 5]);
+}
+
+#[test]
+fn collect_errors_skip_poisoned_final_expr() {
+    // the block's final expression is not a statement, but its uses of
+    // poisoned bindings are still not reported again
+    assert_eq!(
+        check_all("let mut y = z; y"),
+        Err(vec![Error::UnknownVar("z".to_string())])
+    );
+    assert_eq!(
+        check_all("let mut y = 1; let mut s = &w; *s"),
+        Err(vec![Error::UnknownVar("w".to_string())])
+    );
+}
+
+#[test]
+fn collect_errors_in_branches_and_fns() {
+    let errors = check_all(
+        "fn f() -> int { true }\n\
+             if true { let mut a = b; } else { let mut c = d; }\n\
+             f()",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            Error::IncompatibleTypes(Type::Int, Type::Bool),
+            Error::UnknownVar("b".to_string()),
+            Error::UnknownVar("d".to_string()),
+        ]
+    );
+    assert_eq!(check_all("let mut x = 1; x"), Ok(Type::Int));
+}
+
+#[test]
+fn collect_errors_skip_calls_to_failed_fns() {
+    let errors = check_all(
+        "fn f(x: &int) -> &int { let mut y = 1; &y }\n\
+             fn g(x: Foo) -> int { 0 }\n\
+             let mut a = 1;\n\
+             let mut r = f(&a);\n\
+             let mut s = r;\n\
+             g(1);\n\
+             f(&a)",
+    )
+    .unwrap_err();
+    // neither what `f` returns nor `g` itself is known, which the errors
+    // in their definitions already explain
+    assert_eq!(
+        errors,
+        vec![
+            Error::LifetimeTooShort(Expr::Borrow(Lval::new("y", 0), false)),
+            Error::UnknownEnum(String::from("Foo")),
+        ]
+    );
+    let lines: Vec<_> = errors
+        .iter()
+        .map(|e| e.span().unwrap().start.line)
+        .collect();
+    assert_eq!(lines, vec![1, 2]);
+}
+
+#[test]
+fn borrows_end_at_last_use() {
+    // `r` is never used after `z` takes it, and `z` is never used at all
+    assert_eq!(
+        check("let mut x = box 1; let mut r = &x; let mut z = r; let mut m = &mut x; **m = 2;"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut x = 1; let mut r = &mut x; *r = 2; x = 3; let mut y = x;"),
+        Ok(())
+    );
+    // a later use keeps the borrow alive
+    assert_eq!(
+        check("let mut x = 1; let mut r = &mut x; x = 3; *r = 2;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    // ... as does a use in an enclosing block or a later loop iteration
+    assert_eq!(
+        check("let mut x = 1; let mut r = &x; { x = 2; } r;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check(
+            "let mut x = 1; let mut y = 1; let mut r = &mut y;\n\
+                 while x < 3 { let mut z = x < *r; r = &mut x; }"
+        ),
+        Err(Error::CopyAfterMutBorrow(Lval::new("x", 0)))
+    );
+    // a live reference keeps what it points at alive
+    assert_eq!(
+        check("let mut x = 1; let mut r = &mut x; let mut rr = &mut r; x = 2; **rr = 3;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+}
+
+#[test]
+fn overwrites_end_borrows() {
+    // overwriting `r` doesn't read the borrow of `x` it held
+    assert_eq!(
+        check("let mut x = 1; let mut y = 1; let mut r = &mut x; r = &mut y; x = 2; *r = 3;"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut x = 1; let mut y = 1; let mut r = &x; r = &y; x = 2; r;"),
+        Ok(())
+    );
+    // ... but assigning through it or to one of its fields does
+    assert_eq!(
+        check("let mut x = 1; let mut y = 1; let mut r = &mut x; x = 2; *r = 3;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check("let mut x = 1; let mut t = (&x, 0); x = 2; t.1 = 3;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    // ... as does a right-hand side that reads it
+    assert_eq!(
+        check("let mut x = 1; let mut r = &x; r = r; x = 2; r;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+}
+
+#[test]
+fn failed_overwrite_keeps_old_value() {
+    // `r = &y;` fails, so `r` still holds its borrow of `x`
+    assert_eq!(
+        check_all("let mut x = box 1; let mut r = &x; r = &y; **r"),
+        Err(vec![Error::UnknownVar("y".to_string())])
+    );
+    assert_eq!(
+        check_all("let mut x = 1; let mut r = &x; r = &y; x = 2; *r"),
+        Err(vec![
+            Error::UnknownVar("y".to_string()),
+            Error::AssignAfterBorrow(Lval::new("x", 0)),
+        ])
+    );
+}
+
+#[test]
+fn borrows_on_paths() {
+    // overwriting `r` leaves the reborrowed `*r` (that is, `a`) alone
+    let prog = "let mut a = 1; let mut b = 2;
+                    let mut r = &mut a; let mut s = &mut *r;
+                    r = &mut b; *s = 3; *r = 4;";
+    assert_eq!(check(prog), Ok(()));
+    // ... but `a` is still borrowed through `s`
+    assert_eq!(
+        check("let mut a = 1; let mut b = 2; let mut r = &mut a; let mut s = &*r; r = &mut b; a = 5; s;"),
+        Err(Error::AssignAfterBorrow(Lval::new("a", 0)))
+    );
+    // overwriting a box drops what a borrow points into
+    assert_eq!(
+        check("let mut x = box 1; let mut r = &*x; x = box 2; r;"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check("let mut x = box 1; let mut r = &mut *x; let mut y = *x; r;"),
+        Err(Error::CopyAfterMutBorrow(Lval::new("x", 1)))
+    );
+    // reborrowing through the place being assigned borrows its target
+    assert_eq!(
+        check("let mut a = 1; let mut r = &mut a; r = &mut *r; *r"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut a = 1; let mut r = &mut a; r = &mut *r; a = 2; *r"),
+        Err(Error::AssignAfterBorrow(Lval::new("a", 0)))
+    );
+}
+
+#[test]
+fn tuples_move_by_field() {
+    // moving one field leaves the other usable
+    assert_eq!(
+        check("let mut t = (box 1, box 2); let mut a = t.0; let mut b = *t.1; t.0 = box 3; t"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut t = (box 1, 2); let mut a = t.0; t"),
+        Err(Error::MovedOut(Lval::new("t", 0)))
+    );
+    // a tuple is copied only if all of its fields are
+    assert_eq!(
+        check("let mut t = (1, (true, ())); let mut u = t; t"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut t = (1, (true, box 1)); let mut u = t; t.0"),
+        Err(Error::MovedOut(Lval::field("t", &[0], 0)))
+    );
+    assert_eq!(
+        check("let mut t = (1, box 2); let mut a = t.1; let mut r = &t;"),
+        Err(Error::MovedOut(Lval::new("t", 0)))
+    );
+    assert_eq!(
+        check("let mut t = (1, 2); t.2"),
+        Err(Error::UnknownField(Lval::field("t", &[2], 0)))
+    );
+}
+
+#[test]
+fn tuples_borrow_by_field() {
+    assert_eq!(
+        check("let mut t = (1, 2); let mut a = &mut t.0; let mut b = &t.1; *a = *b;"),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut t = (1, 2); let mut a = &mut t.0; let mut b = &t; *a = 3;"),
+        Err(Error::BorrowAfterMutBorrow(Lval::new("t", 0)))
+    );
+    // a reference stored in a tuple keeps its target borrowed
+    assert_eq!(
+        check("let mut x = 1; let mut t = (&x, 2); x = 3; t"),
+        Err(Error::AssignAfterBorrow(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check("let mut t = (1, box 2); let mut r = &mut t; *r = t;"),
+        Err(Error::MoveAfterBorrow(Lval::new("t", 0)))
+    );
+}
+
+#[test]
+fn lifetime_annotations() {
+    assert_eq!(
+        check("fn f<'a, 'b: 'a>(x: &'a int, y: &'b int) -> &'a int { y }"),
+        Ok(())
+    );
+    assert_eq!(
+        check("fn f<'a, 'b>(x: &'a int, y: &'b int) -> &'a int { y }"),
+        Err(Error::DoesNotOutlive(
+            String::from("'b"),
+            String::from("'a")
+        ))
+    );
+    // elided lifetimes are unrelated to everything else
+    assert_eq!(
+        check("fn f<'a>(x: &'a int, y: &int) -> &'a int { y }"),
+        Err(Error::DoesNotOutlive(
+            String::from("'_"),
+            String::from("'a")
+        ))
+    );
+    assert_eq!(
+        check("fn f(x: &int) -> &'static int { x }"),
+        Err(Error::DoesNotOutlive(
+            String::from("'_"),
+            String::from("'static")
+        ))
+    );
+    assert_eq!(
+        check("fn f(x: &'a int) {}"),
+        Err(Error::UndeclaredLifetime(String::from("'a")))
+    );
+}
+
+#[test]
+fn lifetime_annotations_on_stores() {
+    // `&mut &'a int` implies `'a` outlives the outer reference
+    assert_eq!(
+        check("fn f<'a>(x: &mut &'a int, y: &'a int) { *x = y; }"),
+        Ok(())
+    );
+    assert_eq!(
+        check("fn f<'a, 'b>(x: &mut &'a int, y: &'b int) { *x = y; }"),
+        Err(Error::LifetimeTooShort(Expr::Lval(
+            Lval::new("y", 0),
+            false
+        )))
+    );
+    // callers see what the body actually returns
+    assert_eq!(
+        check(
+            "fn f<'a, 'b: 'a>(x: &'a int, y: &'b int) -> &'a int { y }
+                 let mut a = 1; let mut b = 2; let mut r = f(&a, &b);
+                 a = 3; *r"
+        ),
+        Ok(())
+    );
+}
+
+#[test]
+fn rebind_shared_refs() {
+    // a variable or field holding a `&` can be pointed elsewhere
+    assert_eq!(
+        check(
+            "fn f<'a>(x: &'a int, y: &'a int) -> &'a int { y }
+                 let mut a = 1; let mut b = 2;
+                 let mut p = &a;
+                 p = f(&a, &b);
+                 *p"
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check("let mut a = 1; let mut b = 2; let mut t = (&a, 0); t.0 = &b; *t.0"),
+        Ok(())
+    );
+    // ... but not written through
+    assert_eq!(
+        check("let mut a = 1; let mut p = &a; *p = 2;"),
+        Err(Error::UpdateBehindImmRef(Lval::new("p", 1)))
+    );
+}
+
+const OPT: &str = "enum Opt { None, Some(box int) } let mut o = Opt::Some(box 1); ";
+
+#[allow(clippy::result_large_err)]
+fn check_opt(src: &str) -> TypeResult<()> {
+    check(&format!("{}{}", OPT, src))
+}
+
+#[test]
+fn enum_definitions() {
+    assert_eq!(
+        check("enum List { Nil, Cons(int, box List) } List::Cons(1, box List::Nil)"),
+        Ok(())
+    );
+    assert_eq!(
+        check("enum E { A(&int) }"),
+        Err(Error::RefInEnum(String::from("E")))
+    );
+    assert_eq!(
+        check("fn f(x: Foo) {}"),
+        Err(Error::UnknownEnum(String::from("Foo")))
+    );
+    assert_eq!(
+        check_opt("Opt::Many"),
+        Err(Error::UnknownVariant(
+            String::from("Opt"),
+            String::from("Many")
+        ))
+    );
+    assert_eq!(
+        check_opt("Opt::Some(1)"),
+        Err(Error::IncompatibleTypes(Type::boxx(Type::Int), Type::Int))
+    );
+    assert_eq!(
+        check_opt("Opt::Some()"),
+        Err(Error::ArityMismatch(String::from("Opt::Some"), 1, 0))
+    );
+}
+
+#[test]
+fn match_binds_by_move_and_ref() {
+    // moving a field moves the enum out, borrowing it doesn't
+    assert_eq!(
+        check_opt("match o { Opt::Some(b) => *b, Opt::None => 0 }; o"),
+        Err(Error::MovedOut(Lval::new("o", 0)))
+    );
+    assert_eq!(
+        check_opt("match o { Opt::Some(ref mut b) => { **b = 2; } Opt::None => {} } o"),
+        Ok(())
+    );
+    assert_eq!(
+        check_opt("match o { Opt::Some(ref mut b) => { o = Opt::None; **b }, _ => 0 }"),
+        Err(Error::AssignAfterBorrow(Lval::new("o", 0)))
+    );
+    assert_eq!(
+        check_opt("match o { Opt::Some(ref b) => { let mut c = &mut o; 1 }, _ => 0 }"),
+        Ok(())
+    );
+    // bindings, and the fields they borrow, end with the arm
+    assert_eq!(
+        check_opt("let mut r = match o { Opt::Some(ref b) => b, Opt::None => &o };"),
+        Err(Error::LifetimeTooShort(Expr::Lval(
+            Lval::new("b", 0),
+            false
+        )))
+    );
+    assert_eq!(
+        check_opt("let mut b = 1; match o { Opt::Some(ref b) => 1, _ => 0 }"),
+        Err(Error::Shadowing(String::from("b")))
+    );
+    // a temporary is moved into the match
+    assert_eq!(
+        check_opt("1 + match Opt::Some(box 2) { Opt::Some(b) => *b, _ => 0 }"),
+        Ok(())
+    );
+}
+
+#[test]
+fn match_through_refs() {
+    assert_eq!(
+        check(
+            "enum Opt { None, Some(box int) }
+                 fn bump(o: &mut Opt) {
+                     match *o { Opt::Some(ref mut b) => { **b = **b + 1; } Opt::None => {} }
+                 }
+                 fn get(o: &Opt) -> int { match *o { Opt::Some(ref b) => **b, _ => 0 } }
+                 let mut o = Opt::Some(box 1);
+                 bump(&mut o);
+                 get(&o)"
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check_opt("let mut r = &o; match *r { Opt::Some(b) => *b, _ => 0 }"),
+        Err(Error::MoveBehindRef(Lval::new("r", 1)))
+    );
+    assert_eq!(
+        check_opt("let mut r = &o; match *r { Opt::Some(ref mut b) => 1, _ => 0 }"),
+        Err(Error::MutBorrowBehindImmRef(Lval::new("r", 1)))
+    );
+    // the arm keeps `*r` borrowed while it holds a binding into it
+    assert_eq!(
+        check_opt(
+            "let mut r = &mut o;
+                 match *r { Opt::Some(ref mut b) => { *r = Opt::None; **b }, _ => 0 }"
+        ),
+        Err(Error::AssignAfterBorrow(Lval::new("r", 1)))
+    );
+    assert_eq!(
+        check_opt("let mut r = &mut o; match o { _ => 1 }; *r = Opt::None;"),
+        Err(Error::CopyAfterMutBorrow(Lval::new("o", 0)))
+    );
+    assert_eq!(
+        check_opt("let mut p = box o; match *p { Opt::Some(ref b) => 1, _ => 0 }"),
+        Err(Error::BindingBehindBox(Lval::new("p", 1)))
+    );
+}
+
+#[test]
+fn match_arms_join() {
+    assert_eq!(
+        check_opt(
+            "let mut x = box 2; match o { Opt::Some(ref b) => { let mut y = x; } _ => {} } x"
+        ),
+        Err(Error::MovedOut(Lval::new("x", 0)))
+    );
+    assert_eq!(
+        check_opt("match o { Opt::Some(b) => 1 }"),
+        Err(Error::NonExhaustive(
+            String::from("Opt"),
+            vec![String::from("None")]
+        ))
+    );
+    assert_eq!(
+        check_opt("match o { Opt::Some(ref b) => 1, _ => true }"),
+        Err(Error::IncompatibleTypes(Type::Int, Type::Bool))
+    );
+    assert_eq!(
+        check(&format!(
+            "enum E {{ A }} {}match o {{ E::A => 1, _ => 0 }}",
+            OPT
+        )),
+        Err(Error::IncompatibleTypes(
+            Type::Enum(String::from("E")),
+            Type::Enum(String::from("Opt"))
+        ))
+    );
+    assert_eq!(check("1 + match 5 { _ => 1 }"), Ok(()));
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 pub type Ident = String;
 pub type Copyable = bool;
 pub type Mutable = bool;
@@ -11,29 +13,195 @@ impl Lifetime {
     }
 }
 
+/// A 1-based line/column position in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl Position {
+    pub fn start() -> Self {
+        Position { line: 1, col: 1 }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// Source range covered by a token or AST node, `end` is exclusive.
+///
+/// Spans are bookkeeping for diagnostics: they compare equal to each other so
+/// that a parsed tree is `==` to the same tree built by hand. The default span
+/// (line 0) stands for "unknown", e.g. for nodes built with `Lval::new`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn is_known(&self) -> bool {
+        self.start.line > 0
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+/// A place: `ident`, projected through tuple `fields`, then dereferenced
+/// `derefs` times. Like in Rust, `*x.0` is `*(x.0)`; unlike in Rust, there
+/// is no `(*x).0`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lval {
     pub ident: Ident,
+    pub fields: Vec<usize>,
     pub derefs: usize,
+    pub span: Span,
 }
 
 impl Lval {
     pub fn new(ident: &str, derefs: usize) -> Self {
         Lval {
             ident: ident.to_string(),
+            fields: vec![],
             derefs,
+            span: Span::default(),
+        }
+    }
+
+    /// `x.0.1` for `Lval::field("x", &[0, 1], 0)`.
+    pub fn field(ident: &str, fields: &[usize], derefs: usize) -> Self {
+        Lval {
+            fields: fields.to_vec(),
+            ..Lval::new(ident, derefs)
         }
     }
+
+    pub fn at(self, span: Span) -> Self {
+        Lval { span, ..self }
+    }
+
+    /// The place `n` more dereferences away.
+    pub fn deref_by(&self, n: usize) -> Self {
+        Lval {
+            derefs: self.derefs + n,
+            ..self.clone()
+        }
+    }
+
+    /// The place with only the first `n` steps of its path: fields first,
+    /// then dereferences.
+    pub fn prefix(&self, n: usize) -> Self {
+        let k = n.min(self.fields.len());
+        Lval {
+            fields: self.fields[..k].to_vec(),
+            derefs: n - k,
+            ..self.clone()
+        }
+    }
+
+    /// Number of steps in the path from `ident`.
+    pub fn path_len(&self) -> usize {
+        self.fields.len() + self.derefs
+    }
+
+    /// Whether the path from `ident` is a prefix of `other`'s.
+    pub fn is_prefix_of(&self, other: &Lval) -> bool {
+        self.ident == other.ident
+            && self.path_len() <= other.path_len()
+            && other.prefix(self.path_len()).fields == self.fields
+            && other.prefix(self.path_len()).derefs == self.derefs
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl fmt::Display for Lval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", "*".repeat(self.derefs), self.ident)?;
+        for field in &self.fields {
+            write!(f, ".{}", field)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl BinOp {
+    pub fn is_comparison(&self) -> bool {
+        !matches!(
+            self,
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem
+        )
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Expr {
     Unit,
     Int(i32),
+    Bool(bool),
     Lval(Lval, Copyable),
     Box(Box<Expr>),
     Borrow(Lval, Mutable),
     Block(Vec<Stmt>, Box<Expr>, Lifetime),
+    Call(Ident, Vec<Expr>),
+    BinOp(BinOp, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    While(Box<Expr>, Box<Expr>),
+    Tuple(Vec<Expr>),
+    /// `Enum::Variant(args)`
+    Variant(Ident, Ident, Vec<Expr>),
+    /// `match e { arms }`, with the lifetime of the arms' bindings.
+    Match(Box<Expr>, Vec<(Pattern, Expr)>, Lifetime),
+    /// An expression tagged with where it was parsed from.
+    Spanned(Box<Expr>, Span),
 }
 
 impl Expr {
@@ -41,14 +209,201 @@ impl Expr {
         Expr::Box(Box::new(inner))
     }
 
+    pub fn binop(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+        Expr::BinOp(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn iff(cond: Expr, then_e: Expr, else_e: Expr) -> Expr {
+        Expr::If(Box::new(cond), Box::new(then_e), Box::new(else_e))
+    }
+
+    pub fn whilee(cond: Expr, body: Expr) -> Expr {
+        Expr::While(Box::new(cond), Box::new(body))
+    }
+
     pub fn block(stmts: Vec<Stmt>, final_expr: Expr, lifetime: Lifetime) -> Expr {
         Expr::Block(stmts, Box::new(final_expr), lifetime)
     }
+
+    pub fn spanned(inner: Expr, span: Span) -> Expr {
+        Expr::Spanned(Box::new(inner), span)
+    }
+
+    /// The expression under any span tags.
+    pub fn peel(&self) -> &Expr {
+        match self {
+            Expr::Spanned(e, _) => e.peel(),
+            e => e,
+        }
+    }
+
+    pub fn peel_mut(&mut self) -> &mut Expr {
+        match self {
+            Expr::Spanned(e, _) => e.peel_mut(),
+            e => e,
+        }
+    }
+
+    /// The expression that gives `self` its value, past any blocks, e.g.
+    /// `&x` in `{ let mut y = 1; &x }`.
+    pub fn tail(&self) -> &Expr {
+        match self.peel() {
+            Expr::Block(_, final_e, _) => final_e.tail(),
+            _ => self,
+        }
+    }
+
+    /// Where the expression was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Spanned(_, span) => Some(*span),
+            Expr::Lval(lv, _) | Expr::Borrow(lv, _) if lv.span.is_known() => Some(lv.span),
+            _ => None,
+        }
+    }
+}
+
+/// Span tags are ignored, like spans themselves.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Expr) -> bool {
+        use Expr::*;
+        match (self.peel(), other.peel()) {
+            (Unit, Unit) => true,
+            (Int(a), Int(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (Lval(a, c), Lval(b, d)) => a == b && c == d,
+            (Box(a), Box(b)) => a == b,
+            (Borrow(a, m), Borrow(b, n)) => a == b && m == n,
+            (Block(s, e, l), Block(t, f, m)) => s == t && e == f && l == m,
+            (Call(f, xs), Call(g, ys)) => f == g && xs == ys,
+            (BinOp(o, a, b), BinOp(p, c, d)) => o == p && a == c && b == d,
+            (If(c, a, b), If(d, e, f)) => c == d && a == e && b == f,
+            (While(c, a), While(d, b)) => c == d && a == b,
+            (Tuple(xs), Tuple(ys)) => xs == ys,
+            (Variant(e, v, xs), Variant(f, w, ys)) => e == f && v == w && xs == ys,
+            (Match(e, xs, l), Match(f, ys, m)) => e == f && xs == ys && l == m,
+            _ => false,
+        }
+    }
+}
+
+/// A type as written in the source, e.g. in a function signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeAnn {
+    Unit,
+    Int,
+    Bool,
+    Box(Box<TypeAnn>),
+    /// `&'a mut T`, with the lifetime name if one is written.
+    Ref(Box<TypeAnn>, Mutable, Option<Ident>),
+    Tuple(Vec<TypeAnn>),
+    Enum(Ident),
+}
+
+impl TypeAnn {
+    /// The number of references in parameter position that don't name a
+    /// lifetime, each of which gets a lifetime of its own.
+    pub fn elided_refs(&self) -> usize {
+        match self {
+            TypeAnn::Unit | TypeAnn::Int | TypeAnn::Bool | TypeAnn::Enum(_) => 0,
+            TypeAnn::Box(inner) => inner.elided_refs(),
+            TypeAnn::Ref(inner, _, name) => usize::from(name.is_none()) + inner.elided_refs(),
+            TypeAnn::Tuple(anns) => anns.iter().map(TypeAnn::elided_refs).sum(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+pub struct FnDef {
+    pub name: Ident,
+    pub params: Vec<(Ident, TypeAnn)>,
+    pub ret: TypeAnn,
+    /// Lifetime parameters `<'a, ...>` and the lifetimes standing for them,
+    /// followed by a `'_` entry for each elided reference parameter.
+    pub lifetimes: Vec<(Ident, Lifetime)>,
+    /// Declared bounds: `('b, 'a)` for `'b: 'a`, "`'b` outlives `'a`".
+    pub bounds: Vec<(Ident, Ident)>,
+    pub body: Expr,
+    /// Lifetime of the parameters, which encloses the body block.
+    pub lifetime: Lifetime,
+}
+
+/// `enum Name { A, B(T, ...), ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDef {
+    pub name: Ident,
+    pub variants: Vec<(Ident, Vec<TypeAnn>)>,
+}
+
+/// A match arm's pattern: `_`, or a variant with a binding per field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Variant(Ident, Ident, Vec<Binding>),
+}
+
+/// How a pattern binds a field of the variant it matches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    /// `_`
+    Ignore,
+    /// `x`, which moves the field out unless the checker marks it a copy.
+    Move(Ident, Copyable),
+    /// `ref x` or `ref mut x`
+    Ref(Ident, Mutable),
+}
+
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Assign(Lval, Expr),
     LetMut(Ident, Expr),
     Expr(Expr),
+    Fn(FnDef),
+    Enum(EnumDef),
+    /// A statement tagged with where it was parsed from.
+    Spanned(Box<Stmt>, Span),
+}
+
+impl Stmt {
+    pub fn spanned(inner: Stmt, span: Span) -> Stmt {
+        Stmt::Spanned(Box::new(inner), span)
+    }
+
+    /// The statement under any span tags.
+    pub fn peel(&self) -> &Stmt {
+        match self {
+            Stmt::Spanned(s, _) => s.peel(),
+            s => s,
+        }
+    }
+
+    pub fn peel_mut(&mut self) -> &mut Stmt {
+        match self {
+            Stmt::Spanned(s, _) => s.peel_mut(),
+            s => s,
+        }
+    }
+
+    /// Where the statement was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Stmt::Spanned(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+/// Span tags are ignored, like spans themselves.
+impl PartialEq for Stmt {
+    fn eq(&self, other: &Stmt) -> bool {
+        use Stmt::*;
+        match (self.peel(), other.peel()) {
+            (Assign(a, e), Assign(b, f)) => a == b && e == f,
+            (LetMut(x, e), LetMut(y, f)) => x == y && e == f,
+            (Expr(e), Expr(f)) => e == f,
+            (Fn(f), Fn(g)) => f == g,
+            (Enum(a), Enum(b)) => a == b,
+            _ => false,
+        }
+    }
 }
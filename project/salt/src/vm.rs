@@ -0,0 +1,762 @@
+// A compiler from checked salt expressions to a stack bytecode, and the VM
+// that runs it.
+//
+// Each function, and the program itself, compiles to a `Chunk` whose
+// variables live in numbered slots of its frame, one per name as in the
+// tree-walker, and whose places are paths resolved when the chunk is
+// compiled. Boxes live in the same kind of arena as the tree-walker's and
+// frames are numbered the same way, so a result converts back to the
+// `eval::Value` the tree-walker gives for the same program.
+
+use crate::eval::{self, EvalResult, FrameId, Heap, Index, Location, RuntimeError};
+use crate::utils::{BinOp, Binding, Expr, FnDef, Ident, Lval, Pattern, Stmt};
+use std::collections::HashMap;
+
+/// A step on the path from a slot to a place.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Field(usize),
+    Deref,
+}
+
+/// A slot of the current frame, projected along a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub slot: usize,
+    pub path: Vec<Step>,
+}
+
+/// How a match arm binds a field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Copy,
+    Move,
+    Ref,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Unit,
+    Int(i32),
+    Bool(bool),
+    Copy(Place),
+    Move(Place),
+    Borrow(Place),
+    /// Pop a value and write it to the place.
+    Assign(Place),
+    /// Pop a value into a slot.
+    Let(usize),
+    Pop,
+    Box,
+    /// Pop this many values into a tuple.
+    Tuple(usize),
+    /// Pop this many values into the variant with this tag.
+    Variant(usize, usize),
+    BinOp(BinOp),
+    Jump(usize),
+    /// Pop a boolean and jump if it is false.
+    JumpUnless(usize),
+    /// Jump to the first arm whose tag, if any, names the place's variant.
+    Match(Place, Vec<(Option<usize>, usize)>),
+    /// Bind a field of the matched place to a slot.
+    Bind(Place, usize, Mode, usize),
+    /// Pop this many arguments and call the chunk.
+    Call(usize, usize),
+    Return,
+    /// Free the slots and the boxes they own.
+    Drop(Vec<usize>),
+    Trap(RuntimeError),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// The variable held in each slot.
+    pub slots: Vec<Ident>,
+    pub params: Vec<usize>,
+}
+
+/// The program's chunks, the first of which runs it, and the enum and
+/// variant names of each variant tag.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    pub chunks: Vec<Chunk>,
+    pub variants: Vec<(Ident, Ident)>,
+}
+
+impl Chunk {
+    fn slot(&mut self, x: &str) -> usize {
+        match self.slots.iter().position(|y| y == x) {
+            Some(i) => i,
+            None => {
+                self.slots.push(x.to_string());
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    fn place(&mut self, lval: &Lval) -> Place {
+        let mut path: Vec<Step> = lval.fields.iter().map(|&i| Step::Field(i)).collect();
+        path.extend((0..lval.derefs).map(|_| Step::Deref));
+        Place {
+            slot: self.slot(&lval.ident),
+            path,
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    /// Point the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let here = self.code.len();
+        match &mut self.code[at] {
+            Op::Jump(target) | Op::JumpUnless(target) => *target = here,
+            op => unreachable!("`{:?}` is not a jump", op),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    chunks: Vec<Chunk>,
+    fns: HashMap<Ident, usize>,
+    variants: Vec<(Ident, Ident)>,
+}
+
+/// Compile a program, usually one the checker has elaborated.
+pub fn compile(expr: &Expr) -> Program {
+    let mut compiler = Compiler {
+        chunks: vec![Chunk::default()],
+        ..Compiler::default()
+    };
+    let mut main = Chunk::default();
+    compiler.expr(&mut main, expr);
+    main.emit(Op::Return);
+    compiler.chunks[0] = main;
+    Program {
+        chunks: compiler.chunks,
+        variants: compiler.variants,
+    }
+}
+
+impl Compiler {
+    fn tag(&mut self, name: &str, variant: &str) -> usize {
+        let key = (name.to_string(), variant.to_string());
+        match self.variants.iter().position(|v| *v == key) {
+            Some(i) => i,
+            None => {
+                self.variants.push(key);
+                self.variants.len() - 1
+            }
+        }
+    }
+
+    /// Compile the functions a block defines, which are all in scope for
+    /// each other's bodies.
+    fn define_fns(&mut self, stmts: &[Stmt]) {
+        let defs: Vec<&FnDef> = stmts
+            .iter()
+            .filter_map(|s| match s.peel() {
+                Stmt::Fn(def) => Some(def),
+                _ => None,
+            })
+            .collect();
+        for def in &defs {
+            self.fns.insert(def.name.clone(), self.chunks.len());
+            self.chunks.push(Chunk::default());
+        }
+        for def in defs {
+            let mut c = Chunk::default();
+            c.params = def.params.iter().map(|(x, _)| c.slot(x)).collect();
+            self.expr(&mut c, &def.body);
+            c.emit(Op::Drop(c.params.clone()));
+            c.emit(Op::Return);
+            self.chunks[self.fns[&def.name]] = c;
+        }
+    }
+
+    fn expr(&mut self, c: &mut Chunk, expr: &Expr) {
+        match expr {
+            Expr::Unit => {
+                c.emit(Op::Unit);
+            }
+
+            Expr::Int(n) => {
+                c.emit(Op::Int(*n));
+            }
+
+            Expr::Bool(b) => {
+                c.emit(Op::Bool(*b));
+            }
+
+            Expr::Lval(lval, copyable) => {
+                let place = c.place(lval);
+                c.emit(if *copyable {
+                    Op::Copy(place)
+                } else {
+                    Op::Move(place)
+                });
+            }
+
+            Expr::Tuple(elems) => {
+                for e in elems {
+                    self.expr(c, e);
+                }
+                c.emit(Op::Tuple(elems.len()));
+            }
+
+            Expr::Box(inner) => {
+                self.expr(c, inner);
+                c.emit(Op::Box);
+            }
+
+            Expr::Borrow(lval, _) => {
+                let place = c.place(lval);
+                c.emit(Op::Borrow(place));
+            }
+
+            Expr::Block(stmts, final_expr, _) => {
+                self.define_fns(stmts);
+                let mut locals = vec![];
+                for stmt in stmts {
+                    self.stmt(c, stmt, &mut locals);
+                }
+                self.expr(c, final_expr);
+                if !locals.is_empty() {
+                    c.emit(Op::Drop(locals));
+                }
+            }
+
+            Expr::BinOp(op, lhs, rhs) => {
+                self.expr(c, lhs);
+                self.expr(c, rhs);
+                c.emit(Op::BinOp(*op));
+            }
+
+            Expr::If(cond, then_e, else_e) => {
+                self.expr(c, cond);
+                let to_else = c.emit(Op::JumpUnless(0));
+                self.expr(c, then_e);
+                let to_end = c.emit(Op::Jump(0));
+                c.patch(to_else);
+                self.expr(c, else_e);
+                c.patch(to_end);
+            }
+
+            Expr::While(cond, body) => {
+                let top = c.code.len();
+                self.expr(c, cond);
+                let to_end = c.emit(Op::JumpUnless(0));
+                self.expr(c, body);
+                c.emit(Op::Pop);
+                c.emit(Op::Jump(top));
+                c.patch(to_end);
+                c.emit(Op::Unit);
+            }
+
+            Expr::Call(name, args) => {
+                let Some(&f) = self.fns.get(name) else {
+                    c.emit(Op::Trap(RuntimeError::UnknownFn(name.clone())));
+                    return;
+                };
+                for arg in args {
+                    self.expr(c, arg);
+                }
+                c.emit(Op::Call(f, args.len()));
+            }
+
+            Expr::Variant(name, variant, args) => {
+                for arg in args {
+                    self.expr(c, arg);
+                }
+                let tag = self.tag(name, variant);
+                c.emit(Op::Variant(tag, args.len()));
+            }
+
+            Expr::Match(scrutinee, arms, match_lifetime) => {
+                // a place is matched where it is, anything else in a
+                // temporary that lives as long as the arm's bindings
+                let mut temps = vec![];
+                let place = match scrutinee.peel() {
+                    Expr::Lval(lval, _) => c.place(lval),
+                    _ => {
+                        self.expr(c, scrutinee);
+                        let slot = c.slot(&format!("match'{}", match_lifetime.0));
+                        c.emit(Op::Let(slot));
+                        temps.push(slot);
+                        Place { slot, path: vec![] }
+                    }
+                };
+                let dispatch = c.emit(Op::Match(place.clone(), vec![]));
+                let mut targets = vec![];
+                let mut to_end = vec![];
+                for (pattern, body) in arms {
+                    let mut locals = temps.clone();
+                    match pattern {
+                        Pattern::Wildcard => targets.push((None, c.code.len())),
+                        Pattern::Variant(name, variant, bindings) => {
+                            targets.push((Some(self.tag(name, variant)), c.code.len()));
+                            for (i, binding) in bindings.iter().enumerate() {
+                                let (x, mode) = match binding {
+                                    Binding::Ignore => continue,
+                                    Binding::Move(x, true) => (x, Mode::Copy),
+                                    Binding::Move(x, false) => (x, Mode::Move),
+                                    Binding::Ref(x, _) => (x, Mode::Ref),
+                                };
+                                let slot = c.slot(x);
+                                c.emit(Op::Bind(place.clone(), i, mode, slot));
+                                locals.push(slot);
+                            }
+                        }
+                    }
+                    self.expr(c, body);
+                    if !locals.is_empty() {
+                        c.emit(Op::Drop(locals));
+                    }
+                    to_end.push(c.emit(Op::Jump(0)));
+                }
+                c.code[dispatch] = Op::Match(place, targets);
+                for jump in to_end {
+                    c.patch(jump);
+                }
+            }
+
+            Expr::Spanned(inner, _) => self.expr(c, inner),
+        }
+    }
+
+    /// Compile a statement, adding the slots it allocates to `locals`.
+    fn stmt(&mut self, c: &mut Chunk, stmt: &Stmt, locals: &mut Vec<usize>) {
+        match stmt {
+            Stmt::LetMut(x, e) => {
+                self.expr(c, e);
+                let slot = c.slot(x);
+                c.emit(Op::Let(slot));
+                if !locals.contains(&slot) {
+                    locals.push(slot);
+                }
+            }
+
+            Stmt::Assign(lval, e) => {
+                self.expr(c, e);
+                let place = c.place(lval);
+                c.emit(Op::Assign(place));
+            }
+
+            Stmt::Expr(e) => {
+                self.expr(c, e);
+                c.emit(Op::Pop);
+            }
+
+            // compiled when the enclosing block starts
+            Stmt::Fn(_) | Stmt::Enum(_) => {}
+
+            Stmt::Spanned(inner, _) => self.stmt(c, inner, locals),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Root {
+    /// A slot of the frame at this depth, if it still has this id.
+    Local(usize, FrameId, usize),
+    Heap(Index),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Addr {
+    root: Root,
+    fields: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Unit,
+    Int(i32),
+    Bool(bool),
+    Ref(Addr, bool),
+    Tuple(Vec<Pvalue>),
+    Enum(usize, Vec<Pvalue>),
+}
+
+type Pvalue = Option<Value>;
+
+struct Frame {
+    id: FrameId,
+    chunk: usize,
+    pc: usize,
+    /// `None` for slots that aren't allocated.
+    slots: Vec<Option<Pvalue>>,
+}
+
+struct Vm<'p> {
+    program: &'p Program,
+    frames: Vec<Frame>,
+    next_frame: FrameId,
+    heap: Heap<Pvalue>,
+    stack: Vec<Value>,
+    max_depth: usize,
+}
+
+impl Program {
+    pub fn run(&self) -> EvalResult<eval::Value> {
+        self.run_with_limit(eval::MAX_DEPTH)
+    }
+
+    /// Run with at most `max_depth` calls in progress at once, failing as
+    /// the tree-walker does with the same `Context::max_depth`.
+    pub fn run_with_limit(&self, max_depth: usize) -> EvalResult<eval::Value> {
+        let mut vm = Vm {
+            program: self,
+            frames: vec![Frame {
+                id: 0,
+                chunk: 0,
+                pc: 0,
+                slots: vec![None; self.chunks[0].slots.len()],
+            }],
+            next_frame: 1,
+            heap: Heap::default(),
+            stack: vec![],
+            max_depth,
+        };
+        let val = vm.run()?;
+        Ok(vm.reify(&val))
+    }
+}
+
+impl Vm<'_> {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the program's frame is never popped")
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn pop_n(&mut self, n: usize) -> Vec<Pvalue> {
+        let at = self.stack.len() - n;
+        self.stack.split_off(at).into_iter().map(Some).collect()
+    }
+
+    fn run(&mut self) -> EvalResult<Value> {
+        let program = self.program;
+        loop {
+            let frame = self.frame();
+            let op = &program.chunks[frame.chunk].code[frame.pc];
+            frame.pc += 1;
+            match op {
+                Op::Unit => self.stack.push(Value::Unit),
+                Op::Int(n) => self.stack.push(Value::Int(*n)),
+                Op::Bool(b) => self.stack.push(Value::Bool(*b)),
+
+                Op::Copy(place) | Op::Move(place) => {
+                    let addr = self.locate(place)?;
+                    let slot = match self.get_mut(&addr) {
+                        Some(slot) => slot,
+                        None => return Err(RuntimeError::missing(&self.lval(place))),
+                    };
+                    // a move leaves the place uninitialised, like in `eval`
+                    let val = if matches!(op, Op::Copy(_)) {
+                        slot.clone()
+                    } else {
+                        slot.take()
+                    };
+                    let val =
+                        val.ok_or_else(|| RuntimeError::UninitializedRead(self.lval(place)))?;
+                    self.stack.push(val);
+                }
+
+                Op::Borrow(place) => {
+                    let addr = self.locate(place)?;
+                    self.stack.push(Value::Ref(addr, false));
+                }
+
+                Op::Assign(place) => {
+                    let val = self.pop();
+                    let addr = self.locate(place)?;
+                    let old = match self.get_mut(&addr) {
+                        Some(slot) => slot.replace(val),
+                        None => return Err(RuntimeError::missing(&self.lval(place))),
+                    };
+                    self.free(vec![old]);
+                }
+
+                Op::Let(slot) => {
+                    let val = self.pop();
+                    self.frame().slots[*slot] = Some(Some(val));
+                }
+
+                Op::Pop => {
+                    self.pop();
+                }
+
+                Op::Box => {
+                    let val = self.pop();
+                    let addr = Addr {
+                        root: Root::Heap(self.heap.alloc(Some(val))),
+                        fields: vec![],
+                    };
+                    self.stack.push(Value::Ref(addr, true));
+                }
+
+                Op::Tuple(n) => {
+                    let vals = self.pop_n(*n);
+                    self.stack.push(Value::Tuple(vals));
+                }
+
+                Op::Variant(tag, n) => {
+                    let vals = self.pop_n(*n);
+                    self.stack.push(Value::Enum(*tag, vals));
+                }
+
+                Op::BinOp(op) => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    let (a, b) = match (a, b) {
+                        (Value::Int(a), Value::Int(b)) => (a, b),
+                        (Value::Int(_), other) | (other, _) => {
+                            return Err(RuntimeError::NotAnInt(self.reify(&other)))
+                        }
+                    };
+                    let val = match eval::binop(*op, a, b)? {
+                        eval::Value::Int(n) => Value::Int(n),
+                        eval::Value::Bool(b) => Value::Bool(b),
+                        other => unreachable!("`binop` gave `{}`", other),
+                    };
+                    self.stack.push(val);
+                }
+
+                Op::Jump(target) => self.frame().pc = *target,
+
+                Op::JumpUnless(target) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => self.frame().pc = *target,
+                    other => return Err(RuntimeError::NotABool(self.reify(&other))),
+                },
+
+                Op::Match(place, arms) => {
+                    let addr = self.locate(place)?;
+                    let Some(Some(val)) = self.get(&addr) else {
+                        return Err(RuntimeError::UninitializedRead(self.lval(place)));
+                    };
+                    // variants are told apart by name, like in `eval`
+                    let arm = arms.iter().find(|(tag, _)| match (tag, val) {
+                        (None, _) => true,
+                        (Some(t), Value::Enum(u, _)) => {
+                            program.variants[*t].1 == program.variants[*u].1
+                        }
+                        _ => false,
+                    });
+                    match arm {
+                        Some((_, target)) => self.frame().pc = *target,
+                        None => return Err(RuntimeError::NoArmMatches(self.reify(val))),
+                    }
+                }
+
+                Op::Bind(place, i, mode, slot) => {
+                    let mut addr = self.locate(place)?;
+                    addr.fields.push(*i);
+                    let val = match mode {
+                        Mode::Copy => self.get(&addr).cloned().flatten(),
+                        Mode::Move => self.get_mut(&addr).and_then(Option::take),
+                        Mode::Ref => {
+                            let whole = self.get(&addr).is_some_and(is_whole);
+                            whole.then_some(Value::Ref(addr, false))
+                        }
+                    };
+                    let val =
+                        val.ok_or_else(|| RuntimeError::UninitializedRead(self.lval(place)))?;
+                    self.frame().slots[*slot] = Some(Some(val));
+                }
+
+                Op::Call(f, n) => {
+                    if self.frames.len() > self.max_depth {
+                        return Err(RuntimeError::RecursionLimit(self.max_depth));
+                    }
+                    let args = self.pop_n(*n);
+                    let chunk = &program.chunks[*f];
+                    let mut slots = vec![None; chunk.slots.len()];
+                    for (slot, arg) in chunk.params.iter().zip(args) {
+                        slots[*slot] = Some(arg);
+                    }
+                    self.frames.push(Frame {
+                        id: self.next_frame,
+                        chunk: *f,
+                        pc: 0,
+                        slots,
+                    });
+                }
+
+                Op::Return => {
+                    if self.frames.len() == 1 {
+                        return Ok(self.pop());
+                    }
+                    self.frames.pop();
+                }
+
+                Op::Drop(slots) => {
+                    let frame = self.frame();
+                    let vals = slots
+                        .iter()
+                        .filter_map(|s| frame.slots[*s].take())
+                        .collect();
+                    self.free(vals);
+                }
+
+                Op::Trap(e) => return Err(e.clone()),
+            }
+        }
+    }
+
+    /// Follow `place`'s path from its slot in the current frame.
+    fn locate(&self, place: &Place) -> EvalResult<Addr> {
+        let mut addr = Addr {
+            root: Root::Local(self.frames.len() - 1, self.top().id, place.slot),
+            fields: vec![],
+        };
+        for (i, step) in place.path.iter().enumerate() {
+            match step {
+                Step::Field(f) => addr.fields.push(*f),
+                Step::Deref => {
+                    addr = match self.get(&addr) {
+                        Some(Some(Value::Ref(next, _))) => next.clone(),
+                        Some(Some(_)) => return Err(RuntimeError::BadDeref(self.lval(place))),
+                        Some(None) => {
+                            return Err(RuntimeError::UninitializedRead(self.lval(place).prefix(i)))
+                        }
+                        None => return Err(RuntimeError::missing(&self.lval(place).prefix(i))),
+                    }
+                }
+            }
+        }
+        Ok(addr)
+    }
+
+    fn get(&self, addr: &Addr) -> Option<&Pvalue> {
+        let mut v = match addr.root {
+            Root::Local(depth, id, slot) => self.frame_at(depth, id)?.slots[slot].as_ref()?,
+            Root::Heap(i) => self.heap.get(i)?,
+        };
+        for f in &addr.fields {
+            match v {
+                Some(Value::Tuple(vs) | Value::Enum(_, vs)) => v = vs.get(*f)?,
+                _ => return None,
+            }
+        }
+        Some(v)
+    }
+
+    fn get_mut(&mut self, addr: &Addr) -> Option<&mut Pvalue> {
+        let mut v = match addr.root {
+            Root::Local(depth, id, slot) => {
+                let frame = self.frames.get_mut(depth).filter(|f| f.id == id)?;
+                frame.slots[slot].as_mut()?
+            }
+            Root::Heap(i) => self.heap.get_mut(i)?,
+        };
+        for f in &addr.fields {
+            match v {
+                Some(Value::Tuple(vs) | Value::Enum(_, vs)) => v = vs.get_mut(*f)?,
+                _ => return None,
+            }
+        }
+        Some(v)
+    }
+
+    /// Free the boxes `values` own, following chains of owned boxes.
+    fn free(&mut self, values: Vec<Pvalue>) {
+        let mut pending = values;
+        while let Some(pval) = pending.pop() {
+            match pval {
+                Some(Value::Ref(
+                    Addr {
+                        root: Root::Heap(i),
+                        ..
+                    },
+                    true,
+                )) => {
+                    pending.extend(self.heap.free(i));
+                }
+                Some(Value::Tuple(vs) | Value::Enum(_, vs)) => pending.extend(vs),
+                _ => {}
+            }
+        }
+    }
+
+    fn top(&self) -> &Frame {
+        self.frames
+            .last()
+            .expect("the program's frame is never popped")
+    }
+
+    /// The frame at `depth`, unless the call with id `id` has returned.
+    fn frame_at(&self, depth: usize, id: FrameId) -> Option<&Frame> {
+        self.frames.get(depth).filter(|f| f.id == id)
+    }
+
+    /// The place as it was written, for errors.
+    fn lval(&self, place: &Place) -> Lval {
+        let mut fields = vec![];
+        let mut derefs = 0;
+        for step in &place.path {
+            match step {
+                Step::Field(f) => fields.push(*f),
+                Step::Deref => derefs += 1,
+            }
+        }
+        let ident = &self.program.chunks[self.top().chunk].slots[place.slot];
+        Lval::field(ident, &fields, derefs)
+    }
+
+    /// The value the tree-walker would have, with locations as it has
+    /// them.
+    fn reify(&self, val: &Value) -> eval::Value {
+        let reify_all = |vs: &[Pvalue]| {
+            vs.iter()
+                .map(|v| v.as_ref().map(|v| self.reify(v)))
+                .collect()
+        };
+        match val {
+            Value::Unit => eval::Value::Unit,
+            Value::Int(n) => eval::Value::Int(*n),
+            Value::Bool(b) => eval::Value::Bool(*b),
+            Value::Ref(addr, owned) => {
+                let root = match addr.root {
+                    Root::Heap(i) => eval::Root::Heap(i),
+                    Root::Local(depth, id, slot) => {
+                        // a call that has returned leaves the slot unnamed
+                        let x = self
+                            .frame_at(depth, id)
+                            .map_or("?", |f| &self.program.chunks[f.chunk].slots[slot]);
+                        eval::Root::Var(id, x.to_string())
+                    }
+                };
+                let loc = Location {
+                    root,
+                    fields: addr.fields.clone(),
+                    tag: None,
+                };
+                eval::Value::Ref(loc, *owned)
+            }
+            Value::Tuple(vs) => eval::Value::Tuple(reify_all(vs)),
+            Value::Enum(tag, vs) => {
+                let (name, variant) = self.program.variants[*tag].clone();
+                eval::Value::Enum(name, variant, reify_all(vs))
+            }
+        }
+    }
+}
+
+/// Whether `v` is initialised, fields of tuples and enums included.
+fn is_whole(v: &Pvalue) -> bool {
+    match v {
+        Some(Value::Tuple(vs) | Value::Enum(_, vs)) => vs.iter().all(is_whole),
+        Some(_) => true,
+        None => false,
+    }
+}
@@ -0,0 +1,278 @@
+use crate::eval::{Context, EvalResult, Index, Location, Root, RuntimeError, Value, MAX_DEPTH};
+use crate::parser::parse_program;
+use crate::part_1_tests::programs;
+use crate::types;
+use crate::utils::{BinOp, Expr, Lifetime, Lval, Pattern, Stmt};
+use crate::vm::{self, Op, Place, Step};
+
+/// Run `e` on the tree-walker and on the VM, which must agree.
+fn agree(e: &Expr) -> EvalResult<Value> {
+    let walked = Context::default().eval_expr(e, &Lifetime::global());
+    let program = vm::compile(e);
+    assert_eq!(program.run(), walked, "{:#?}", program);
+    walked
+}
+
+fn elaborate(src: &str) -> Expr {
+    let program = parse_program(src).unwrap();
+    types::Context::default().elaborate(&program).unwrap().expr
+}
+
+fn block(stmts: Vec<Stmt>, final_e: Expr) -> Expr {
+    Expr::block(stmts, final_e, Lifetime(1))
+}
+
+fn let_mut(x: &str, e: Expr) -> Stmt {
+    Stmt::LetMut(String::from(x), e)
+}
+
+fn copy(x: &str, derefs: usize) -> Expr {
+    Expr::Lval(Lval::new(x, derefs), true)
+}
+
+fn mov(x: &str, derefs: usize) -> Expr {
+    Expr::Lval(Lval::new(x, derefs), false)
+}
+
+/// An owning reference to the first box allocated.
+fn first_box() -> Value {
+    let i = Index {
+        cell: 0,
+        generation: 0,
+    };
+    Value::Ref(Location::new(Root::Heap(i)), true)
+}
+
+#[test]
+fn compile_to_slots() {
+    let program = vm::compile(&elaborate("let mut x = 1; let mut y = &x; *y"));
+    let main = &program.chunks[0];
+    assert_eq!(main.slots, ["x", "y"]);
+    assert_eq!(
+        main.code,
+        [
+            Op::Int(1),
+            Op::Let(0),
+            Op::Borrow(Place {
+                slot: 0,
+                path: vec![]
+            }),
+            Op::Let(1),
+            Op::Copy(Place {
+                slot: 1,
+                path: vec![Step::Deref]
+            }),
+            Op::Drop(vec![0, 1]),
+            Op::Return,
+        ]
+    );
+}
+
+// The programs of part 1's eval tests, which inspect the store, each
+// followed by reads of what its test inspected.
+#[test]
+fn part_1_eval_programs() {
+    assert_eq!(agree(&Expr::Unit), Ok(Value::Unit));
+    assert_eq!(agree(&Expr::Int(234)), Ok(Value::Int(234)));
+
+    // eval_copy and eval_move
+    let read_twice = |read: fn(&str, usize) -> Expr| {
+        block(
+            vec![programs::let_int("x", 34), Stmt::Expr(read("x", 0))],
+            read("x", 0),
+        )
+    };
+    assert_eq!(agree(&read_twice(copy)), Ok(Value::Int(34)));
+    assert_eq!(
+        agree(&read_twice(mov)),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 0)))
+    );
+
+    // eval_box, eval_box_box and eval_let_mut
+    assert_eq!(agree(&Expr::boxx(Expr::Int(-1))), Ok(first_box()));
+    let let_b = || vec![let_mut("b", programs::box_box())];
+    assert_eq!(agree(&block(let_b(), copy("b", 2))), Ok(Value::Int(12)));
+    assert_eq!(agree(&block(let_b(), mov("b", 1))), Ok(first_box()));
+    assert_eq!(
+        agree(&block(vec![programs::let_box("x", 14)], copy("x", 1))),
+        Ok(Value::Int(14))
+    );
+
+    // eval_assign_copy, eval_assign_move, eval_assign_replace and
+    // eval_assign_move_deref
+    let after_two_boxes = |stmts: Vec<Stmt>| {
+        block(
+            [programs::two_boxes(), stmts].concat(),
+            Expr::Tuple(vec![copy("x", 1), copy("y", 1)]),
+        )
+    };
+    let int_pair = |a, b| Value::Tuple(vec![Some(Value::Int(a)), Some(Value::Int(b))]);
+    assert_eq!(agree(&after_two_boxes(vec![])), Ok(int_pair(14, 15)));
+    assert_eq!(
+        agree(&after_two_boxes(vec![programs::assign_copy()])),
+        Ok(int_pair(15, 15))
+    );
+    assert_eq!(
+        agree(&after_two_boxes(vec![programs::assign_move()])),
+        Err(RuntimeError::UninitializedRead(Lval::new("y", 0)))
+    );
+    assert_eq!(
+        agree(&after_two_boxes(programs::assign_replace())),
+        Ok(int_pair(15, 16))
+    );
+    assert_eq!(
+        agree(&after_two_boxes(vec![programs::assign_move_deref()])),
+        Err(RuntimeError::UninitializedRead(Lval::new("y", 1)))
+    );
+
+    // eval_expr_stmt
+    assert_eq!(
+        agree(&block(
+            vec![programs::let_box("x", 14), programs::move_deref()],
+            copy("x", 1)
+        )),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 1)))
+    );
+
+    // eval_block, eval_block_ref and eval_block_mut_ref
+    assert_eq!(
+        agree(&block(vec![Stmt::Expr(programs::block())], copy("x", 0))),
+        Err(RuntimeError::UnknownLocation(Lval::new("x", 0)))
+    );
+    let after_x = |e| vec![programs::let_box("x", 203), Stmt::Expr(e)];
+    assert_eq!(
+        agree(&block(
+            after_x(programs::block_ref()),
+            Expr::Tuple(vec![copy("x", 1), copy("y", 0)])
+        )),
+        Err(RuntimeError::UnknownLocation(Lval::new("y", 0)))
+    );
+    assert_eq!(
+        agree(&block(after_x(programs::block_mut_ref()), copy("x", 1))),
+        Ok(Value::Int(-150))
+    );
+}
+
+#[test]
+fn accepted_programs_agree() {
+    let programs = [
+        "fn fib(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+             fib(15)",
+        "fn bump(r: &mut int) { *r = *r + 1; }
+             let mut i = 0;
+             let mut n = box 0;
+             while i < 10 { bump(&mut *n); i = i + 1; }
+             (i, *n, n)",
+        // an assignment frees the box it overwrites, for the next to reuse
+        "let mut x = box 1;
+             x = box 2;
+             let mut y = box 3;
+             (*x, y)",
+        // each box reuses the cell the one before it freed
+        "let mut i = 0;
+             while i < 3 { let mut b = box i; i = i + 1; }
+             box 7",
+        "let mut t = (1, box 2, (true, 3));
+             let mut r = &mut t.2;
+             *r = (false, 4);
+             let mut b = t.1;
+             (t.0, *b, t.2)",
+        "fn first<'a>(x: &'a int, y: &int) -> &'a int { x }
+             let mut p = (7, 8);
+             let mut q = 9;
+             let mut r = first(&p.1, &q);
+             *r",
+        "enum List { Nil, Cons(int, box List) }
+             fn sum(l: &List) -> int {
+                 match *l { List::Nil => 0, List::Cons(n, ref rest) => n + sum(&**rest) }
+             }
+             fn push(l: &mut List, n: int) {
+                 match *l {
+                     List::Nil => { *l = List::Cons(n, box List::Nil); }
+                     List::Cons(_, ref mut rest) => push(&mut **rest, n),
+                 }
+             }
+             let mut l = List::Nil;
+             push(&mut l, 1);
+             push(&mut l, 2);
+             push(&mut l, 3);
+             (sum(&l), l)",
+        "enum Opt { None, Some(box int) }
+             let mut o = Opt::Some(box 5);
+             let mut n = match o { Opt::Some(b) => *b, Opt::None => 0 };
+             let mut m = match Opt::Some(box 6) { Opt::Some(c) => *c, _ => 0 };
+             n + m",
+    ];
+    for src in programs {
+        assert!(agree(&elaborate(src)).is_ok(), "{}", src);
+    }
+}
+
+#[test]
+fn runtime_errors_agree() {
+    assert_eq!(
+        agree(&elaborate("fn f(n: int) -> int { n * n * n } f(2000)")),
+        Err(RuntimeError::Overflow(BinOp::Mul, 4000000, 2000))
+    );
+    assert_eq!(
+        agree(&elaborate("let mut z = 0; 1 + 4 / z")),
+        Err(RuntimeError::DivisionByZero(BinOp::Div, 4))
+    );
+    assert_eq!(
+        agree(&parse_program("let mut x = 1; g(x)").unwrap()),
+        Err(RuntimeError::UnknownFn(String::from("g")))
+    );
+    assert_eq!(
+        agree(&parse_program("fn f(x: int) -> int { *x } f(1)").unwrap()),
+        Err(RuntimeError::BadDeref(Lval::new("x", 1)))
+    );
+    assert_eq!(
+        agree(&parse_program("fn f(x: int) -> int { x + x } f(1)").unwrap()),
+        Err(RuntimeError::UninitializedRead(Lval::new("x", 0)))
+    );
+    let no_arm = Expr::Match(
+        Box::new(Expr::Int(1)),
+        vec![(
+            Pattern::Variant(String::from("E"), String::from("A"), vec![]),
+            Expr::Int(2),
+        )],
+        Lifetime(1),
+    );
+    assert_eq!(
+        agree(&no_arm),
+        Err(RuntimeError::NoArmMatches(Value::Int(1)))
+    );
+}
+
+#[test]
+fn recursion_limits_agree() {
+    let depth = |n| {
+        elaborate(&format!(
+            "fn f(n: int) -> int {{ if n == 0 {{ 0 }} else {{ 1 + f(n - 1) }} }} f({})",
+            n
+        ))
+    };
+    for (n, expected) in [
+        (4, Ok(Value::Int(4))),
+        (5, Err(RuntimeError::RecursionLimit(5))),
+    ] {
+        let mut limited = Context {
+            max_depth: 5,
+            ..Context::default()
+        };
+        let e = depth(n);
+        assert_eq!(limited.eval_expr(&e, &Lifetime::global()), expected);
+        assert_eq!(vm::compile(&e).run_with_limit(5), expected);
+    }
+
+    // the default limit stops the tree-walker before the host's stack
+    // would overflow, given a stack of the size the interpreter runs on
+    let deep = std::thread::Builder::new()
+        .stack_size(256 << 20)
+        .spawn(move || agree(&depth(100000)))
+        .unwrap();
+    assert_eq!(
+        deep.join().unwrap(),
+        Err(RuntimeError::RecursionLimit(MAX_DEPTH))
+    );
+}